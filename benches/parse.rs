@@ -5,7 +5,7 @@ use bencher::Bencher;
 
 extern crate parsip;
 
-const REQ: &'static [u8] = b"\
+const REQ: &[u8] = b"\
 INVITE sip:bob@biloxi.com SIP/2.0\r\n\
 Via: SIP/2.0/UDP bigbox3.site3.atlanta.com;branch=z9hG4bK77ef4c2312983.1\r\n\
 Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashds8;received=192.0.2.1\r\n\
@@ -18,19 +18,46 @@ Contact: <sip:alice@pc33.atlanta.com>\r\n\
 Content-Type: application/sdp\r\n\
 Content-Length: 0\r\n\r\n";
 
+/// Mostly headers outside of the hot path, with long token and text runs.
+const REQ_HEADERS: &[u8] = b"\
+SUBSCRIBE sip:presence-agent@example.com;transport=tcp SIP/2.0\r\n\
+Via: SIP/2.0/TCP client.example.com:5060;branch=z9hG4bK74bf9;rport\r\n\
+Max-Forwards: 70\r\n\
+To: <sip:presence-agent@example.com>\r\n\
+From: \"Alice Liddell\" <sip:alice@example.com>;tag=xfg9\r\n\
+Call-ID: 2xTb9vxSit55XU7p8@example.com\r\n\
+CSeq: 17766 SUBSCRIBE\r\n\
+User-Agent: Example-Presence-Client/4.2.1 (Embedded Linux; build 20231104)\r\n\
+Accept: application/pidf+xml, application/rlmi+xml, multipart/related\r\n\
+Accept-Encoding: identity, gzip\r\n\
+Allow: INVITE, ACK, CANCEL, OPTIONS, BYE, REFER, SUBSCRIBE, NOTIFY, MESSAGE\r\n\
+Supported: eventlist, replaces, norefersub, timer, 100rel, path, gruu\r\n\
+Event: presence;id=0b3d4e\r\n\
+Expires: 3600\r\n\
+P-Preferred-Identity: \"Alice Liddell\" <sip:alice@example.com>\r\n\
+Subject: A rather long subject line describing the purpose of this\r\n \
+subscription, folded onto a second line\r\n\
+Content-Length: 0\r\n\r\n";
 
 
 fn bench_parsip_request(b: &mut Bencher) {
-    let mut headers = [parsip::Header {
-        name: "",
-        value: &[],
-    }; 16];
+    let mut headers = [parsip::EMPTY_HEADER; 16];
     let mut req = parsip::Request::new(&mut headers);
     b.iter(|| {
-               assert_eq!(req.parse(REQ), parsip::IResult::Done(&b""[..], REQ.len()));
+               assert_eq!(req.parse(REQ), Ok(parsip::Status::Complete(REQ.len())));
            });
     b.bytes = REQ.len() as u64;
 }
 
-benchmark_group!(benches, bench_parsip_request);
+fn bench_parsip_request_headers(b: &mut Bencher) {
+    let mut headers = [parsip::EMPTY_HEADER; 32];
+    let mut req = parsip::Request::new(&mut headers);
+    b.iter(|| {
+               assert_eq!(req.parse(REQ_HEADERS),
+                          Ok(parsip::Status::Complete(REQ_HEADERS.len())));
+           });
+    b.bytes = REQ_HEADERS.len() as u64;
+}
+
+benchmark_group!(benches, bench_parsip_request, bench_parsip_request_headers);
 benchmark_main!(benches);
@@ -3,7 +3,7 @@ import sys
 
 def generate_lookup_table(name, func, n=256, align=16):
     assert(isinstance(n, int))
-    FIRST_LINE = 'static {}: [bool; {}] = byte_map!['.format(name, n)
+    FIRST_LINE = 'const {}: ByteSet = byte_map!['.format(name)
     LAST_LINE = '];'
     print(FIRST_LINE, end='')
     for i in range(n):
@@ -50,10 +50,11 @@ hostname         =  *( domainlabel "." ) toplabel [ "." ]
 domainlabel      =  alphanum
                     / alphanum *( alphanum / "-" ) alphanum
 toplabel         =  ALPHA / ALPHA *( alphanum / "-" ) alphanum
+IPv6reference    =  "[" IPv6address "]"
 '''
 def is_request_uri(b):
     return is_alphanum(b) or \
-        (b in b"!$%&'()*+,-./:;=?@_~")
+        (b in b"!$%&'()*+,-./:;=?@[]_~")
 
 
 ''' RFC 3261
@@ -112,7 +113,7 @@ def is_header_value(b):
     return is_TEXT_UTF8char(b) or \
         is_UTF8_CONT(b) or \
         is_LWS(b) or \
-        (b in b'\x00\x07\x7F') # NUL BEL DEL - not sure if valid
+        (b in b'\x07\x7F') # BEL DEL - not sure if valid
 
 
 GENERATORS = {
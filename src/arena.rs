@@ -0,0 +1,209 @@
+//! Copying parsed messages into a caller-provided arena.
+//!
+//! Parsed messages borrow the receive buffer. Copying them into a scratch
+//! arena (ie. one per call or per transaction) releases the receive buffer
+//! for the next read, while keeping the zero-copy views: every slice of the
+//! copied message borrows the arena instead.
+
+use std::{mem, str};
+use sip::{Header, HeaderValue, Request, Response};
+
+/// A bump allocator over a caller-provided byte buffer.
+///
+/// Allocations are never freed individually; the whole arena is released
+/// when the underlying buffer is.
+///
+/// # Example
+///
+/// ```
+/// use parsip::arena::Arena;
+///
+/// let mut storage = [0u8; 256];
+/// let mut arena = Arena::new(&mut storage);
+/// let hello = arena.alloc_str("hello").unwrap();
+/// assert_eq!(hello, "hello");
+/// assert_eq!(arena.remaining(), 251);
+/// ```
+#[derive(Debug)]
+pub struct Arena<'a> {
+    free: &'a mut [u8],
+}
+
+impl<'a> Arena<'a> {
+    /// Creates a new arena allocating from `buf`.
+    #[inline]
+    pub fn new(buf: &'a mut [u8]) -> Arena<'a> {
+        Arena { free: buf }
+    }
+
+    /// Number of bytes still available.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Copies `bytes` into the arena.
+    ///
+    /// Returns `None` if there is not enough room left.
+    pub fn alloc_bytes(&mut self, bytes: &[u8]) -> Option<&'a [u8]> {
+        if bytes.len() > self.free.len() {
+            return None;
+        }
+        let free = mem::take(&mut self.free);
+        let (head, tail) = free.split_at_mut(bytes.len());
+        head.copy_from_slice(bytes);
+        self.free = tail;
+        Some(head)
+    }
+
+    /// Copies `s` into the arena.
+    ///
+    /// Returns `None` if there is not enough room left.
+    #[inline]
+    pub fn alloc_str(&mut self, s: &str) -> Option<&'a str> {
+        self.alloc_bytes(s.as_bytes()).and_then(|bytes| str::from_utf8(bytes).ok())
+    }
+
+    /// Copies `headers` into the arena, storing them in `dst`.
+    ///
+    /// Returns `None` if there is not enough room left in the arena or in
+    /// `dst`.
+    pub fn alloc_headers<'h>(&mut self,
+                             headers: &[Header],
+                             dst: &'h mut [Header<'a>])
+                             -> Option<&'h mut [Header<'a>]> {
+        if headers.len() > dst.len() {
+            return None;
+        }
+        let dst = &mut dst[..headers.len()];
+        for (to, from) in dst.iter_mut().zip(headers) {
+            *to = Header {
+                name: self.alloc_str(from.name)?,
+                value: HeaderValue::from_parsed(self.alloc_bytes(from.value.as_bytes())?),
+            };
+        }
+        Some(dst)
+    }
+}
+
+impl<'h, 'b> Request<'h, 'b> {
+    /// Copies this request into `arena`, storing its headers in `headers`.
+    ///
+    /// Returns `None` if there is not enough room left in the arena or in
+    /// `headers`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use parsip::arena::Arena;
+    ///
+    /// let mut storage = [0u8; 1024];
+    /// let mut arena = Arena::new(&mut storage);
+    /// let mut copied_headers = [parsip::EMPTY_HEADER; 8];
+    ///
+    /// let copy = {
+    ///     let buf = b"BYE sip:bob@biloxi.com SIP/2.0\r\nCall-ID: a84b4c76e66710\r\n\r\n".to_vec();
+    ///     let mut headers = [parsip::EMPTY_HEADER; 8];
+    ///     let mut req = parsip::Request::new(&mut headers);
+    ///     req.parse(&buf).unwrap();
+    ///     req.copy_into(&mut arena, &mut copied_headers).unwrap()
+    /// };
+    /// assert_eq!(copy.method, Some("BYE"));
+    /// assert_eq!(copy.headers[0].value, b"a84b4c76e66710");
+    /// ```
+    pub fn copy_into<'h2, 'a>(&self,
+                              arena: &mut Arena<'a>,
+                              headers: &'h2 mut [Header<'a>])
+                              -> Option<Request<'h2, 'a>> {
+        Some(Request {
+            method: copy_str(arena, self.method)?,
+            path: copy_str(arena, self.path)?,
+            version: self.version,
+            headers: arena.alloc_headers(self.headers, headers)?,
+            body: copy_bytes(arena, self.body)?,
+        })
+    }
+}
+
+impl<'h, 'b> Response<'h, 'b> {
+    /// Copies this response into `arena`, storing its headers in `headers`.
+    ///
+    /// Returns `None` if there is not enough room left in the arena or in
+    /// `headers`.
+    pub fn copy_into<'h2, 'a>(&self,
+                              arena: &mut Arena<'a>,
+                              headers: &'h2 mut [Header<'a>])
+                              -> Option<Response<'h2, 'a>> {
+        Some(Response {
+            version: self.version,
+            code: self.code,
+            reason: copy_str(arena, self.reason)?,
+            headers: arena.alloc_headers(self.headers, headers)?,
+            body: copy_bytes(arena, self.body)?,
+        })
+    }
+}
+
+/// Copies an optional string, failing only if the arena is exhausted.
+#[inline]
+fn copy_str<'a>(arena: &mut Arena<'a>, s: Option<&str>) -> Option<Option<&'a str>> {
+    match s {
+        Some(s) => arena.alloc_str(s).map(Some),
+        None => Some(None),
+    }
+}
+
+/// Copies an optional body, failing only if the arena is exhausted.
+#[inline]
+fn copy_bytes<'a>(arena: &mut Arena<'a>, bytes: Option<&[u8]>) -> Option<Option<&'a [u8]>> {
+    match bytes {
+        Some(bytes) => arena.alloc_bytes(bytes).map(Some),
+        None => Some(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sip::{Response, EMPTY_HEADER};
+    use super::Arena;
+
+    #[test]
+    fn test_arena_exhausted() {
+        let mut storage = [0u8; 4];
+        let mut arena = Arena::new(&mut storage);
+        assert_eq!(arena.alloc_bytes(b"abc"), Some(&b"abc"[..]));
+        assert_eq!(arena.alloc_bytes(b"de"), None);
+        assert_eq!(arena.alloc_bytes(b"d"), Some(&b"d"[..]));
+        assert_eq!(arena.remaining(), 0);
+    }
+
+    #[test]
+    fn test_response_copy_into() {
+        let mut storage = [0u8; 64];
+        let mut arena = Arena::new(&mut storage);
+        let mut copied = [EMPTY_HEADER; 2];
+        let buf = b"SIP/2.0 180 Ringing\r\nCSeq: 1 INVITE\r\n\r\n".to_vec();
+        let mut headers = [EMPTY_HEADER; 2];
+        let mut res = Response::new(&mut headers);
+        res.parse(&buf).unwrap();
+
+        let copy = res.copy_into(&mut arena, &mut copied).unwrap();
+        drop(buf);
+        assert_eq!(copy.code, Some(180));
+        assert_eq!(copy.reason, Some("Ringing"));
+        assert_eq!(copy.headers.len(), 1);
+        assert_eq!(copy.headers[0].name, "CSeq");
+        assert_eq!(copy.headers[0].value, b"1 INVITE");
+    }
+
+    #[test]
+    fn test_response_copy_into_too_many_headers() {
+        let mut storage = [0u8; 64];
+        let mut arena = Arena::new(&mut storage);
+        let buf = b"SIP/2.0 200 OK\r\nA: 1\r\nB: 2\r\n\r\n";
+        let mut headers = [EMPTY_HEADER; 2];
+        let mut res = Response::new(&mut headers);
+        res.parse(buf).unwrap();
+        assert!(res.copy_into(&mut arena, &mut [EMPTY_HEADER; 1]).is_none());
+    }
+}
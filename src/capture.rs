@@ -0,0 +1,184 @@
+//! Best-effort field extraction from captured traffic.
+//!
+//! Passive monitoring sees truncated datagrams, messages cut at a snap
+//! length and plain garbage. `extract` salvages the fields that identify
+//! a call and transaction from whatever is there, never failing.
+
+use std::str;
+use headers::names_eq;
+use lookup::is_token;
+use memchr::memchr;
+use scan;
+
+/// Fields extracted by `extract`, when present and readable.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fields<'b> {
+    /// The request method.
+    pub method: Option<&'b str>,
+    /// The response status code.
+    pub code: Option<u16>,
+    /// The Call-ID header value.
+    pub call_id: Option<&'b [u8]>,
+    /// The From header value.
+    pub from: Option<&'b [u8]>,
+    /// The To header value.
+    pub to: Option<&'b [u8]>,
+    /// The CSeq header value.
+    pub cseq: Option<&'b [u8]>,
+    /// The first element of the top Via header.
+    pub via: Option<&'b [u8]>,
+    /// `true` if the buffer ends before the end of the header section, so
+    /// the last field found may be cut short.
+    pub truncated: bool,
+}
+
+/// Extracts the key fields of the message at the start of `buf`.
+///
+/// Unreadable lines are skipped, and only the first line of folded header
+/// values is kept. When the first line is not a start line, it is tried as
+/// a header, as captures may begin in the middle of a message.
+///
+/// # Example
+///
+/// ```
+/// use parsip::capture::extract;
+///
+/// let fields = extract(b"SIP/2.0 180 Ringing\r\n\
+///                        \x00\x01garbage\r\n\
+///                        i: a84b4c76e66710\r\n\
+///                        CSeq: 314159 INV");
+/// assert_eq!(fields.code, Some(180));
+/// assert_eq!(fields.call_id, Some(&b"a84b4c76e66710"[..]));
+/// assert_eq!(fields.cseq, Some(&b"314159 INV"[..]));
+/// assert!(fields.truncated);
+/// ```
+pub fn extract<'b>(buf: &'b [u8]) -> Fields<'b> {
+    let mut fields = Fields::default();
+    let start = buf.iter().position(|&b| b != b'\r' && b != b'\n').unwrap_or(buf.len());
+    let mut rest = &buf[start..];
+    let mut first = true;
+    loop {
+        let (line, next) = match memchr(b'\n', rest) {
+            Some(lf) => (&rest[..lf], Some(&rest[lf + 1..])),
+            None => (rest, None),
+        };
+        let line = match line {
+            [line @ .., b'\r'] => line,
+            line => line,
+        };
+        if line.is_empty() && next.is_some() {
+            return fields;
+        }
+        if !(first && start_line(line, &mut fields)) {
+            header(line, &mut fields);
+        }
+        first = false;
+        match next {
+            Some(next) => rest = next,
+            None => {
+                fields.truncated = true;
+                return fields;
+            }
+        }
+    }
+}
+
+/// Reads a request or status line into `fields`, returning `false` if
+/// `line` is neither.
+fn start_line<'b>(line: &'b [u8], fields: &mut Fields<'b>) -> bool {
+    let space = match memchr(b' ', line) {
+        Some(space) => space,
+        None => return false,
+    };
+    let (first, rest) = (&line[..space], &line[space + 1..]);
+    if first.len() >= 4 && first[..4].eq_ignore_ascii_case(b"SIP/") {
+        match rest {
+            [a, b, c, ..] if a.is_ascii_digit() && b.is_ascii_digit() && c.is_ascii_digit() => {
+                fields.code = Some((a - b'0') as u16 * 100 + (b - b'0') as u16 * 10 +
+                                   (c - b'0') as u16);
+                true
+            }
+            _ => false,
+        }
+    } else if !first.is_empty() && first.iter().all(|&b| is_token(b)) &&
+              !rest.starts_with(b" ") {
+        // tokens are ASCII
+        fields.method = str::from_utf8(first).ok();
+        true
+    } else {
+        false
+    }
+}
+
+/// Reads a header line into `fields`, keeping the first value of each
+/// field.
+fn header<'b>(line: &'b [u8], fields: &mut Fields<'b>) {
+    let colon = match memchr(b':', line) {
+        Some(colon) => colon,
+        None => return,
+    };
+    let name = scan::trim(&line[..colon]);
+    let name = match str::from_utf8(name) {
+        Ok(name) if !name.is_empty() && name.bytes().all(is_token) => name,
+        _ => return,
+    };
+    let value = scan::trim(&line[colon + 1..]);
+    let field = if names_eq(name, "Call-ID") {
+        &mut fields.call_id
+    } else if names_eq(name, "From") {
+        &mut fields.from
+    } else if names_eq(name, "To") {
+        &mut fields.to
+    } else if names_eq(name, "CSeq") {
+        &mut fields.cseq
+    } else if names_eq(name, "Via") {
+        &mut fields.via
+    } else {
+        return;
+    };
+    if field.is_none() && !value.is_empty() {
+        *field = Some(if names_eq(name, "Via") { scan::split_first(value).0 } else { value });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract, Fields};
+
+    #[test]
+    fn test_extract_complete() {
+        let fields = extract(b"\r\nINVITE sip:bob@biloxi.com SIP/2.0\r\n\
+                               v: SIP/2.0/UDP p1.example.com;branch=z9hG4bK1, SIP/2.0/UDP a\r\n\
+                               Via: SIP/2.0/UDP b\r\n\
+                               f: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+                               To: Bob <sip:bob@biloxi.com>\r\n\
+                               Call-ID: a84b4c76e66710\r\n\
+                               CSeq: 314159 INVITE\r\n\r\n\
+                               Call-ID: body");
+        assert_eq!(fields,
+                   Fields {
+                       method: Some("INVITE"),
+                       code: None,
+                       call_id: Some(b"a84b4c76e66710"),
+                       from: Some(b"Alice <sip:alice@atlanta.com>;tag=1928301774"),
+                       to: Some(b"Bob <sip:bob@biloxi.com>"),
+                       cseq: Some(b"314159 INVITE"),
+                       via: Some(b"SIP/2.0/UDP p1.example.com;branch=z9hG4bK1"),
+                       truncated: false,
+                   });
+    }
+
+    #[test]
+    fn test_extract_damaged() {
+        let fields = extract(b"ll-ID: cut\r\nTo: <sip:bob@biloxi.com>\r\n\xff\xfe: x\r\nFrom");
+        assert_eq!(fields.method, None);
+        assert_eq!(fields.call_id, None);
+        assert_eq!(fields.to, Some(&b"<sip:bob@biloxi.com>"[..]));
+        assert_eq!(fields.from, None);
+        assert!(fields.truncated);
+
+        let fields = extract(b"SIP/2.0 2");
+        assert_eq!(fields.code, None);
+        assert_eq!(extract(b""), Fields { truncated: true, ..Fields::default() });
+    }
+}
@@ -0,0 +1,108 @@
+//! Cheap classification of datagrams before parsing.
+//!
+//! UDP servers frequently share a single port between SIP and other
+//! protocols (SigComp, STUN, media). Looking at the first byte is enough to
+//! route such packets away without running the SIP parser on them.
+
+use lookup::is_token;
+
+/// The kind of payload a datagram most likely carries.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Datagram {
+    /// The datagram looks like a SIP request or response.
+    Sip,
+    /// The datagram consists only of `CR` and `LF` bytes.
+    ///
+    /// See [RFC 5626](https://tools.ietf.org/html/rfc5626#section-3.5.1)
+    /// for the `CRLF` keep-alive mechanism.
+    Keepalive,
+    /// A SigComp-compressed message, first byte in `0xF8..=0xFF`.
+    ///
+    /// See [RFC 3320](https://tools.ietf.org/html/rfc3320#section-7).
+    SigComp,
+    /// A STUN message, first byte in `0..=3`.
+    Stun,
+    /// A DTLS record, a content type in `20..=25` followed by the `0xFE`
+    /// major version byte.
+    Dtls,
+    /// An RTP or RTCP packet, first byte in `128..=191`.
+    Rtp,
+    /// The datagram is empty.
+    Empty,
+    /// Anything else, certainly not SIP.
+    Unknown,
+}
+
+/// Classifies a datagram by looking at its first bytes.
+///
+/// This is a heuristic: a `Datagram::Sip` result only means that the
+/// payload is worth handing to the parser, not that it is valid.
+/// Demultiplexing of the non-SIP ranges follows
+/// [RFC 7983](https://tools.ietf.org/html/rfc7983#section-7), except that
+/// DTLS is narrowed to the defined record content types: the rest of its
+/// `20..=63` range holds digits and token symbols a SIP method may start
+/// with.
+///
+/// # Example
+///
+/// ```
+/// use parsip::classify::{classify, Datagram};
+///
+/// assert_eq!(classify(b"OPTIONS sip:carol@chicago.com SIP/2.0\r\n"), Datagram::Sip);
+/// assert_eq!(classify(b"\r\n\r\n"), Datagram::Keepalive);
+/// assert_eq!(classify(&[0xF8, 0x00, 0x01]), Datagram::SigComp);
+/// ```
+pub fn classify(buf: &[u8]) -> Datagram {
+    let first = match buf.first() {
+        Some(&b) => b,
+        None => return Datagram::Empty,
+    };
+    match first {
+        b'\r' | b'\n' => {
+            match buf.iter().find(|&&b| b != b'\r' && b != b'\n') {
+                None => Datagram::Keepalive,
+                Some(&b) if is_token(b) => Datagram::Sip,
+                Some(_) => Datagram::Unknown,
+            }
+        }
+        0xF8..=0xFF => Datagram::SigComp,
+        0..=3 => Datagram::Stun,
+        20..=25 if buf.get(1) == Some(&0xFE) => Datagram::Dtls,
+        128..=191 => Datagram::Rtp,
+        b if is_token(b) => Datagram::Sip,
+        _ => Datagram::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify, Datagram};
+
+    #[test]
+    fn test_classify_sip() {
+        assert_eq!(classify(b"INVITE sip:bob@biloxi.com SIP/2.0\r\n"), Datagram::Sip);
+        assert_eq!(classify(b"SIP/2.0 200 OK\r\n"), Datagram::Sip);
+        assert_eq!(classify(b"\r\nSIP/2.0 200 OK\r\n"), Datagram::Sip);
+        let intmeth = include_bytes!("../tests/requests/intmeth.dat");
+        assert_eq!(classify(intmeth), Datagram::Sip);
+        assert_eq!(classify(b"123 sip:bob@biloxi.com SIP/2.0\r\n"), Datagram::Sip);
+    }
+
+    #[test]
+    fn test_classify_keepalive() {
+        assert_eq!(classify(b"\r\n\r\n"), Datagram::Keepalive);
+        assert_eq!(classify(b"\r\n"), Datagram::Keepalive);
+    }
+
+    #[test]
+    fn test_classify_other() {
+        assert_eq!(classify(b""), Datagram::Empty);
+        assert_eq!(classify(&[0xFF, 0x12]), Datagram::SigComp);
+        assert_eq!(classify(&[0x00, 0x01, 0x00, 0x00]), Datagram::Stun);
+        assert_eq!(classify(&[22, 0xFE, 0xFD]), Datagram::Dtls);
+        assert_eq!(classify(&[0x80, 0x00]), Datagram::Rtp);
+        assert_eq!(classify(&[22, 0x03, 0x03]), Datagram::Unknown);
+        assert_eq!(classify(b"{}"), Datagram::Unknown);
+        assert_eq!(classify(b"\r\n\x00"), Datagram::Unknown);
+    }
+}
@@ -0,0 +1,174 @@
+//! Keys correlating the messages of a call.
+//!
+//! CDR and tracing systems group messages by call, dialog or transaction.
+//! A `CorrelationKey` hashes the identifiers involved into a fixed-size
+//! value that is cheap to store, compare and index.
+
+use std::fmt;
+use headers::names_eq;
+use rewrite::Fnv;
+use scan;
+use sip::Header;
+
+/// Which identifiers go into a `CorrelationKey`, besides the Call-ID.
+///
+/// # Example
+///
+/// ```
+/// use parsip::correlation::{CorrelationKey, KeyConfig};
+///
+/// let mut config = KeyConfig::new();
+/// config.tags(true);
+///
+/// let buf = b"BYE sip:alice@192.0.2.4 SIP/2.0\r\n\
+///             From: <sip:bob@biloxi.com>;tag=a6c85cf\r\n\
+///             To: <sip:alice@atlanta.com>;tag=1928301774\r\n\
+///             Call-ID: a84b4c76e66710\r\n\r\n";
+/// let mut headers = [parsip::EMPTY_HEADER; 8];
+/// let mut req = parsip::Request::new(&mut headers);
+/// req.parse(buf).unwrap();
+///
+/// let key = CorrelationKey::new(req.headers, &config).unwrap();
+/// println!("dialog {}", key);
+/// ```
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct KeyConfig {
+    tags: bool,
+    branch: bool,
+}
+
+impl KeyConfig {
+    /// Creates a configuration keying on the Call-ID alone.
+    #[inline]
+    pub const fn new() -> KeyConfig {
+        KeyConfig {
+            tags: false,
+            branch: false,
+        }
+    }
+
+    /// Sets whether the From and To tags are included, keying dialogs.
+    ///
+    /// The tags are combined regardless of their order, so requests sent
+    /// in either direction of a dialog get the same key. Requests and
+    /// responses sent before the dialog is established lack the To tag,
+    /// and get another key.
+    #[inline]
+    pub const fn tags(&mut self, value: bool) -> &mut Self {
+        self.tags = value;
+        self
+    }
+
+    /// Sets whether the branch of the top Via is included, keying
+    /// transactions.
+    #[inline]
+    pub const fn branch(&mut self, value: bool) -> &mut Self {
+        self.branch = value;
+        self
+    }
+}
+
+/// A 64-bit hash of the identifiers of a call, dialog or transaction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CorrelationKey(u64);
+
+impl CorrelationKey {
+    /// Computes the key of a message from its headers.
+    ///
+    /// Returns `None` if the message has no Call-ID. Missing tags and
+    /// branches hash as empty.
+    pub fn new(headers: &[Header], config: &KeyConfig) -> Option<CorrelationKey> {
+        let call_id = find(headers, "Call-ID").map(scan::trim).filter(|id| !id.is_empty())?;
+        let mut hash = Fnv::new();
+        hash.write(call_id);
+        if config.tags {
+            let from = find(headers, "From").and_then(tag).unwrap_or(b"");
+            let to = find(headers, "To").and_then(tag).unwrap_or(b"");
+            let (a, b) = if from <= to { (from, to) } else { (to, from) };
+            hash.write(a);
+            hash.write(b);
+        }
+        if config.branch {
+            let branch = find(headers, "Via")
+                .and_then(|via| {
+                    let (_, params) = scan::split_addr(scan::split_first(via).0);
+                    scan::param(params, b"branch")
+                })
+                .unwrap_or(b"");
+            hash.write(branch);
+        }
+        Some(CorrelationKey(hash.0))
+    }
+
+    /// The key as an integer.
+    #[inline]
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for CorrelationKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl From<CorrelationKey> for u64 {
+    #[inline]
+    fn from(key: CorrelationKey) -> u64 {
+        key.0
+    }
+}
+
+/// Value of the first header called `name`.
+fn find<'b>(headers: &[Header<'b>], name: &str) -> Option<&'b [u8]> {
+    headers.iter().find(|header| names_eq(header.name, name)).map(|header| header.value.as_bytes())
+}
+
+/// The `tag` parameter of a From or To value.
+fn tag(value: &[u8]) -> Option<&[u8]> {
+    scan::param(scan::split_addr(value).1, b"tag")
+}
+
+#[cfg(test)]
+mod tests {
+    use sip::{Header, HeaderValue};
+    use super::{CorrelationKey, KeyConfig};
+
+    const FROM_CALLER: [Header; 4] = [
+        Header::new("Via", HeaderValue::from_static(b"SIP/2.0/UDP a;branch=z9hG4bK1")),
+        Header::new("From", HeaderValue::from_static(b"<sip:a@x>;tag=1")),
+        Header::new("To", HeaderValue::from_static(b"<sip:b@y>;tag=2")),
+        Header::new("Call-ID", HeaderValue::from_static(b"abc@x")),
+    ];
+
+    const FROM_CALLEE: [Header; 4] = [
+        Header::new("v", HeaderValue::from_static(b"SIP/2.0/UDP b;branch=z9hG4bK2")),
+        Header::new("f", HeaderValue::from_static(b"\"B\" <sip:b@y;tag=9>;tag=2")),
+        Header::new("t", HeaderValue::from_static(b"sip:a@x;tag=1")),
+        Header::new("i", HeaderValue::from_static(b"abc@x")),
+    ];
+
+    #[test]
+    fn test_dialog_key_is_symmetric() {
+        let call = KeyConfig::new();
+        let mut dialog = KeyConfig::new();
+        dialog.tags(true);
+        let mut transaction = KeyConfig::new();
+        transaction.tags(true).branch(true);
+
+        assert_eq!(CorrelationKey::new(&FROM_CALLER, &call),
+                   CorrelationKey::new(&FROM_CALLEE, &call));
+        assert_eq!(CorrelationKey::new(&FROM_CALLER, &dialog),
+                   CorrelationKey::new(&FROM_CALLEE, &dialog));
+        assert_ne!(CorrelationKey::new(&FROM_CALLER, &call),
+                   CorrelationKey::new(&FROM_CALLER, &dialog));
+        assert_ne!(CorrelationKey::new(&FROM_CALLER, &transaction),
+                   CorrelationKey::new(&FROM_CALLEE, &transaction));
+    }
+
+    #[test]
+    fn test_missing_call_id() {
+        assert_eq!(CorrelationKey::new(&FROM_CALLER[..3], &KeyConfig::new()), None);
+    }
+}
@@ -0,0 +1,187 @@
+//! Receiving messages from an `embedded_io::Read` source.
+//!
+//! `MessageReader` pulls bytes from a stream transport into a fixed,
+//! caller-provided buffer, skips keepalives, frames messages by their
+//! `Content-Length` and drives the parser, so bare-metal firmware gets a
+//! complete receive path without std.
+
+use embedded_io::Read;
+use framing::{self, Frame};
+use sip::{Header, Message, Request, Response, Status};
+
+pub use framing::ReadError;
+
+/// A message received by a `MessageReader`, along with its body.
+#[derive(Debug, PartialEq)]
+pub enum Received<'h, 'b: 'h> {
+    /// A request and its body.
+    Request(Request<'h, 'b>, &'b [u8]),
+    /// A response and its body.
+    Response(Response<'h, 'b>, &'b [u8]),
+}
+
+/// Reads messages from an `embedded_io::Read` source into a fixed buffer.
+///
+/// # Example
+///
+/// ```
+/// use parsip::embedded::{MessageReader, Received};
+///
+/// let stream: &[u8] = b"\r\n\r\nSIP/2.0 200 OK\r\nContent-Length: 2\r\n\r\nhi";
+/// let mut buf = [0u8; 512];
+/// let mut reader = MessageReader::new(stream, &mut buf);
+///
+/// let mut headers = [parsip::EMPTY_HEADER; 16];
+/// match reader.read_message(&mut headers).unwrap() {
+///     Received::Response(res, body) => {
+///         assert_eq!(res.code, Some(200));
+///         assert_eq!(body, b"hi");
+///     }
+///     Received::Request(..) => unreachable!(),
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MessageReader<'a, R> {
+    reader: R,
+    buf: &'a mut [u8],
+    start: usize,
+    end: usize,
+}
+
+impl<'a, R: Read> MessageReader<'a, R> {
+    /// Creates a reader buffering into `buf`, which bounds the size of a
+    /// single message.
+    #[inline]
+    pub fn new(reader: R, buf: &'a mut [u8]) -> MessageReader<'a, R> {
+        MessageReader {
+            reader,
+            buf,
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// Returns the underlying source.
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads the next message, storing its headers in `headers`.
+    ///
+    /// Blocks until a complete message has been received.
+    pub fn read_message<'s, 'h>(&'s mut self,
+                                headers: &'h mut [Header<'s>])
+                                -> Result<Received<'h, 's>, ReadError<R::Error>> {
+        let len = self.fill_message()?;
+        let start = self.start;
+        self.start += len;
+        let msg = &self.buf[start..start + len];
+        match Message::parse(headers, msg) {
+            Ok(Status::Complete((Message::Request(req), head))) => {
+                Ok(Received::Request(req, &msg[head..]))
+            }
+            Ok(Status::Complete((Message::Response(res), head))) => {
+                Ok(Received::Response(res, &msg[head..]))
+            }
+            Ok(Status::Partial(_)) => Err(ReadError::Parse(framing::truncated(msg))),
+            Err(e) => Err(ReadError::Parse(e)),
+        }
+    }
+
+    /// Reads until the buffer holds a complete message at `start`, and
+    /// returns its length.
+    fn fill_message(&mut self) -> Result<usize, ReadError<R::Error>> {
+        loop {
+            self.start += framing::keepalive_len(&self.buf[self.start..self.end]);
+            match framing::frame(&self.buf[self.start..self.end]) {
+                Frame::Complete(len) => return Ok(len),
+                Frame::Invalid => return Err(ReadError::ContentLength),
+                Frame::Conflicting => return Err(ReadError::ConflictingContentLength),
+                Frame::Partial => {}
+            }
+            if self.start > 0 {
+                self.buf.copy_within(self.start..self.end, 0);
+                self.end -= self.start;
+                self.start = 0;
+            }
+            if self.end == self.buf.len() {
+                return Err(ReadError::BufferFull);
+            }
+            match self.reader.read(&mut self.buf[self.end..]) {
+                Ok(0) => return Err(ReadError::Closed),
+                Ok(n) => self.end += n,
+                Err(e) => return Err(ReadError::Io(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use embedded_io::{ErrorType, Read};
+    use sip::EMPTY_HEADER;
+    use super::{MessageReader, ReadError, Received};
+
+    /// Hands out its bytes one at a time.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> ErrorType for Trickle<'a> {
+        type Error = Infallible;
+    }
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            match self.0.split_first() {
+                Some((&b, rest)) if !buf.is_empty() => {
+                    buf[0] = b;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    const STREAM: &[u8] = b"OPTIONS sip:carol@chicago.com SIP/2.0\r\nCSeq: 1 OPTIONS\r\n\r\n\
+                            \r\n\r\n\
+                            MESSAGE sip:bob@biloxi.com SIP/2.0\r\nl: 5\r\n\r\nhello";
+
+    #[test]
+    fn test_read_messages_in_pieces() {
+        let mut buf = [0u8; 64];
+        let mut reader = MessageReader::new(Trickle(STREAM), &mut buf);
+        {
+            let mut headers = [EMPTY_HEADER; 4];
+            match reader.read_message(&mut headers).unwrap() {
+                Received::Request(req, body) => {
+                    assert_eq!(req.method, Some("OPTIONS"));
+                    assert_eq!(req.headers.len(), 1);
+                    assert_eq!(body, b"");
+                }
+                received => panic!("unexpected {:?}", received),
+            }
+        }
+        {
+            let mut headers = [EMPTY_HEADER; 4];
+            match reader.read_message(&mut headers).unwrap() {
+                Received::Request(req, body) => {
+                    assert_eq!(req.method, Some("MESSAGE"));
+                    assert_eq!(body, b"hello");
+                }
+                received => panic!("unexpected {:?}", received),
+            }
+        }
+        let mut headers = [EMPTY_HEADER; 4];
+        assert_eq!(reader.read_message(&mut headers), Err(ReadError::Closed));
+    }
+
+    #[test]
+    fn test_read_message_buffer_full() {
+        let mut buf = [0u8; 32];
+        let mut reader = MessageReader::new(STREAM, &mut buf);
+        let mut headers = [EMPTY_HEADER; 4];
+        assert_eq!(reader.read_message(&mut headers), Err(ReadError::BufferFull));
+    }
+}
@@ -0,0 +1,433 @@
+//! Serializing requests and responses built in memory.
+//!
+//! Unlike `rewrite`, which copies most of a message from the buffer it was
+//! parsed from, these functions write every part of a `Request` or
+//! `Response` from its fields, so messages can be built from scratch, for
+//! example a `491 Request Pending` answering a parsed request.
+//!
+//! The start line is written from its fields, a missing version being
+//! written as `SIP/2.0`. Headers are written in order as `name: value`
+//! lines, except `Content-Length` and its compact form `l`, which are
+//! replaced by a single `Content-Length` giving the length of the body.
+//! Every line ends with CRLF. Header names are written as given, or in
+//! their canonical form with `EncodeConfig::canonical_names`.
+//!
+//! # Example
+//!
+//! ```
+//! use parsip::{Request, Response, EMPTY_HEADER};
+//!
+//! let buf = b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+//!             Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+//!             Call-ID: a84b4c76e66710\r\n\
+//!             CSeq: 314159 INVITE\r\n\
+//!             Content-Length: 0\r\n\r\n";
+//! let mut headers = [EMPTY_HEADER; 8];
+//! let mut req = Request::new(&mut headers);
+//! assert!(req.parse(buf).unwrap().is_complete());
+//!
+//! let mut copied = [EMPTY_HEADER; 3];
+//! for (slot, name) in copied.iter_mut().zip(&["Via", "Call-ID", "CSeq"]) {
+//!     *slot = *req.headers.iter().find(|h| h.name == *name).unwrap();
+//! }
+//! let mut res = Response::new(&mut copied);
+//! res.code = Some(491);
+//! res.reason = Some("Request Pending");
+//!
+//! let mut out = [0u8; 256];
+//! let len = res.encode_into(&mut out).unwrap();
+//! assert_eq!(&out[..len], &b"SIP/2.0 491 Request Pending\r\n\
+//!     Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+//!     Call-ID: a84b4c76e66710\r\n\
+//!     CSeq: 314159 INVITE\r\n\
+//!     Content-Length: 0\r\n\r\n"[..]);
+//! ```
+
+#[cfg(feature = "std")]
+use std::io;
+use headers::{canonical_name, names_eq};
+use lookup::{is_reason_phrase, is_request_uri, is_token};
+use rewrite::{Writer, WriteError};
+use sip::{Header, Request, Response, SipVersion};
+
+/// Encoder configuration.
+///
+/// # Example
+///
+/// ```
+/// use parsip::encode::EncodeConfig;
+/// use parsip::{Header, HeaderValue, Response};
+///
+/// let mut headers = [Header::new("i", HeaderValue::from_static(b"a84b4c76e66710"))];
+/// let mut res = Response::new(&mut headers);
+/// res.code = Some(200);
+/// res.reason = Some("OK");
+///
+/// let mut config = EncodeConfig::new();
+/// config.canonical_names(true);
+/// let mut buf = [0u8; 64];
+/// let len = res.encode_into_with(&config, &mut buf).unwrap();
+/// assert_eq!(&buf[..len], &b"SIP/2.0 200 OK\r\n\
+///                           Call-ID: a84b4c76e66710\r\n\
+///                           Content-Length: 0\r\n\r\n"[..]);
+/// ```
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct EncodeConfig {
+    canonical_names: bool,
+}
+
+impl EncodeConfig {
+    /// Creates the default configuration, usable in const contexts.
+    #[inline]
+    pub const fn new() -> EncodeConfig {
+        EncodeConfig { canonical_names: false }
+    }
+
+    /// Sets whether header names are written in their canonical form, see
+    /// `headers::canonical_name`, so `call-id` and `i` are both written as
+    /// `Call-ID`.
+    ///
+    /// Default is `false`, writing names as given.
+    #[inline]
+    pub const fn canonical_names(&mut self, value: bool) -> &mut Self {
+        self.canonical_names = value;
+        self
+    }
+}
+
+/// Destination of an encoded message.
+trait Sink {
+    type Error: From<WriteError>;
+
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl<'a> Sink for Writer<'a> {
+    type Error = WriteError;
+
+    #[inline]
+    fn put(&mut self, bytes: &[u8]) -> Result<(), WriteError> {
+        self.write(bytes)
+    }
+}
+
+#[cfg(feature = "std")]
+struct IoSink<'w, W: io::Write + 'w>(&'w mut W);
+
+#[cfg(feature = "std")]
+impl<'w, W: io::Write> Sink for IoSink<'w, W> {
+    type Error = io::Error;
+
+    #[inline]
+    fn put(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.0.write_all(bytes)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<WriteError> for io::Error {
+    fn from(error: WriteError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, error)
+    }
+}
+
+impl<'h, 'b> Request<'h, 'b> {
+    /// Writes the request into `buf`, returning the number of bytes written.
+    ///
+    /// The method and path must be set. See the [module](index.html)
+    /// documentation for how the message is laid out.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use parsip::{Header, HeaderValue, Request};
+    ///
+    /// let mut headers = [
+    ///     Header::new("Call-ID", HeaderValue::from_static(b"a84b4c76e66710")),
+    ///     Header::new("CSeq", HeaderValue::from_static(b"314159 ACK")),
+    /// ];
+    /// let mut req = Request::new(&mut headers);
+    /// req.method = Some("ACK");
+    /// req.path = Some("sip:bob@192.0.2.4");
+    ///
+    /// let mut buf = [0u8; 128];
+    /// let len = req.encode_into(&mut buf).unwrap();
+    /// assert_eq!(&buf[..len], &b"ACK sip:bob@192.0.2.4 SIP/2.0\r\n\
+    ///                           Call-ID: a84b4c76e66710\r\n\
+    ///                           CSeq: 314159 ACK\r\n\
+    ///                           Content-Length: 0\r\n\r\n"[..]);
+    /// ```
+    #[inline]
+    pub fn encode_into(&self, buf: &mut [u8]) -> Result<usize, WriteError> {
+        self.encode_into_with(&EncodeConfig::default(), buf)
+    }
+
+    /// Writes the request into `buf`, using the given `EncodeConfig`.
+    pub fn encode_into_with(&self, config: &EncodeConfig, buf: &mut [u8])
+                            -> Result<usize, WriteError> {
+        let mut writer = Writer::new(buf);
+        encode_request(self, config, &mut writer)?;
+        Ok(writer.len())
+    }
+
+    /// Writes the request to `out`.
+    ///
+    /// Invalid requests fail with an `InvalidInput` error wrapping a
+    /// `WriteError`, possibly after part of the request was written.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn write_to<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        self.write_to_with(&EncodeConfig::default(), out)
+    }
+
+    /// Writes the request to `out`, using the given `EncodeConfig`.
+    #[cfg(feature = "std")]
+    pub fn write_to_with<W: io::Write>(&self, config: &EncodeConfig, out: &mut W)
+                                       -> io::Result<()> {
+        encode_request(self, config, &mut IoSink(out))
+    }
+}
+
+impl<'h, 'b> Response<'h, 'b> {
+    /// Writes the response into `buf`, returning the number of bytes written.
+    ///
+    /// The code must be set; a missing reason-phrase is written empty. See
+    /// the [module](index.html) documentation for how the message is laid
+    /// out.
+    #[inline]
+    pub fn encode_into(&self, buf: &mut [u8]) -> Result<usize, WriteError> {
+        self.encode_into_with(&EncodeConfig::default(), buf)
+    }
+
+    /// Writes the response into `buf`, using the given `EncodeConfig`.
+    pub fn encode_into_with(&self, config: &EncodeConfig, buf: &mut [u8])
+                            -> Result<usize, WriteError> {
+        let mut writer = Writer::new(buf);
+        encode_response(self, config, &mut writer)?;
+        Ok(writer.len())
+    }
+
+    /// Writes the response to `out`.
+    ///
+    /// Invalid responses fail with an `InvalidInput` error wrapping a
+    /// `WriteError`, possibly after part of the response was written.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn write_to<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        self.write_to_with(&EncodeConfig::default(), out)
+    }
+
+    /// Writes the response to `out`, using the given `EncodeConfig`.
+    #[cfg(feature = "std")]
+    pub fn write_to_with<W: io::Write>(&self, config: &EncodeConfig, out: &mut W)
+                                       -> io::Result<()> {
+        encode_response(self, config, &mut IoSink(out))
+    }
+}
+
+fn encode_request<S: Sink>(req: &Request,
+                           config: &EncodeConfig,
+                           out: &mut S)
+                           -> Result<(), S::Error> {
+    let method = req.method.ok_or(WriteError::Incomplete)?;
+    let path = req.path.ok_or(WriteError::Incomplete)?;
+    if method.is_empty() || !method.bytes().all(is_token) {
+        return Err(WriteError::Method.into());
+    }
+    if path.is_empty() || !path.bytes().all(is_request_uri) {
+        return Err(WriteError::RequestUri.into());
+    }
+    out.put(method.as_bytes())?;
+    out.put(b" ")?;
+    out.put(path.as_bytes())?;
+    out.put(b" ")?;
+    encode_version(req.version, out)?;
+    out.put(b"\r\n")?;
+    encode_rest(req.headers, req.body, config, out)
+}
+
+fn encode_response<S: Sink>(res: &Response,
+                            config: &EncodeConfig,
+                            out: &mut S)
+                            -> Result<(), S::Error> {
+    let code = res.code.ok_or(WriteError::Incomplete)?;
+    let reason = res.reason.unwrap_or("");
+    if !(100..=699).contains(&code) || !reason.bytes().all(is_reason_phrase) {
+        return Err(WriteError::Status.into());
+    }
+    encode_version(res.version, out)?;
+    out.put(b" ")?;
+    let mut digits = [0u8; 20];
+    out.put(decimal(code as usize, &mut digits))?;
+    out.put(b" ")?;
+    out.put(reason.as_bytes())?;
+    out.put(b"\r\n")?;
+    encode_rest(res.headers, res.body, config, out)
+}
+
+fn encode_version<S: Sink>(version: Option<SipVersion>, out: &mut S) -> Result<(), S::Error> {
+    let SipVersion(major, minor) = version.unwrap_or(SipVersion::SIP_2_0);
+    let mut digits = [0u8; 20];
+    out.put(b"SIP/")?;
+    out.put(decimal(major as usize, &mut digits))?;
+    out.put(b".")?;
+    out.put(decimal(minor as usize, &mut digits))
+}
+
+/// Writes the header lines, the Content-Length, the empty line and the body.
+fn encode_rest<S: Sink>(headers: &[Header],
+                        body: Option<&[u8]>,
+                        config: &EncodeConfig,
+                        out: &mut S)
+                        -> Result<(), S::Error> {
+    for header in headers {
+        if names_eq(header.name, "Content-Length") {
+            continue;
+        }
+        if header.name.is_empty() || !header.name.bytes().all(is_token) {
+            return Err(WriteError::HeaderName.into());
+        }
+        let name = if config.canonical_names {
+            canonical_name(header.name)
+        } else {
+            header.name
+        };
+        out.put(name.as_bytes())?;
+        out.put(b": ")?;
+        out.put(&header.value)?;
+        out.put(b"\r\n")?;
+    }
+    let body = body.unwrap_or(b"");
+    let mut digits = [0u8; 20];
+    out.put(b"Content-Length: ")?;
+    out.put(decimal(body.len(), &mut digits))?;
+    out.put(b"\r\n\r\n")?;
+    out.put(body)
+}
+
+/// Formats `value` in decimal into the end of `digits`.
+fn decimal(mut value: usize, digits: &mut [u8; 20]) -> &[u8] {
+    let mut start = digits.len();
+    loop {
+        start -= 1;
+        digits[start] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            return &digits[start..];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rewrite::WriteError;
+    use sip::{Header, HeaderValue, Request, Response, SipVersion, Status, EMPTY_HEADER};
+    use super::EncodeConfig;
+
+    #[test]
+    fn test_encode_request_round_trip() {
+        let mut headers = [
+            Header::new("Via", HeaderValue::from_static(b"SIP/2.0/TCP h.example.com")),
+            Header::new("l", HeaderValue::from_static(b"99")),
+            Header::new("Content-Type", HeaderValue::from_static(b"text/plain")),
+        ];
+        let mut req = Request::new(&mut headers);
+        req.method = Some("MESSAGE");
+        req.path = Some("sip:bob@example.com");
+        req.body = Some(b"hello");
+
+        let mut buf = [0u8; 128];
+        let len = req.encode_into(&mut buf).unwrap();
+        assert_eq!(&buf[..len],
+                   &b"MESSAGE sip:bob@example.com SIP/2.0\r\n\
+                      Via: SIP/2.0/TCP h.example.com\r\n\
+                      Content-Type: text/plain\r\n\
+                      Content-Length: 5\r\n\r\nhello"[..]);
+
+        let mut parsed_headers = [EMPTY_HEADER; 4];
+        let mut parsed = Request::new(&mut parsed_headers);
+        assert_eq!(parsed.parse_message(&buf[..len]), Ok(Status::Complete(len)));
+        assert_eq!(parsed.method, Some("MESSAGE"));
+        assert_eq!(parsed.version, Some(SipVersion::SIP_2_0));
+        assert_eq!(parsed.body, Some(&b"hello"[..]));
+    }
+
+    #[test]
+    fn test_encode_response() {
+        let mut headers = [EMPTY_HEADER; 0];
+        let mut res = Response::new(&mut headers);
+        res.code = Some(100);
+        let mut buf = [0u8; 64];
+        let len = res.encode_into(&mut buf).unwrap();
+        assert_eq!(&buf[..len], &b"SIP/2.0 100 \r\nContent-Length: 0\r\n\r\n"[..]);
+        assert_eq!(res.encode_into(&mut buf[..len - 1]), Err(WriteError::BufferTooSmall));
+
+        res.code = Some(700);
+        assert_eq!(res.encode_into(&mut buf), Err(WriteError::Status));
+        res.code = Some(180);
+        res.reason = Some("Ringing\r\n");
+        assert_eq!(res.encode_into(&mut buf), Err(WriteError::Status));
+    }
+
+    #[test]
+    fn test_encode_canonical_names() {
+        let mut headers = [
+            Header::new("call-id", HeaderValue::from_static(b"1")),
+            Header::new("i", HeaderValue::from_static(b"2")),
+            Header::new("X-custom", HeaderValue::from_static(b"3")),
+        ];
+        let mut req = Request::new(&mut headers);
+        req.method = Some("OPTIONS");
+        req.path = Some("sip:bob@example.com");
+
+        let mut config = EncodeConfig::new();
+        config.canonical_names(true);
+        let mut buf = [0u8; 128];
+        let len = req.encode_into_with(&config, &mut buf).unwrap();
+        assert_eq!(&buf[..len],
+                   &b"OPTIONS sip:bob@example.com SIP/2.0\r\n\
+                      Call-ID: 1\r\n\
+                      Call-ID: 2\r\n\
+                      X-custom: 3\r\n\
+                      Content-Length: 0\r\n\r\n"[..]);
+        let len = req.encode_into(&mut buf).unwrap();
+        assert!(buf[..len].ends_with(b"\r\ncall-id: 1\r\ni: 2\r\nX-custom: 3\r\n\
+                                       Content-Length: 0\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_encode_invalid_request() {
+        let mut headers = [EMPTY_HEADER; 1];
+        let mut req = Request::new(&mut headers);
+        let mut buf = [0u8; 64];
+        assert_eq!(req.encode_into(&mut buf), Err(WriteError::Incomplete));
+        req.method = Some("BYE now");
+        req.path = Some("sip:bob@example.com");
+        assert_eq!(req.encode_into(&mut buf), Err(WriteError::Method));
+        req.method = Some("BYE");
+        req.path = Some("sip:bob@example.com x");
+        assert_eq!(req.encode_into(&mut buf), Err(WriteError::RequestUri));
+        req.path = Some("sip:bob@example.com");
+        assert_eq!(req.encode_into(&mut buf), Err(WriteError::HeaderName));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_write_to() {
+        use std::io;
+
+        let mut headers = [Header::new("CSeq", HeaderValue::from_static(b"1 ACK"))];
+        let mut req = Request::new(&mut headers);
+        req.method = Some("ACK");
+        req.path = Some("sip:bob@example.com");
+        let mut out = Vec::new();
+        req.write_to(&mut out).unwrap();
+        let mut buf = [0u8; 128];
+        let len = req.encode_into(&mut buf).unwrap();
+        assert_eq!(&out[..], &buf[..len]);
+
+        req.path = None;
+        let err = req.write_to(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
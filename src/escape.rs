@@ -0,0 +1,165 @@
+//! Decoding of `%xx` escaped sequences, as found in the user part of URIs
+//! and in reason-phrases.
+//!
+//! > ```notrust
+//! > escaped     =  "%" HEXDIG HEXDIG
+//! > ```
+//!
+//! # Example
+//!
+//! ```
+//! use parsip::escape::{unescape_into, EscapeError};
+//!
+//! let mut buf = [0u8; 16];
+//! assert_eq!(unescape_into(b"sip%3Auser", &mut buf), Ok("sip:user"));
+//! assert_eq!(unescape_into(b"100%", &mut buf), Err(EscapeError::Malformed));
+//! assert_eq!(unescape_into(b"%FF", &mut buf), Err(EscapeError::Utf8));
+//! ```
+
+use std::fmt;
+use std::str;
+
+/// Errors in decoding escaped sequences.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EscapeError {
+    /// A `%` is not followed by two hexadecimal digits.
+    Malformed,
+    /// The output buffer is too small.
+    BufferTooSmall,
+    /// The decoded bytes are not valid UTF-8.
+    Utf8,
+}
+
+impl EscapeError {
+    /// A short description of the error.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            EscapeError::Malformed => "malformed escaped sequence",
+            EscapeError::BufferTooSmall => "output buffer too small",
+            EscapeError::Utf8 => "unescaped bytes are not valid UTF-8",
+        }
+    }
+}
+
+impl fmt::Display for EscapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for EscapeError {}
+
+/// Returns the value of a hexadecimal digit.
+#[inline]
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Calls `put` with every byte of `src`, its escaped sequences decoded.
+#[inline]
+fn unescape_with<F>(src: &[u8], mut put: F) -> Result<(), EscapeError>
+    where F: FnMut(u8) -> Result<(), EscapeError>
+{
+    let mut idx = 0;
+    while idx < src.len() {
+        let b = match src[idx] {
+            b'%' => {
+                let digit = |idx| src.get(idx).cloned().and_then(hex_value);
+                match (digit(idx + 1), digit(idx + 2)) {
+                    (Some(hi), Some(lo)) => {
+                        idx += 3;
+                        hi << 4 | lo
+                    }
+                    _ => return Err(EscapeError::Malformed),
+                }
+            }
+            b => {
+                idx += 1;
+                b
+            }
+        };
+        put(b)?;
+    }
+    Ok(())
+}
+
+/// Decodes all escaped sequences of `src` into `dst`, returning the bytes
+/// written.
+///
+/// Unlike `unescape_into`, the result may hold any byte, such as the `NUL`
+/// of `%00`.
+pub fn unescape_bytes_into<'d>(src: &[u8], dst: &'d mut [u8]) -> Result<&'d [u8], EscapeError> {
+    let mut len = 0;
+    unescape_with(src, |b| {
+        *dst.get_mut(len).ok_or(EscapeError::BufferTooSmall)? = b;
+        len += 1;
+        Ok(())
+    })?;
+    Ok(&dst[..len])
+}
+
+/// Decodes all escaped sequences of `src` into `dst`, returning the
+/// decoded text.
+pub fn unescape_into<'d>(src: &[u8], dst: &'d mut [u8]) -> Result<&'d str, EscapeError> {
+    let bytes = unescape_bytes_into(src, dst)?;
+    str::from_utf8(bytes).map_err(|_| EscapeError::Utf8)
+}
+
+/// Decodes all escaped sequences of `src` into a new `String`.
+///
+/// # Example
+///
+/// ```
+/// assert_eq!(parsip::escape::unescape(b"Not%20Found").unwrap(), "Not Found");
+/// ```
+#[cfg(feature = "std")]
+pub fn unescape(src: &[u8]) -> Result<String, EscapeError> {
+    let mut bytes = Vec::with_capacity(src.len());
+    unescape_with(src, |b| {
+        bytes.push(b);
+        Ok(())
+    })?;
+    String::from_utf8(bytes).map_err(|_| EscapeError::Utf8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unescape_bytes_into, unescape_into, EscapeError};
+
+    #[test]
+    fn test_unescape_into() {
+        let mut buf = [0u8; 16];
+        assert_eq!(unescape_into(b"Not%20Found", &mut buf), Ok("Not Found"));
+        assert_eq!(unescape_into(b"%c3%A9", &mut buf), Ok("é"));
+        assert_eq!(unescape_bytes_into(b"null-%00-null", &mut buf), Ok(&b"null-\0-null"[..]));
+    }
+
+    #[test]
+    fn test_unescape_into_malformed() {
+        let mut buf = [0u8; 16];
+        assert_eq!(unescape_into(b"100%", &mut buf), Err(EscapeError::Malformed));
+        assert_eq!(unescape_into(b"%4", &mut buf), Err(EscapeError::Malformed));
+        assert_eq!(unescape_into(b"%zz", &mut buf), Err(EscapeError::Malformed));
+        assert_eq!(unescape_into(b"%c3", &mut buf), Err(EscapeError::Utf8));
+    }
+
+    #[test]
+    fn test_unescape_into_small_buffer() {
+        let mut buf = [0u8; 2];
+        assert_eq!(unescape_into(b"abc", &mut buf), Err(EscapeError::BufferTooSmall));
+        assert_eq!(unescape_into(b"%61bc", &mut buf[..0]), Err(EscapeError::BufferTooSmall));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_unescape() {
+        assert_eq!(super::unescape(b"%E3%81%B2"), Ok("ひ".to_string()));
+        assert_eq!(super::unescape(b"%e"), Err(EscapeError::Malformed));
+    }
+}
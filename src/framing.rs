@@ -0,0 +1,285 @@
+//! Locating message boundaries on stream transports.
+//!
+//! Over TCP and other streams, a message ends after the blank line closing
+//! its header section plus the number of body bytes given by its
+//! `Content-Length` header, see
+//! [RFC 3261](https://tools.ietf.org/html/rfc3261#section-18.3).
+
+use memchr::memchr;
+use headers::names_eq;
+use scan::{self, LengthError};
+use sip::{parse_headers_iter_with, Error, ErrorKind, ParserConfig};
+
+/// Errors of the adapters reading messages from a stream.
+///
+/// After any error other than `Parse` the message boundaries are lost, and
+/// the connection should be closed.
+#[derive(Debug, PartialEq)]
+pub enum ReadError<E> {
+    /// The source failed.
+    Io(E),
+    /// The source reached its end before a complete message.
+    Closed,
+    /// A message does not fit in the buffer.
+    BufferFull,
+    /// The `Content-Length` header of a message is malformed.
+    ContentLength,
+    /// A message has several `Content-Length` values that differ.
+    ///
+    /// Picking either would let a crafted message smuggle another one in
+    /// its body.
+    ConflictingContentLength,
+    /// A complete message could not be parsed; it has been skipped.
+    Parse(Error),
+}
+
+/// Where the first message of a stream buffer ends.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Frame {
+    /// The buffer holds a complete message of the given length.
+    Complete(usize),
+    /// More bytes are needed.
+    Partial,
+    /// The header section or its `Content-Length` header is malformed, so
+    /// the message boundary is unknown.
+    Invalid,
+    /// Several `Content-Length` values differ, so the message boundary is
+    /// ambiguous.
+    Conflicting,
+}
+
+/// Number of leading keepalive `CRLF`s (or bare `LF`s) in `buf`.
+///
+/// See [RFC 5626](https://tools.ietf.org/html/rfc5626#section-3.5.1).
+#[inline]
+pub fn keepalive_len(buf: &[u8]) -> usize {
+    buf.iter().position(|&b| b != b'\r' && b != b'\n').unwrap_or(buf.len())
+}
+
+/// Finds the end of the first message in `buf`, which must not start with
+/// keepalives.
+pub fn frame(buf: &[u8]) -> Frame {
+    let head = match head_len(buf) {
+        Some(head) => head,
+        None => return Frame::Partial,
+    };
+    let body = match content_length(&buf[..head]) {
+        Ok(body) => body.unwrap_or(0),
+        Err(frame) => return frame,
+    };
+    match head.checked_add(body) {
+        Some(len) if len <= buf.len() => Frame::Complete(len),
+        Some(_) => Frame::Partial,
+        None => Frame::Invalid,
+    }
+}
+
+/// Splits the complete messages pipelined in the buffer of a stream
+/// connection, without parsing their start lines.
+///
+/// Yields each message, start line to end of body, skipping the
+/// keepalives between them; `remainder` holds the bytes of the partial
+/// message following them, to keep for the next read. A malformed header
+/// section, or a malformed or conflicting `Content-Length`, is reported as an
+/// `ErrorKind::ContentLength` error at the start of the message, after
+/// which the boundaries are lost and nothing more is yielded.
+///
+/// # Example
+///
+/// ```
+/// use parsip::framing::MessageScanner;
+///
+/// let buf = b"MESSAGE sip:bob@biloxi.com SIP/2.0\r\nl: 2\r\n\r\nhi\
+///             \r\n\r\nSIP/2.0 200 OK\r\nl: 0\r\n\r\nSIP/2.0 1";
+/// let mut scanner = MessageScanner::new(buf);
+/// assert_eq!(scanner.next(), Some(Ok(&buf[..46])));
+/// assert_eq!(scanner.next(), Some(Ok(&buf[50..74])));
+/// assert_eq!(scanner.next(), None);
+/// assert_eq!(scanner.remainder(), b"SIP/2.0 1");
+///
+/// let mut headers = [parsip::EMPTY_HEADER; 4];
+/// let mut req = parsip::Request::new(&mut headers);
+/// assert!(req.parse_message(&buf[..46]).unwrap().is_complete());
+/// ```
+#[derive(Clone, Debug)]
+pub struct MessageScanner<'b> {
+    buf: &'b [u8],
+    offset: usize,
+    failed: bool,
+}
+
+impl<'b> MessageScanner<'b> {
+    /// Creates a scanner over the bytes received on a connection.
+    #[inline]
+    pub fn new(buf: &'b [u8]) -> MessageScanner<'b> {
+        MessageScanner {
+            buf,
+            offset: 0,
+            failed: false,
+        }
+    }
+
+    /// The bytes following the messages yielded so far.
+    #[inline]
+    pub fn remainder(&self) -> &'b [u8] {
+        &self.buf[self.offset..]
+    }
+}
+
+impl<'b> Iterator for MessageScanner<'b> {
+    type Item = Result<&'b [u8], Error>;
+
+    fn next(&mut self) -> Option<Result<&'b [u8], Error>> {
+        if self.failed {
+            return None;
+        }
+        self.offset += keepalive_len(&self.buf[self.offset..]);
+        let start = self.offset;
+        match frame(&self.buf[start..]) {
+            Frame::Complete(len) => {
+                self.offset += len;
+                Some(Ok(&self.buf[start..self.offset]))
+            }
+            Frame::Partial => None,
+            Frame::Invalid | Frame::Conflicting => {
+                self.failed = true;
+                Some(Err(Error::new(ErrorKind::ContentLength, start)))
+            }
+        }
+    }
+}
+
+/// Length of the start line and header section, including the blank line
+/// closing it.
+fn head_len(buf: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+    while let Some(lf) = memchr(b'\n', &buf[pos..]) {
+        let next = pos + lf + 1;
+        match buf.get(next..) {
+            Some([b'\n', ..]) => return Some(next + 1),
+            Some([b'\r', b'\n', ..]) => return Some(next + 2),
+            _ => pos = next,
+        }
+    }
+    None
+}
+
+/// The error for a framed message whose head the parser found incomplete:
+/// the message ends where a new line is required.
+#[cfg(any(feature = "embedded-io", feature = "async"))]
+#[cold]
+pub(crate) fn truncated(msg: &[u8]) -> Error {
+    Error::new(ErrorKind::NewLine, msg.len())
+}
+
+/// Parses the header sections found by `head_len`, which may end lines with
+/// a bare `LF`.
+const HEAD_CONFIG: ParserConfig = {
+    let mut config = ParserConfig::new();
+    config.allow_lf_line_endings(true);
+    config
+};
+
+/// Value of the `Content-Length` headers of `head`.
+///
+/// The headers are found by the parser, so folded lines and header values
+/// cannot pass for a `Content-Length` the parser does not see.
+///
+/// Returns `Ok(None)` if there is none, `Err(Frame::Invalid)` if one, or
+/// the header section, is malformed, and `Err(Frame::Conflicting)` if
+/// repeated headers or comma-separated values differ.
+fn content_length(head: &[u8]) -> Result<Option<usize>, Frame> {
+    let start = memchr(b'\n', head).map_or(head.len(), |lf| lf + 1);
+    let mut malformed = false;
+    let values = parse_headers_iter_with(&head[start..], &HEAD_CONFIG)
+        .map_while(|header| {
+            malformed |= header.is_err();
+            header.ok()
+        })
+        .filter(|header| names_eq(header.name, "Content-Length"))
+        .map(|header| header.value.as_bytes());
+    let length = scan::content_length(values);
+    if malformed {
+        return Err(Frame::Invalid);
+    }
+    length.map_err(|error| match error {
+        LengthError::Invalid => Frame::Invalid,
+        LengthError::Conflicting => Frame::Conflicting,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sip::{Error, ErrorKind, Request, Status, EMPTY_HEADER};
+    use super::{frame, keepalive_len, Frame, MessageScanner};
+
+    #[test]
+    fn test_frame() {
+        let buf = b"MESSAGE sip:a SIP/2.0\r\nl: 5\r\n\r\nhelloOPTIONS";
+        assert_eq!(frame(buf), Frame::Complete(36));
+        assert_eq!(frame(&buf[..35]), Frame::Partial);
+        assert_eq!(frame(&buf[..30]), Frame::Partial);
+    }
+
+    #[test]
+    fn test_frame_folded_content_length() {
+        // a folded line is part of the Subject, not a Content-Length
+        let buf = b"MESSAGE sip:a SIP/2.0\r\nSubject: x\r\n l: 5\r\n\r\n\
+                    helloOPTIONS sip:b SIP/2.0\r\n\r\n";
+        assert_eq!(frame(buf), Frame::Complete(44));
+        // the parser finds no Content-Length either, taking the rest of the
+        // buffer as the body, as for a datagram
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        assert_eq!(req.parse_message(buf), Ok(Status::Complete(buf.len())));
+        assert_eq!(req.body, Some(&buf[44..]));
+        assert_eq!(req.parse_message(&buf[..44]), Ok(Status::Complete(44)));
+
+        assert_eq!(frame(b"SIP/2.0 200 OK\r\nTo\r\nl: 1\r\n\r\nx"), Frame::Invalid);
+    }
+
+    #[test]
+    fn test_frame_without_content_length() {
+        assert_eq!(frame(b"SIP/2.0 200 OK\r\nTo: <sip:b>\r\n\r\nx"), Frame::Complete(31));
+        assert_eq!(frame(b"SIP/2.0 200 OK\n\n"), Frame::Complete(16));
+    }
+
+    #[test]
+    fn test_frame_invalid_content_length() {
+        assert_eq!(frame(b"SIP/2.0 200 OK\r\nContent-Length: x\r\n\r\n"), Frame::Invalid);
+        assert_eq!(frame(b"SIP/2.0 200 OK\r\nContent-Length: 99999999999999999999999\r\n\r\n"),
+                   Frame::Invalid);
+    }
+
+    #[test]
+    fn test_frame_conflicting_content_length() {
+        assert_eq!(frame(b"MESSAGE sip:a SIP/2.0\r\nl: 5\r\nContent-Length: 30\r\n\r\nhello"),
+                   Frame::Conflicting);
+        assert_eq!(frame(b"MESSAGE sip:a SIP/2.0\r\nContent-Length: 5, 0\r\n\r\nhello"),
+                   Frame::Conflicting);
+        assert_eq!(frame(b"MESSAGE sip:a SIP/2.0\r\nl: 5\r\nContent-Length: 005\r\n\r\nhello"),
+                   Frame::Complete(57));
+        assert_eq!(frame(b"MESSAGE sip:a SIP/2.0\r\nContent-Length: 5,\r\n\r\nhello"),
+                   Frame::Invalid);
+    }
+
+    #[test]
+    fn test_message_scanner() {
+        let buf = b"\r\nSIP/2.0 200 OK\r\n\r\nSIP/2.0 180 Ringing\r\nl: 1\r\n\r\n";
+        let mut scanner = MessageScanner::new(buf);
+        assert_eq!(scanner.next(), Some(Ok(&buf[2..20])));
+        assert_eq!(scanner.next(), None);
+        assert_eq!(scanner.remainder(), &buf[20..]);
+
+        let buf = b"SIP/2.0 200 OK\r\n\r\n\r\nSIP/2.0 200 OK\r\nl: x\r\n\r\nSIP/2.0 200 OK\r\n\r\n";
+        let messages: Vec<_> = MessageScanner::new(buf).collect();
+        assert_eq!(messages, [Ok(&buf[..18]), Err(Error::new(ErrorKind::ContentLength, 20))]);
+    }
+
+    #[test]
+    fn test_keepalive_len() {
+        assert_eq!(keepalive_len(b"\r\n\r\nINVITE"), 4);
+        assert_eq!(keepalive_len(b"\r\n"), 2);
+        assert_eq!(keepalive_len(b"SIP/2.0"), 0);
+    }
+}
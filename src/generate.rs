@@ -0,0 +1,305 @@
+//! Generating families of valid messages for load tests and benchmarks.
+//!
+//! A `Scenario` describes the shape of the messages, and a `Generator`
+//! writes an endless sequence of them, varying identifiers, tags and
+//! filler content from a seed so runs can be reproduced.
+
+use lookup::is_token;
+use rewrite::{WriteError, Writer};
+
+/// The shape of the Request-URI and the addresses in generated messages.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UriShape {
+    /// `sip:user@example.com`.
+    Simple,
+    /// A SIP URI with a port and parameters.
+    WithParams,
+    /// A SIPS URI with an IP address and a port.
+    Sips,
+    /// A `tel` URI.
+    Tel,
+}
+
+/// The shape of the messages a `Generator` writes.
+///
+/// # Example
+///
+/// ```
+/// use parsip::generate::{Generator, Scenario, UriShape};
+/// use parsip::rewrite::Writer;
+///
+/// let mut scenario = Scenario::request("INVITE");
+/// scenario.headers(20).uri(UriShape::Sips).body_len(200);
+/// let mut generator = Generator::new(scenario, 42);
+///
+/// let mut buf = [0u8; 4096];
+/// let mut out = Writer::new(&mut buf);
+/// generator.generate(&mut out).unwrap();
+///
+/// let mut headers = [parsip::EMPTY_HEADER; 32];
+/// let mut req = parsip::Request::new(&mut headers);
+/// req.parse_message(out.written()).unwrap();
+/// assert_eq!(req.method, Some("INVITE"));
+/// assert_eq!(req.body.map(<[u8]>::len), Some(200));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Scenario<'a> {
+    method: &'a str,
+    code: u16,
+    headers: usize,
+    value_len: usize,
+    uri: UriShape,
+    body_len: usize,
+}
+
+impl<'a> Scenario<'a> {
+    /// Creates a scenario of `method` requests, with no extra headers and
+    /// no body.
+    ///
+    /// # Panics
+    ///
+    /// If `method` is not a token.
+    pub fn request(method: &'a str) -> Scenario<'a> {
+        assert!(!method.is_empty() && method.bytes().all(is_token), "invalid method");
+        Scenario {
+            method,
+            code: 0,
+            headers: 0,
+            value_len: 16,
+            uri: UriShape::Simple,
+            body_len: 0,
+        }
+    }
+
+    /// Creates a scenario of `code` responses to `method` requests, with no
+    /// extra headers and no body.
+    ///
+    /// # Panics
+    ///
+    /// If `method` is not a token, or `code` is not in `100..=699`.
+    pub fn response(code: u16, method: &'a str) -> Scenario<'a> {
+        assert!((100..=699).contains(&code), "invalid status code");
+        Scenario {
+            code,
+            ..Scenario::request(method)
+        }
+    }
+
+    /// Sets the number of extra headers, besides the mandatory ones.
+    #[inline]
+    pub fn headers(&mut self, count: usize) -> &mut Self {
+        self.headers = count;
+        self
+    }
+
+    /// Sets the length of the values of the extra headers.
+    #[inline]
+    pub fn value_len(&mut self, len: usize) -> &mut Self {
+        self.value_len = len;
+        self
+    }
+
+    /// Sets the shape of the URIs.
+    #[inline]
+    pub fn uri(&mut self, shape: UriShape) -> &mut Self {
+        self.uri = shape;
+        self
+    }
+
+    /// Sets the length of the body.
+    #[inline]
+    pub fn body_len(&mut self, len: usize) -> &mut Self {
+        self.body_len = len;
+        self
+    }
+}
+
+/// Writes messages following a `Scenario`.
+#[derive(Clone, Debug)]
+pub struct Generator<'a> {
+    scenario: Scenario<'a>,
+    state: u64,
+    cseq: u32,
+}
+
+impl<'a> Generator<'a> {
+    /// Creates a generator; the same seed generates the same messages.
+    #[inline]
+    pub fn new(scenario: Scenario<'a>, seed: u64) -> Generator<'a> {
+        Generator {
+            scenario,
+            state: seed,
+            cseq: 0,
+        }
+    }
+
+    /// Writes the next message.
+    pub fn generate(&mut self, out: &mut Writer) -> Result<(), WriteError> {
+        let scenario = self.scenario;
+        self.cseq = self.cseq.wrapping_add(1);
+        if scenario.code == 0 {
+            out.write(scenario.method.as_bytes())?;
+            out.write(b" ")?;
+            self.write_uri(out, b"bob")?;
+            out.write(b" SIP/2.0\r\n")?;
+        } else {
+            out.write(b"SIP/2.0 ")?;
+            write_number(out, scenario.code as u64)?;
+            out.write(b" Generated\r\n")?;
+        }
+
+        out.write(b"Via: SIP/2.0/UDP 192.0.2.1:5060;branch=z9hG4bK")?;
+        self.write_hex(out)?;
+        out.write(b"\r\n")?;
+        if scenario.code == 0 {
+            out.write(b"Max-Forwards: 70\r\n")?;
+        }
+        out.write(b"From: <")?;
+        self.write_uri(out, b"alice")?;
+        out.write(b">;tag=")?;
+        self.write_hex(out)?;
+        out.write(b"\r\nTo: <")?;
+        self.write_uri(out, b"bob")?;
+        out.write(b">")?;
+        if scenario.code != 0 {
+            out.write(b";tag=")?;
+            self.write_hex(out)?;
+        }
+        out.write(b"\r\nCall-ID: ")?;
+        self.write_hex(out)?;
+        out.write(b"@192.0.2.1\r\nCSeq: ")?;
+        write_number(out, self.cseq as u64)?;
+        out.write(b" ")?;
+        out.write(scenario.method.as_bytes())?;
+        out.write(b"\r\nContact: <")?;
+        self.write_uri(out, b"alice")?;
+        out.write(b">\r\n")?;
+
+        for idx in 0..scenario.headers {
+            out.write(b"X-Filler-")?;
+            write_number(out, idx as u64)?;
+            out.write(b": ")?;
+            for _ in 0..scenario.value_len {
+                out.write(&[b"abcdefghijklmnopqrstuvwxyz0123456789"[self.next() as usize % 36]])?;
+            }
+            out.write(b"\r\n")?;
+        }
+
+        out.write(b"Content-Length: ")?;
+        write_number(out, scenario.body_len as u64)?;
+        out.write(b"\r\n\r\n")?;
+        for _ in 0..scenario.body_len {
+            out.write(b"x")?;
+        }
+        Ok(())
+    }
+
+    fn write_uri(&mut self, out: &mut Writer, user: &[u8]) -> Result<(), WriteError> {
+        match self.scenario.uri {
+            UriShape::Simple => {
+                out.write(b"sip:")?;
+                out.write(user)?;
+                out.write(b"@example.com")
+            }
+            UriShape::WithParams => {
+                out.write(b"sip:")?;
+                out.write(user)?;
+                out.write(b"@example.com:5080;transport=tcp;x-id=")?;
+                self.write_hex(out)
+            }
+            UriShape::Sips => {
+                out.write(b"sips:")?;
+                out.write(user)?;
+                out.write(b"@192.0.2.4:5061")
+            }
+            UriShape::Tel => {
+                out.write(b"tel:+1555")?;
+                write_number(out, self.next() % 10_000_000)
+            }
+        }
+    }
+
+    /// Writes 16 random hex digits.
+    fn write_hex(&mut self, out: &mut Writer) -> Result<(), WriteError> {
+        let value = self.next();
+        for idx in 0..16 {
+            out.write(&[b"0123456789abcdef"[(value >> (60 - idx * 4)) as usize & 0xf]])?;
+        }
+        Ok(())
+    }
+
+    /// SplitMix64.
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Writes `value` in decimal.
+fn write_number(out: &mut Writer, mut value: u64) -> Result<(), WriteError> {
+    let mut digits = [0u8; 20];
+    let mut start = digits.len();
+    loop {
+        start -= 1;
+        digits[start] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            return out.write(&digits[start..]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sip::{Request, Response, EMPTY_HEADER};
+    use rewrite::Writer;
+    use super::{Generator, Scenario, UriShape};
+
+    #[test]
+    fn test_generated_requests_parse() {
+        for &shape in &[UriShape::Simple, UriShape::WithParams, UriShape::Sips, UriShape::Tel] {
+            let mut scenario = Scenario::request("MESSAGE");
+            scenario.headers(5).value_len(3).uri(shape).body_len(7);
+            let mut generator = Generator::new(scenario, 1);
+            for _ in 0..3 {
+                let mut buf = [0u8; 1024];
+                let mut out = Writer::new(&mut buf);
+                generator.generate(&mut out).unwrap();
+                let mut headers = [EMPTY_HEADER; 16];
+                let mut req = Request::new(&mut headers);
+                req.parse_message(out.written()).unwrap();
+                assert_eq!(req.method, Some("MESSAGE"));
+                assert_eq!(req.headers.len(), 13);
+                assert_eq!(req.headers[7].value.len(), 3);
+                assert_eq!(req.body, Some(&b"xxxxxxx"[..]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated_responses_parse() {
+        let mut generator = Generator::new(Scenario::response(486, "INVITE"), 7);
+        let mut buf = [0u8; 512];
+        let mut out = Writer::new(&mut buf);
+        generator.generate(&mut out).unwrap();
+        let mut headers = [EMPTY_HEADER; 16];
+        let mut res = Response::new(&mut headers);
+        res.parse(out.written()).unwrap().unwrap();
+        assert_eq!(res.code, Some(486));
+        assert_eq!(res.headers[4].value.as_bytes(), b"1 INVITE");
+    }
+
+    #[test]
+    fn test_same_seed_same_messages() {
+        let scenario = Scenario::request("OPTIONS");
+        let (mut a, mut b) = ([0u8; 512], [0u8; 512]);
+        let mut out_a = Writer::new(&mut a);
+        let mut out_b = Writer::new(&mut b);
+        Generator::new(scenario, 3).generate(&mut out_a).unwrap();
+        Generator::new(scenario, 3).generate(&mut out_b).unwrap();
+        assert_eq!(out_a.written(), out_b.written());
+    }
+}
@@ -0,0 +1,398 @@
+//! Helpers for working with parsed header sections.
+
+use std::slice;
+use scan;
+use sip::Header;
+
+pub mod typed;
+
+/// Compact header forms, see
+/// [RFC 3261](https://tools.ietf.org/html/rfc3261#section-7.3.3) and the
+/// [IANA registry](https://www.iana.org/assignments/sip-parameters).
+static COMPACT_FORMS: [(&str, &str); 20] = [
+    ("a", "Accept-Contact"),
+    ("b", "Referred-By"),
+    ("c", "Content-Type"),
+    ("d", "Request-Disposition"),
+    ("e", "Content-Encoding"),
+    ("f", "From"),
+    ("i", "Call-ID"),
+    ("j", "Reject-Contact"),
+    ("k", "Supported"),
+    ("l", "Content-Length"),
+    ("m", "Contact"),
+    ("n", "Identity-Info"),
+    ("o", "Event"),
+    ("r", "Refer-To"),
+    ("s", "Subject"),
+    ("t", "To"),
+    ("u", "Allow-Events"),
+    ("v", "Via"),
+    ("x", "Session-Expires"),
+    ("y", "Identity"),
+];
+
+/// Registered header names in their canonical casing.
+static KNOWN_NAMES: [&str; 74] = [
+    "Accept", "Accept-Contact", "Accept-Encoding", "Accept-Language",
+    "Accept-Resource-Priority", "Alert-Info", "Allow", "Allow-Events",
+    "Authentication-Info", "Authorization", "Call-ID", "Call-Info", "Contact",
+    "Content-Disposition", "Content-Encoding", "Content-Language",
+    "Content-Length", "Content-Type", "CSeq", "Date", "Error-Info", "Event",
+    "Expires", "From", "History-Info", "Identity", "Identity-Info",
+    "In-Reply-To", "Join", "Max-Forwards", "Min-Expires", "Min-SE",
+    "MIME-Version", "Organization", "P-Asserted-Identity",
+    "P-Preferred-Identity", "Path", "Priority", "Privacy", "Proxy-Authenticate",
+    "Proxy-Authorization", "Proxy-Require", "RAck", "Reason", "Record-Route",
+    "Refer-To", "Referred-By", "Reject-Contact", "Replaces", "Reply-To",
+    "Request-Disposition", "Require", "Resource-Priority", "Retry-After",
+    "Route", "RSeq", "Security-Client", "Security-Server", "Security-Verify",
+    "Server", "Service-Route", "Session-Expires", "SIP-ETag", "SIP-If-Match",
+    "Subject", "Subscription-State", "Supported", "Timestamp", "To",
+    "Unsupported", "User-Agent", "Via", "Warning", "WWW-Authenticate",
+];
+
+/// Expands a compact header name (ie. `v`) into its long form (`Via`).
+///
+/// Any other name is returned unchanged.
+pub fn expand_compact(name: &str) -> &str {
+    if name.len() != 1 {
+        return name;
+    }
+    COMPACT_FORMS.iter()
+        .find(|&&(compact, _)| compact.eq_ignore_ascii_case(name))
+        .map_or(name, |&(_, long)| long)
+}
+
+/// Maps any case or compact variant of a known header name to its canonical
+/// registered casing (ie. `call-id` and `i` become `Call-ID`).
+///
+/// Unknown names are returned unchanged. The encoder writes names in this
+/// form with `encode::EncodeConfig::canonical_names`.
+///
+/// # Example
+///
+/// ```
+/// use parsip::headers::canonical_name;
+///
+/// assert_eq!(canonical_name("call-id"), "Call-ID");
+/// assert_eq!(canonical_name("WWW-AUTHENTICATE"), "WWW-Authenticate");
+/// assert_eq!(canonical_name("v"), "Via");
+/// assert_eq!(canonical_name("X-Custom"), "X-Custom");
+/// ```
+pub fn canonical_name(name: &str) -> &str {
+    let name = expand_compact(name);
+    KNOWN_NAMES.iter()
+        .find(|known| known.eq_ignore_ascii_case(name))
+        .map_or(name, |&known| known)
+}
+
+/// Compares two header names case-insensitively, treating compact forms as
+/// equal to their long forms.
+///
+/// # Example
+///
+/// ```
+/// assert!(parsip::headers::names_eq("v", "VIA"));
+/// assert!(!parsip::headers::names_eq("To", "From"));
+/// ```
+pub fn names_eq(a: &str, b: &str) -> bool {
+    expand_compact(a).eq_ignore_ascii_case(expand_compact(b))
+}
+
+/// A header section, looking headers up by name.
+///
+/// Names are compared case-insensitively, and compact forms match their
+/// long forms, see `names_eq`.
+///
+/// # Example
+///
+/// ```
+/// use parsip::headers::Headers;
+///
+/// let buf = b"SIP/2.0 200 OK\r\n\
+///             Via: SIP/2.0/UDP a.example.com;branch=z9hG4bK1, SIP/2.0/UDP b.example.com\r\n\
+///             v: SIP/2.0/TCP c.example.com\r\n\
+///             Call-ID: a84b4c76e66710\r\n\r\n";
+/// let mut headers = [parsip::EMPTY_HEADER; 8];
+/// let mut res = parsip::Response::new(&mut headers);
+/// res.parse(buf).unwrap();
+///
+/// let headers = Headers::new(res.headers);
+/// assert_eq!(headers.get("i"), Some(&b"a84b4c76e66710"[..]));
+/// assert_eq!(headers.get_all("Via").count(), 2);
+/// assert_eq!(headers.values("via").collect::<Vec<_>>(),
+///            [&b"SIP/2.0/UDP a.example.com;branch=z9hG4bK1"[..],
+///             b"SIP/2.0/UDP b.example.com",
+///             b"SIP/2.0/TCP c.example.com"]);
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct Headers<'h, 'b: 'h> {
+    headers: &'h [Header<'b>],
+}
+
+impl<'h, 'b> Headers<'h, 'b> {
+    /// Wraps a parsed header section.
+    #[inline]
+    pub fn new(headers: &'h [Header<'b>]) -> Headers<'h, 'b> {
+        Headers { headers }
+    }
+
+    /// The value of the first header called `name`.
+    pub fn get(&self, name: &str) -> Option<&'b [u8]> {
+        self.get_all(name).next()
+    }
+
+    /// The values of all the headers called `name`, in order.
+    #[inline]
+    pub fn get_all<'n>(&self, name: &'n str) -> GetAll<'h, 'b, 'n> {
+        GetAll {
+            headers: self.headers.iter(),
+            name,
+        }
+    }
+
+    /// The comma-separated elements of all the headers called `name`, in
+    /// order, without surrounding whitespace.
+    ///
+    /// > ```notrust
+    /// > header  =  "header-name" HCOLON header-value *(COMMA header-value)
+    /// > ```
+    ///
+    /// Commas inside quoted strings and angle brackets do not separate
+    /// elements. Only use this for headers defined as lists: the values of
+    /// others, such as `Date` or `WWW-Authenticate`, hold commas of their
+    /// own.
+    #[inline]
+    pub fn values<'n>(&self, name: &'n str) -> Values<'h, 'b, 'n> {
+        Values {
+            headers: self.get_all(name),
+            rest: None,
+        }
+    }
+
+    /// The headers, in order.
+    #[inline]
+    pub fn iter(&self) -> slice::Iter<'h, Header<'b>> {
+        self.headers.iter()
+    }
+
+    /// The number of headers.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// Returns `true` if there are no headers.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+}
+
+/// An iterator over the values of the headers with a given name.
+///
+/// Created by `Headers::get_all`.
+#[derive(Clone, Debug)]
+pub struct GetAll<'h, 'b: 'h, 'n> {
+    headers: slice::Iter<'h, Header<'b>>,
+    name: &'n str,
+}
+
+impl<'h, 'b, 'n> Iterator for GetAll<'h, 'b, 'n> {
+    type Item = &'b [u8];
+
+    fn next(&mut self) -> Option<&'b [u8]> {
+        let name = self.name;
+        self.headers
+            .find(|header| names_eq(header.name, name))
+            .map(|header| header.value.as_bytes())
+    }
+}
+
+/// An iterator over the comma-separated elements of the headers with a
+/// given name.
+///
+/// Created by `Headers::values`.
+#[derive(Clone, Debug)]
+pub struct Values<'h, 'b: 'h, 'n> {
+    headers: GetAll<'h, 'b, 'n>,
+    rest: Option<&'b [u8]>,
+}
+
+impl<'h, 'b, 'n> Iterator for Values<'h, 'b, 'n> {
+    type Item = &'b [u8];
+
+    fn next(&mut self) -> Option<&'b [u8]> {
+        loop {
+            let value = match self.rest {
+                Some(value) => value,
+                None => self.headers.next()?,
+            };
+            let (element, rest) = scan::split_first(value);
+            self.rest = rest;
+            if !element.is_empty() {
+                return Some(element);
+            }
+        }
+    }
+}
+
+/// Groups headers by name, preserving the original order and duplicates.
+///
+/// Groups are yielded in order of the first appearance of each header
+/// name, which is reported in its canonical form; the values of each group
+/// are yielded in their original order.
+/// This is what forwarding proxies need to reconstruct or filter header
+/// sections faithfully, as the relative order of headers with the same
+/// name is significant.
+///
+/// # Example
+///
+/// ```
+/// use parsip::{Header, HeaderValue};
+///
+/// let headers = [
+///     Header { name: "Via", value: HeaderValue::from_static(b"SIP/2.0/UDP a.example.com") },
+///     Header { name: "To", value: HeaderValue::from_static(b"<sip:bob@biloxi.com>") },
+///     Header { name: "v", value: HeaderValue::from_static(b"SIP/2.0/UDP b.example.com") },
+/// ];
+/// let mut groups = parsip::headers::groups(&headers);
+///
+/// let (name, values) = groups.next().unwrap();
+/// assert_eq!(name, "Via");
+/// assert_eq!(values.collect::<Vec<_>>(),
+///            [&b"SIP/2.0/UDP a.example.com"[..], &b"SIP/2.0/UDP b.example.com"[..]]);
+///
+/// let (name, _) = groups.next().unwrap();
+/// assert_eq!(name, "To");
+/// assert!(groups.next().is_none());
+/// ```
+#[inline]
+pub fn groups<'h, 'b>(headers: &'h [Header<'b>]) -> Groups<'h, 'b> {
+    Groups {
+        headers,
+        pos: 0,
+    }
+}
+
+/// An iterator over groups of headers sharing a name.
+///
+/// Created by the `groups` function.
+#[derive(Clone, Debug)]
+pub struct Groups<'h, 'b: 'h> {
+    headers: &'h [Header<'b>],
+    pos: usize,
+}
+
+impl<'h, 'b> Iterator for Groups<'h, 'b> {
+    type Item = (&'b str, GroupValues<'h, 'b>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < self.headers.len() {
+            let (seen, rest) = self.headers.split_at(self.pos);
+            self.pos += 1;
+            let name = rest[0].name;
+            if seen.iter().any(|header| names_eq(header.name, name)) {
+                continue;
+            }
+            let values = GroupValues {
+                headers: rest,
+                name,
+            };
+            return Some((canonical_name(name), values));
+        }
+        None
+    }
+}
+
+/// An iterator over the values of a single header group.
+#[derive(Clone, Debug)]
+pub struct GroupValues<'h, 'b: 'h> {
+    headers: &'h [Header<'b>],
+    name: &'b str,
+}
+
+impl<'h, 'b> Iterator for GroupValues<'h, 'b> {
+    type Item = &'b [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((header, rest)) = self.headers.split_first() {
+            self.headers = rest;
+            if names_eq(header.name, self.name) {
+                return Some(header.value.as_bytes());
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sip::{Header, HeaderValue};
+    use super::{expand_compact, canonical_name, groups, Headers};
+
+    #[test]
+    fn test_expand_compact() {
+        assert_eq!(expand_compact("i"), "Call-ID");
+        assert_eq!(expand_compact("L"), "Content-Length");
+        assert_eq!(expand_compact("q"), "q");
+        assert_eq!(expand_compact("Subject"), "Subject");
+    }
+
+    #[test]
+    fn test_canonical_name() {
+        assert_eq!(canonical_name("cseq"), "CSeq");
+        assert_eq!(canonical_name("I"), "Call-ID");
+        assert_eq!(canonical_name("max-forwards"), "Max-Forwards");
+        assert_eq!(canonical_name("MIME-version"), "MIME-Version");
+        assert_eq!(canonical_name("NewFangledHeader"), "NewFangledHeader");
+    }
+
+    #[test]
+    fn test_groups_preserve_order() {
+        let headers = [
+            Header { name: "route", value: HeaderValue::from_static(b"<sip:a>") },
+            Header { name: "call-id", value: HeaderValue::from_static(b"x") },
+            Header { name: "ROUTE", value: HeaderValue::from_static(b"<sip:b>") },
+            Header { name: "i", value: HeaderValue::from_static(b"y") },
+            Header { name: "Route", value: HeaderValue::from_static(b"<sip:a>") },
+        ];
+        let mut it = groups(&headers);
+
+        let (name, values) = it.next().unwrap();
+        assert_eq!(name, "Route");
+        let values: Vec<&[u8]> = values.collect();
+        assert_eq!(values, [&b"<sip:a>"[..], b"<sip:b>", b"<sip:a>"]);
+
+        let (name, values) = it.next().unwrap();
+        assert_eq!(name, "Call-ID");
+        let values: Vec<&[u8]> = values.collect();
+        assert_eq!(values, [&b"x"[..], b"y"]);
+
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn test_groups_empty() {
+        assert!(groups(&[]).next().is_none());
+    }
+
+    #[test]
+    fn test_headers_lookup() {
+        let headers = [
+            Header { name: "Allow", value: HeaderValue::from_static(b"INVITE, ACK,,") },
+            Header { name: "k", value: HeaderValue::from_static(b"100rel") },
+            Header { name: "allow", value: HeaderValue::from_static(b" BYE ") },
+            Header { name: "Supported", value: HeaderValue::from_static(b"") },
+        ];
+        let headers = Headers::new(&headers);
+        assert_eq!(headers.len(), 4);
+        assert_eq!(headers.get("ALLOW"), Some(&b"INVITE, ACK,,"[..]));
+        assert_eq!(headers.get("Contact"), None);
+        assert_eq!(headers.get_all("supported").collect::<Vec<_>>(), [&b"100rel"[..], b""]);
+        assert_eq!(headers.values("Allow").collect::<Vec<_>>(),
+                   [&b"INVITE"[..], b"ACK", b"BYE"]);
+        assert_eq!(headers.values("Supported").collect::<Vec<_>>(), [&b"100rel"[..]]);
+    }
+}
@@ -0,0 +1,819 @@
+//! Parsing the values of core headers into borrowed, structured types.
+//!
+//! Header values holding several comma-separated elements, such as Via and
+//! Contact, are split with `elements` and parsed one element at a time.
+//!
+//! # Example
+//!
+//! ```
+//! use parsip::headers::typed::{elements, CSeq, NameAddr, Via};
+//!
+//! let via = b"SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds;rport, \
+//!             SIP/2.0/TCP 192.0.2.1:5080;branch=z9hG4bK1";
+//! let vias: Vec<_> = elements(via).map(Via::parse).collect();
+//! let top = vias[0].unwrap();
+//! assert_eq!(top.transport, b"UDP");
+//! assert_eq!(top.branch, Some(&b"z9hG4bK776asdhds"[..]));
+//! assert_eq!(top.rport, Some(None));
+//! assert_eq!(vias[1].unwrap().port, Some(5080));
+//!
+//! let to = NameAddr::parse(b"\"Bob\" <sip:bob@biloxi.com>;tag=a6c85cf").unwrap();
+//! assert_eq!(to.display_name, Some(&b"Bob"[..]));
+//! assert_eq!(to.uri, b"sip:bob@biloxi.com");
+//! assert_eq!(to.tag, Some(&b"a6c85cf"[..]));
+//!
+//! let cseq = CSeq::parse(b"314159 INVITE").unwrap();
+//! assert_eq!((cseq.seq, cseq.method), (314159, "INVITE"));
+//! ```
+//!
+//! The authentication headers, unlike the others, hold a single value whose
+//! commas separate its parameters; they are parsed whole with
+//! `DigestChallenge::parse` and `DigestCredentials::parse`.
+
+use std::str;
+use lookup::is_token;
+use scan;
+use uri::SipUri;
+
+/// Errors in parsing a header value.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ValueError {
+    /// The value is empty.
+    Empty,
+    /// The sent-protocol of a Via is malformed.
+    Protocol,
+    /// The sent-by host of a Via is malformed.
+    SentBy,
+    /// A port is not a number up to 65535.
+    Port,
+    /// The sequence number of a CSeq is not a number below 2**31.
+    Number,
+    /// The method of a CSeq is not a token.
+    Method,
+    /// The display name or URI of an address is malformed.
+    Addr,
+    /// The parameters do not start with `;`.
+    Params,
+    /// A Call-ID holds whitespace or other invalid bytes.
+    CallId,
+    /// The authentication scheme is not `Digest`.
+    Scheme,
+    /// An auth-param is not a `name=value` pair, or its quoted string is
+    /// not terminated.
+    AuthParam,
+    /// A required auth-param, such as the `nonce`, is missing.
+    MissingParam,
+}
+
+/// Splits a header value into its comma-separated elements, skipping
+/// empty ones.
+///
+/// Commas inside quoted strings and angle brackets do not separate
+/// elements.
+#[inline]
+pub fn elements(value: &[u8]) -> Elements<'_> {
+    Elements { rest: Some(value) }
+}
+
+/// An iterator over the elements of a header value.
+///
+/// Created by the `elements` function.
+#[derive(Clone, Debug)]
+pub struct Elements<'a> {
+    rest: Option<&'a [u8]>,
+}
+
+impl<'a> Iterator for Elements<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        loop {
+            let (element, rest) = scan::split_first(self.rest?);
+            self.rest = rest;
+            if !element.is_empty() {
+                return Some(element);
+            }
+        }
+    }
+}
+
+/// An element of a Via header.
+///
+/// > ```notrust
+/// > via-parm          =  sent-protocol LWS sent-by *( SEMI via-params )
+/// > sent-protocol     =  protocol-name SLASH protocol-version
+/// >                      SLASH transport
+/// > sent-by           =  host [ COLON port ]
+/// > ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Via<'a> {
+    /// The protocol name, such as `SIP`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub protocol: &'a [u8],
+    /// The protocol version, such as `2.0`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub version: &'a [u8],
+    /// The transport, such as `UDP`; see `transport::Transport::from_name`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub transport: &'a [u8],
+    /// The host of the sent-by, without the brackets of an IPv6 reference.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub host: &'a [u8],
+    /// The port of the sent-by, if explicit.
+    pub port: Option<u16>,
+    /// The parameters with their leading `;`, such as `;branch=z9hG4bK1`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub params: &'a [u8],
+    /// The `branch` parameter.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub branch: Option<&'a [u8]>,
+    /// The `received` parameter.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub received: Option<&'a [u8]>,
+    /// The `rport` parameter, `Some(None)` when it has no value, as in
+    /// requests asking for it.
+    pub rport: Option<Option<u16>>,
+}
+
+impl<'a> Via<'a> {
+    /// Parses a single element of a Via header.
+    pub fn parse(element: &'a [u8]) -> Result<Via<'a>, ValueError> {
+        let element = scan::trim(element);
+        if element.is_empty() {
+            return Err(ValueError::Empty);
+        }
+        let semi = element.iter().position(|&b| b == b';').unwrap_or(element.len());
+        let (head, params) = element.split_at(semi);
+
+        let mut parts = head.splitn(3, |&b| b == b'/');
+        let protocol = token(parts.next(), ValueError::Protocol)?;
+        let version = token(parts.next(), ValueError::Protocol)?;
+        let rest = scan::trim(parts.next().ok_or(ValueError::Protocol)?);
+        let space = rest.iter().position(|&b| b == b' ' || b == b'\t').ok_or(ValueError::SentBy)?;
+        let transport = token(Some(&rest[..space]), ValueError::Protocol)?;
+        let (host, port) = sent_by(scan::trim(&rest[space..]))?;
+
+        let rport = match scan::param(params, b"rport") {
+            Some(b"") => Some(None),
+            Some(value) => Some(Some(port_number(value)?)),
+            None => None,
+        };
+        Ok(Via {
+            protocol,
+            version,
+            transport,
+            host,
+            port,
+            params,
+            branch: scan::param(params, b"branch"),
+            received: scan::param(params, b"received"),
+            rport,
+        })
+    }
+
+    /// The value of the first parameter called `name`, compared
+    /// case-insensitively, empty for parameters without one.
+    #[inline]
+    pub fn param(&self, name: &str) -> Option<&'a [u8]> {
+        scan::param(self.params, name.as_bytes())
+    }
+}
+
+/// The value of a CSeq header.
+///
+/// > ```notrust
+/// > CSeq  =  "CSeq" HCOLON 1*DIGIT LWS Method
+/// > ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct CSeq<'a> {
+    /// The sequence number.
+    pub seq: u32,
+    /// The method.
+    pub method: &'a str,
+}
+
+impl<'a> CSeq<'a> {
+    /// Parses the value of a CSeq header.
+    pub fn parse(value: &'a [u8]) -> Result<CSeq<'a>, ValueError> {
+        let value = scan::trim(value);
+        if value.is_empty() {
+            return Err(ValueError::Empty);
+        }
+        let space = value.iter().position(|&b| b == b' ' || b == b'\t').ok_or(ValueError::Method)?;
+        let seq = number(&value[..space]).filter(|&seq| seq < 1 << 31).ok_or(ValueError::Number)?;
+        let method = token(Some(scan::trim(&value[space..])), ValueError::Method)?;
+        Ok(CSeq {
+            seq,
+            // tokens are ASCII
+            method: str::from_utf8(method).map_err(|_| ValueError::Method)?,
+        })
+    }
+}
+
+/// An address in a From, To, Contact or similar header, with its header
+/// parameters.
+///
+/// > ```notrust
+/// > name-addr      =  [ display-name ] LAQUOT addr-spec RAQUOT
+/// > addr-spec      =  SIP-URI / SIPS-URI / absoluteURI
+/// > display-name   =  *(token LWS)/ quoted-string
+/// > ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct NameAddr<'a> {
+    /// The display name, without the quotes of a quoted string; escaped
+    /// characters are kept escaped.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub display_name: Option<&'a [u8]>,
+    /// The URI; `uri::Uri` parses it.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub uri: &'a [u8],
+    /// The header parameters with their leading `;`, such as `;tag=1`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub params: &'a [u8],
+    /// The `tag` parameter.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub tag: Option<&'a [u8]>,
+}
+
+impl<'a> NameAddr<'a> {
+    /// Parses a name-addr or addr-spec, such as the value of a From or To
+    /// header, or an element of a Contact header.
+    pub fn parse(element: &'a [u8]) -> Result<NameAddr<'a>, ValueError> {
+        let element = scan::trim(element);
+        if element.is_empty() {
+            return Err(ValueError::Empty);
+        }
+        let (display_name, rest) = display_name(element)?;
+        if display_name.is_some() && !rest.starts_with(b"<") {
+            return Err(ValueError::Addr);
+        }
+        if rest.starts_with(b"<") && !rest.contains(&b'>') {
+            return Err(ValueError::Addr);
+        }
+        let (uri, params) = scan::split_addr(rest);
+        let params = scan::trim(params);
+        // the URI needs a scheme
+        let colon = uri.iter().position(|&b| b == b':').unwrap_or(0);
+        if colon == 0 || uri.iter().any(|&b| b == b' ' || b == b'\t') {
+            return Err(ValueError::Addr);
+        }
+        if !params.is_empty() && !params.starts_with(b";") {
+            return Err(ValueError::Params);
+        }
+        Ok(NameAddr {
+            display_name,
+            uri,
+            params,
+            tag: scan::param(params, b"tag"),
+        })
+    }
+
+    /// The value of the first parameter called `name`, compared
+    /// case-insensitively, empty for parameters without one.
+    #[inline]
+    pub fn param(&self, name: &str) -> Option<&'a [u8]> {
+        scan::param(self.params, name.as_bytes())
+    }
+}
+
+/// An element of a Contact header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum Contact<'a> {
+    /// `*`, removing all the bindings in a REGISTER.
+    Wildcard,
+    /// An address.
+    Addr(NameAddr<'a>),
+}
+
+impl<'a> Contact<'a> {
+    /// Parses a single element of a Contact header.
+    pub fn parse(element: &'a [u8]) -> Result<Contact<'a>, ValueError> {
+        if scan::trim(element) == b"*" {
+            return Ok(Contact::Wildcard);
+        }
+        NameAddr::parse(element).map(Contact::Addr)
+    }
+}
+
+/// An element of a Route or Record-Route header.
+///
+/// > ```notrust
+/// > route-param  =  name-addr *( SEMI rr-param )
+/// > ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Route<'a> {
+    /// The address of the hop, with its header parameters.
+    pub addr: NameAddr<'a>,
+    /// Whether the URI holds the `lr` parameter, marking a loose router.
+    pub lr: bool,
+}
+
+impl<'a> Route<'a> {
+    /// Parses a single element of a Route or Record-Route header.
+    pub fn parse(element: &'a [u8]) -> Result<Route<'a>, ValueError> {
+        let addr = NameAddr::parse(element)?;
+        let lr = str::from_utf8(addr.uri)
+            .ok()
+            .and_then(|uri| SipUri::parse(uri).ok())
+            .is_some_and(|uri| uri.param("lr").is_some());
+        Ok(Route { addr, lr })
+    }
+}
+
+/// The value of a Call-ID header.
+///
+/// > ```notrust
+/// > callid  =  word [ "@" word ]
+/// > ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct CallId<'a> {
+    /// The identifier.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub id: &'a [u8],
+}
+
+impl<'a> CallId<'a> {
+    /// Parses the value of a Call-ID header.
+    pub fn parse(value: &'a [u8]) -> Result<CallId<'a>, ValueError> {
+        let id = scan::trim(value);
+        if id.is_empty() {
+            return Err(ValueError::Empty);
+        }
+        let mut words = id.split(|&b| b == b'@');
+        let valid = words.by_ref().take(2).all(|word| !word.is_empty() && word.iter().all(is_word));
+        if !valid || words.next().is_some() {
+            return Err(ValueError::CallId);
+        }
+        Ok(CallId { id })
+    }
+
+    /// The part after the `@`, usually a host, if any.
+    #[inline]
+    pub fn host(&self) -> Option<&'a [u8]> {
+        let at = self.id.iter().position(|&b| b == b'@')?;
+        Some(&self.id[at + 1..])
+    }
+}
+
+/// A Digest challenge, the value of a WWW-Authenticate or
+/// Proxy-Authenticate header.
+///
+/// Quoted values are returned without their quotes; escaped characters
+/// are kept escaped.
+///
+/// > ```notrust
+/// > challenge    =  ("Digest" LWS digest-cln *(COMMA digest-cln))
+/// > digest-cln   =  realm / domain / nonce / opaque / stale / algorithm
+/// >                 / qop-options / auth-param
+/// > ```
+///
+/// # Example
+///
+/// ```
+/// use parsip::headers::typed::DigestChallenge;
+///
+/// let challenge = DigestChallenge::parse(b"Digest realm=\"atlanta.com\", \
+///                                          nonce=\"84a4cc6f3082121f32b42a2187831a9e\", \
+///                                          qop=\"auth,auth-int\", stale=TRUE").unwrap();
+/// assert_eq!(challenge.realm, b"atlanta.com");
+/// assert_eq!(challenge.qop, Some(&b"auth,auth-int"[..]));
+/// assert!(challenge.stale);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DigestChallenge<'a> {
+    /// The `realm`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub realm: &'a [u8],
+    /// The `domain`, a space-separated list of URIs.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub domain: Option<&'a [u8]>,
+    /// The `nonce`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub nonce: &'a [u8],
+    /// The `opaque` value, to be returned in the credentials.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub opaque: Option<&'a [u8]>,
+    /// Whether the `stale` flag is `true`, meaning only the nonce expired.
+    pub stale: bool,
+    /// The `algorithm`, such as `MD5`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub algorithm: Option<&'a [u8]>,
+    /// The `qop` options, a comma-separated list such as `auth,auth-int`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub qop: Option<&'a [u8]>,
+    /// All the auth-params, after the scheme.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub params: &'a [u8],
+}
+
+impl<'a> DigestChallenge<'a> {
+    /// Parses the value of a WWW-Authenticate or Proxy-Authenticate header.
+    pub fn parse(value: &'a [u8]) -> Result<DigestChallenge<'a>, ValueError> {
+        let params = digest_params(value)?;
+        Ok(DigestChallenge {
+            realm: auth_param(params, b"realm").ok_or(ValueError::MissingParam)?,
+            domain: auth_param(params, b"domain"),
+            nonce: auth_param(params, b"nonce").ok_or(ValueError::MissingParam)?,
+            opaque: auth_param(params, b"opaque"),
+            stale: auth_param(params, b"stale").is_some_and(|stale| {
+                stale.eq_ignore_ascii_case(b"true")
+            }),
+            algorithm: auth_param(params, b"algorithm"),
+            qop: auth_param(params, b"qop"),
+            params,
+        })
+    }
+
+    /// The value of the first auth-param called `name`, compared
+    /// case-insensitively.
+    #[inline]
+    pub fn param(&self, name: &str) -> Option<&'a [u8]> {
+        auth_param(self.params, name.as_bytes())
+    }
+}
+
+/// Digest credentials, the value of an Authorization or
+/// Proxy-Authorization header.
+///
+/// Quoted values are returned without their quotes; escaped characters
+/// are kept escaped.
+///
+/// > ```notrust
+/// > credentials     =  ("Digest" LWS digest-response)
+/// > digest-response =  dig-resp *(COMMA dig-resp)
+/// > dig-resp        =  username / realm / nonce / digest-uri
+/// >                    / dresponse / algorithm / cnonce
+/// >                    / opaque / message-qop
+/// >                    / nonce-count / auth-param
+/// > ```
+///
+/// # Example
+///
+/// ```
+/// use parsip::headers::typed::DigestCredentials;
+///
+/// let credentials = DigestCredentials::parse(b"Digest username=\"bob\", \
+///     realm=\"biloxi.com\", nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", \
+///     uri=\"sip:bob@biloxi.com\", qop=auth, nc=00000001, cnonce=\"0a4f113b\", \
+///     response=\"6629fae49393a05397450978507c4ef1\"").unwrap();
+/// assert_eq!(credentials.username, b"bob");
+/// assert_eq!(credentials.uri, b"sip:bob@biloxi.com");
+/// assert_eq!(credentials.nc, Some(&b"00000001"[..]));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DigestCredentials<'a> {
+    /// The `username`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub username: &'a [u8],
+    /// The `realm`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub realm: &'a [u8],
+    /// The `nonce`, as given in the challenge.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub nonce: &'a [u8],
+    /// The digest `uri`, usually the Request-URI.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub uri: &'a [u8],
+    /// The `response`, 32 hexadecimal digits for MD5.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub response: &'a [u8],
+    /// The `algorithm`, such as `MD5`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub algorithm: Option<&'a [u8]>,
+    /// The `cnonce`, the client nonce.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub cnonce: Option<&'a [u8]>,
+    /// The `opaque` value of the challenge.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub opaque: Option<&'a [u8]>,
+    /// The chosen `qop`, such as `auth`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub qop: Option<&'a [u8]>,
+    /// The nonce count `nc`, 8 hexadecimal digits as written.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub nc: Option<&'a [u8]>,
+    /// All the auth-params, after the scheme.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::text"))]
+    pub params: &'a [u8],
+}
+
+impl<'a> DigestCredentials<'a> {
+    /// Parses the value of an Authorization or Proxy-Authorization header.
+    pub fn parse(value: &'a [u8]) -> Result<DigestCredentials<'a>, ValueError> {
+        let params = digest_params(value)?;
+        let required = |name: &[u8]| auth_param(params, name).ok_or(ValueError::MissingParam);
+        Ok(DigestCredentials {
+            username: required(b"username")?,
+            realm: required(b"realm")?,
+            nonce: required(b"nonce")?,
+            uri: required(b"uri")?,
+            response: required(b"response")?,
+            algorithm: auth_param(params, b"algorithm"),
+            cnonce: auth_param(params, b"cnonce"),
+            opaque: auth_param(params, b"opaque"),
+            qop: auth_param(params, b"qop"),
+            nc: auth_param(params, b"nc"),
+            params,
+        })
+    }
+
+    /// The value of the first auth-param called `name`, compared
+    /// case-insensitively.
+    #[inline]
+    pub fn param(&self, name: &str) -> Option<&'a [u8]> {
+        auth_param(self.params, name.as_bytes())
+    }
+}
+
+/// Checks that `value` uses the Digest scheme and that its auth-params are
+/// well-formed, returning them.
+fn digest_params(value: &[u8]) -> Result<&[u8], ValueError> {
+    let value = scan::trim(value);
+    if value.is_empty() {
+        return Err(ValueError::Empty);
+    }
+    let space = value.iter().position(|&b| b == b' ' || b == b'\t').unwrap_or(value.len());
+    if !value[..space].eq_ignore_ascii_case(b"Digest") {
+        return Err(ValueError::Scheme);
+    }
+    let params = scan::trim(&value[space..]);
+    for element in elements(params) {
+        split_auth_param(element)?;
+    }
+    Ok(params)
+}
+
+/// The unquoted value of the first auth-param called `name` in the
+/// well-formed list `params`.
+fn auth_param<'a>(params: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    elements(params)
+        .filter_map(|element| split_auth_param(element).ok())
+        .find(|&(param, _)| param.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+/// Splits an auth-param into its name and its value, without the quotes
+/// of a quoted string.
+///
+/// > ```notrust
+/// > auth-param  =  auth-param-name EQUAL ( token / quoted-string )
+/// > ```
+fn split_auth_param(element: &[u8]) -> Result<(&[u8], &[u8]), ValueError> {
+    let eq = element.iter().position(|&b| b == b'=').ok_or(ValueError::AuthParam)?;
+    let name = token(Some(&element[..eq]), ValueError::AuthParam)?;
+    let value = match scan::trim(&element[eq + 1..]) {
+        [b'"', quoted @ ..] => match quoted {
+            [inner @ .., b'"'] if !is_escaped(inner) => inner,
+            _ => return Err(ValueError::AuthParam),
+        },
+        value => value,
+    };
+    Ok((name, value))
+}
+
+/// Returns `true` if `inner` ends in an unpaired backslash, which escapes
+/// the quote following it.
+fn is_escaped(inner: &[u8]) -> bool {
+    inner.iter().rev().take_while(|&&b| b == b'\\').count() % 2 == 1
+}
+
+/// Checks that `part` is a non-empty token, ignoring surrounding
+/// whitespace.
+fn token(part: Option<&[u8]>, error: ValueError) -> Result<&[u8], ValueError> {
+    let part = scan::trim(part.ok_or(error)?);
+    if part.is_empty() || !part.iter().all(|&b| is_token(b)) {
+        return Err(error);
+    }
+    Ok(part)
+}
+
+/// Splits a sent-by into its host and port.
+fn sent_by(sent_by: &[u8]) -> Result<(&[u8], Option<u16>), ValueError> {
+    let (host, rest) = if let Some(reference) = sent_by.strip_prefix(b"[") {
+        let close = reference.iter().position(|&b| b == b']').ok_or(ValueError::SentBy)?;
+        let address = &reference[..close];
+        if !address.iter().all(|&b| b.is_ascii_hexdigit() || b == b':' || b == b'.') {
+            return Err(ValueError::SentBy);
+        }
+        (address, &reference[close + 1..])
+    } else {
+        let end = sent_by.iter().position(|&b| b == b':').unwrap_or(sent_by.len());
+        let host = scan::trim(&sent_by[..end]);
+        if !host.iter().all(|&b| b.is_ascii_alphanumeric() || b == b'-' || b == b'.') {
+            return Err(ValueError::SentBy);
+        }
+        (host, &sent_by[end..])
+    };
+    if host.is_empty() {
+        return Err(ValueError::SentBy);
+    }
+    match scan::trim(rest) {
+        b"" => Ok((host, None)),
+        rest => match rest.strip_prefix(b":") {
+            Some(port) => Ok((host, Some(port_number(scan::trim(port))?))),
+            None => Err(ValueError::SentBy),
+        },
+    }
+}
+
+/// Splits the display name, if any, off a trimmed name-addr.
+fn display_name(element: &[u8]) -> Result<(Option<&[u8]>, &[u8]), ValueError> {
+    if let Some(quoted) = element.strip_prefix(b"\"") {
+        let mut escaped = false;
+        for (idx, &b) in quoted.iter().enumerate() {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => return Ok((Some(&quoted[..idx]), scan::trim(&quoted[idx + 1..]))),
+                _ => {}
+            }
+        }
+        return Err(ValueError::Addr);
+    }
+    match element.iter().position(|&b| b == b'<') {
+        Some(0) | None => Ok((None, element)),
+        Some(open) => {
+            let name = scan::trim(&element[..open]);
+            if !name.iter().all(|&b| is_token(b) || matches!(b, b' ' | b'\t' | b'\r' | b'\n')) {
+                return Err(ValueError::Addr);
+            }
+            Ok((Some(name), &element[open..]))
+        }
+    }
+}
+
+/// Parses a port.
+fn port_number(digits: &[u8]) -> Result<u16, ValueError> {
+    number(digits)
+        .filter(|&port| port <= u16::MAX as u32)
+        .map(|port| port as u16)
+        .ok_or(ValueError::Port)
+}
+
+/// Parses a decimal number of up to 10 digits.
+fn number(digits: &[u8]) -> Option<u32> {
+    if digits.is_empty() || digits.len() > 10 || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let value = digits.iter().fold(0u64, |acc, &b| acc * 10 + (b - b'0') as u64);
+    if value > u32::MAX as u64 { None } else { Some(value as u32) }
+}
+
+/// > ```notrust
+/// > word  =  1*(alphanum / "-" / "." / "!" / "%" / "*" /
+/// >          "_" / "+" / "`" / "'" / "~" /
+/// >          "(" / ")" / "<" / ">" /
+/// >          ":" / "\" / DQUOTE /
+/// >          "/" / "[" / "]" / "?" /
+/// >          "{" / "}" )
+/// > ```
+#[inline]
+fn is_word(b: &u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-.!%*_+`'~()<>:\\\"/[]?{}".contains(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{elements, CallId, Contact, CSeq, NameAddr, Route, ValueError, Via};
+    use super::{DigestChallenge, DigestCredentials};
+
+    #[test]
+    fn test_via() {
+        let via = Via::parse(b"SIP / 2.0 / TCP [2001:db8::9]:5080 ;branch=z9hG4bK1;\
+                               received=192.0.2.1;rport=5070").unwrap();
+        assert_eq!(via,
+                   Via {
+                       protocol: b"SIP",
+                       version: b"2.0",
+                       transport: b"TCP",
+                       host: b"2001:db8::9",
+                       port: Some(5080),
+                       params: b";branch=z9hG4bK1;received=192.0.2.1;rport=5070",
+                       branch: Some(b"z9hG4bK1"),
+                       received: Some(b"192.0.2.1"),
+                       rport: Some(Some(5070)),
+                   });
+        assert_eq!(via.param("RECEIVED"), Some(&b"192.0.2.1"[..]));
+
+        let via = Via::parse(b"SIP/2.0/UDP pc33.atlanta.com").unwrap();
+        assert_eq!((via.host, via.port, via.branch, via.rport),
+                   (&b"pc33.atlanta.com"[..], None, None, None));
+    }
+
+    #[test]
+    fn test_via_errors() {
+        assert_eq!(Via::parse(b" "), Err(ValueError::Empty));
+        assert_eq!(Via::parse(b"SIP/2.0 pc33.atlanta.com"), Err(ValueError::Protocol));
+        assert_eq!(Via::parse(b"SIP/2.0/UDP"), Err(ValueError::SentBy));
+        assert_eq!(Via::parse(b"SIP/2.0/UDP a b"), Err(ValueError::SentBy));
+        assert_eq!(Via::parse(b"SIP/2.0/UDP [::1"), Err(ValueError::SentBy));
+        assert_eq!(Via::parse(b"SIP/2.0/UDP a:70000"), Err(ValueError::Port));
+        assert_eq!(Via::parse(b"SIP/2.0/UDP a;rport=x"), Err(ValueError::Port));
+    }
+
+    #[test]
+    fn test_cseq() {
+        assert_eq!(CSeq::parse(b" 1\t ACK "), Ok(CSeq { seq: 1, method: "ACK" }));
+        assert_eq!(CSeq::parse(b"2147483648 INVITE"), Err(ValueError::Number));
+        assert_eq!(CSeq::parse(b"x INVITE"), Err(ValueError::Number));
+        assert_eq!(CSeq::parse(b"1"), Err(ValueError::Method));
+        assert_eq!(CSeq::parse(b"1 IN@VITE"), Err(ValueError::Method));
+    }
+
+    #[test]
+    fn test_name_addr() {
+        let addr = NameAddr::parse(b"Bob Smith <sip:bob@biloxi.com;lr> ;tag=1;x").unwrap();
+        assert_eq!(addr.display_name, Some(&b"Bob Smith"[..]));
+        assert_eq!(addr.uri, b"sip:bob@biloxi.com;lr");
+        assert_eq!(addr.params, b";tag=1;x");
+        assert_eq!(addr.param("x"), Some(&b""[..]));
+
+        let addr = NameAddr::parse(b"\"A \\\"<b>\\\"\" <tel:+1555>").unwrap();
+        assert_eq!(addr.display_name, Some(&b"A \\\"<b>\\\""[..]));
+        assert_eq!((addr.uri, addr.tag), (&b"tel:+1555"[..], None));
+
+        let addr = NameAddr::parse(b"sip:alice@atlanta.com;tag=88sja8x").unwrap();
+        assert_eq!((addr.display_name, addr.uri), (None, &b"sip:alice@atlanta.com"[..]));
+        assert_eq!(addr.tag, Some(&b"88sja8x"[..]));
+
+        assert_eq!(NameAddr::parse(b"\"Bob\" sip:bob@biloxi.com"), Err(ValueError::Addr));
+        assert_eq!(NameAddr::parse(b"<sip:bob@biloxi.com"), Err(ValueError::Addr));
+        assert_eq!(NameAddr::parse(b"<bob>"), Err(ValueError::Addr));
+        assert_eq!(NameAddr::parse(b"<sip:bob@biloxi.com> tag=1"), Err(ValueError::Params));
+    }
+
+    #[test]
+    fn test_route() {
+        let route = Route::parse(b"<sip:p1.example.com;LR>;x=1").unwrap();
+        assert!(route.lr);
+        assert_eq!(route.addr.uri, b"sip:p1.example.com;LR");
+        assert_eq!(route.addr.params, b";x=1");
+        assert!(!Route::parse(b"<sip:p2.example.com>").unwrap().lr);
+        assert!(!Route::parse(b"sip:p3.example.com;lr").unwrap().lr);
+        assert_eq!(Route::parse(b"<sip:p4.example.com"), Err(ValueError::Addr));
+    }
+
+    #[test]
+    fn test_contact_elements() {
+        let value = b"\"Doe, John\" <sip:j@x;a=1,2>;q=0.7, *,, <sip:k@y>";
+        let contacts: Vec<_> = elements(value).map(Contact::parse).collect();
+        assert_eq!(contacts.len(), 3);
+        match contacts[0] {
+            Ok(Contact::Addr(addr)) => assert_eq!(addr.param("q"), Some(&b"0.7"[..])),
+            _ => panic!("expected an address"),
+        }
+        assert_eq!(contacts[1], Ok(Contact::Wildcard));
+    }
+
+    #[test]
+    fn test_call_id() {
+        let call_id = CallId::parse(b" a84b4c76e66710@pc33.atlanta.com ").unwrap();
+        assert_eq!(call_id.id, b"a84b4c76e66710@pc33.atlanta.com");
+        assert_eq!(call_id.host(), Some(&b"pc33.atlanta.com"[..]));
+        assert_eq!(CallId::parse(b"f81d4fae-7dec").unwrap().host(), None);
+        assert_eq!(CallId::parse(b""), Err(ValueError::Empty));
+        assert_eq!(CallId::parse(b"a b"), Err(ValueError::CallId));
+        assert_eq!(CallId::parse(b"a@b@c"), Err(ValueError::CallId));
+        assert_eq!(CallId::parse(b"a@"), Err(ValueError::CallId));
+    }
+
+    #[test]
+    fn test_digest_challenge() {
+        let challenge = DigestChallenge::parse(b"DIGEST realm=\"a, \\\"b\\\"\",nonce=n1 ,\
+                                                 opaque=\"\", algorithm=MD5, x=1").unwrap();
+        assert_eq!(challenge.realm, b"a, \\\"b\\\"");
+        assert_eq!((challenge.nonce, challenge.opaque), (&b"n1"[..], Some(&b""[..])));
+        assert_eq!(challenge.algorithm, Some(&b"MD5"[..]));
+        assert_eq!((challenge.stale, challenge.qop, challenge.domain), (false, None, None));
+        assert_eq!(challenge.param("X"), Some(&b"1"[..]));
+
+        assert_eq!(DigestChallenge::parse(b""), Err(ValueError::Empty));
+        assert_eq!(DigestChallenge::parse(b"Basic realm=\"a\""), Err(ValueError::Scheme));
+        assert_eq!(DigestChallenge::parse(b"Digest realm=\"a\""), Err(ValueError::MissingParam));
+        assert_eq!(DigestChallenge::parse(b"Digest realm=\"a\", nonce"),
+                   Err(ValueError::AuthParam));
+        assert_eq!(DigestChallenge::parse(b"Digest realm=\"a\\\", nonce=1"),
+                   Err(ValueError::AuthParam));
+    }
+
+    #[test]
+    fn test_digest_credentials() {
+        let value = b"Digest username=\"alice\", realm=\"atlanta.com\", nonce=\"n\", \
+                      uri=\"sip:bob@biloxi.com\", response=\"42ce3cef44b22f50c6a6071bc8\"";
+        let credentials = DigestCredentials::parse(value).unwrap();
+        assert_eq!(credentials.username, b"alice");
+        assert_eq!(credentials.response, b"42ce3cef44b22f50c6a6071bc8");
+        assert_eq!((credentials.qop, credentials.nc, credentials.cnonce), (None, None, None));
+
+        assert_eq!(DigestCredentials::parse(&value[..value.len() - 39]),
+                   Err(ValueError::MissingParam));
+    }
+}
@@ -0,0 +1,558 @@
+//! Resuming the parse of a message as its bytes arrive.
+//!
+//! Over TCP and other streams, messages arrive in fragments. Parsing the
+//! whole buffer again after every read scans a large message over and
+//! over; a `MessageParser` remembers how far it got instead, and only
+//! examines the bytes it has not seen yet.
+
+use std::{result, str};
+use memchr::memchr;
+use headers::{expand_compact, names_eq};
+use scan;
+use sip::{check_line_len, Error, ErrorKind, Header, HeaderIter, HeaderValue, Message,
+          ParserConfig, Request, Response, Result, SipVersion, Status};
+
+/// The offsets of a header parsed by a `MessageParser`, in the buffer it
+/// was fed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeaderIndices {
+    /// The start and end of the name.
+    pub name: (usize, usize),
+    /// The start and end of the value.
+    pub value: (usize, usize),
+}
+
+/// Empty indices, useful for constructing a `HeaderIndices` array to pass
+/// in to a `MessageParser`.
+pub const EMPTY_INDICES: HeaderIndices = HeaderIndices {
+    name: (0, 0),
+    value: (0, 0),
+};
+
+/// The start line of the message, as offsets in the buffer.
+#[derive(Copy, Clone, Debug)]
+enum StartLine {
+    Request {
+        method: (usize, usize),
+        path: (usize, usize),
+        version: SipVersion,
+    },
+    Response {
+        version: SipVersion,
+        code: u16,
+        reason: (usize, usize),
+    },
+}
+
+/// How far the parser got.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    /// Looking for the end of the start line.
+    StartLine,
+    /// Looking for the end of the header at `pos`, or of the header section.
+    Headers,
+    /// The head ends at `head`, the body at `end`.
+    Body { head: usize, end: usize },
+}
+
+/// A parser fed a message in successive chunks.
+///
+/// Each call to `parse` is handed all the bytes of the message received so
+/// far, and picks up where the previous one stopped: the start line and
+/// the headers already parsed, as well as the part of a line already
+/// searched for its end, are not examined again. Only offsets are kept
+/// between calls, so the buffer may move in the meantime, as a growing
+/// `Vec` does.
+///
+/// As on stream transports, a message without `Content-Length` has no
+/// body.
+///
+/// # Example
+///
+/// ```
+/// use parsip::incremental::{MessageParser, EMPTY_INDICES};
+/// use parsip::{Message, Status};
+///
+/// let chunks: [&[u8]; 3] = [b"MESSAGE sip:bob@biloxi.com SIP/2.0\r\nCall-ID: a8",
+///                           b"4b4c76e66710\r\nContent-Length: 5\r\n\r\nhe",
+///                           b"llo"];
+/// let mut indices = [EMPTY_INDICES; 8];
+/// let mut parser = MessageParser::new(&mut indices);
+/// let mut buf = Vec::new();
+/// let mut status = Status::Partial(None);
+/// for chunk in chunks.iter() {
+///     buf.extend_from_slice(chunk);
+///     status = parser.parse(&buf).unwrap();
+/// }
+/// assert_eq!(status, Status::Complete(buf.len()));
+///
+/// let mut headers = [parsip::EMPTY_HEADER; 8];
+/// match parser.message(&buf, &mut headers) {
+///     Some(Message::Request(req)) => {
+///         assert_eq!(req.method, Some("MESSAGE"));
+///         assert_eq!(req.body, Some(&b"hello"[..]));
+///     }
+///     _ => unreachable!(),
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MessageParser<'i> {
+    config: ParserConfig,
+    indices: &'i mut [HeaderIndices],
+    state: State,
+    start_line: Option<StartLine>,
+    headers: usize,
+    content_length: Option<usize>,
+    /// Offset of the header section.
+    section: usize,
+    /// Offset of the first byte not parsed yet.
+    pos: usize,
+    /// Offset of the first byte not searched for a line end yet.
+    scanned: usize,
+}
+
+impl<'i> MessageParser<'i> {
+    /// Creates a parser using the default `ParserConfig`, keeping the
+    /// offsets of the headers in a slice you allocate.
+    #[inline]
+    pub fn new(indices: &'i mut [HeaderIndices]) -> MessageParser<'i> {
+        MessageParser::with_config(ParserConfig::new(), indices)
+    }
+
+    /// Creates a parser using the given `ParserConfig`.
+    ///
+    /// Its limits apply as they do to `Request::parse_with`, to the bytes
+    /// received so far. Its `Metrics`, if any, are not reported to.
+    #[inline]
+    pub fn with_config(config: ParserConfig,
+                       indices: &'i mut [HeaderIndices])
+                       -> MessageParser<'i> {
+        MessageParser {
+            config,
+            indices,
+            state: State::StartLine,
+            start_line: None,
+            headers: 0,
+            content_length: None,
+            section: 0,
+            pos: 0,
+            scanned: 0,
+        }
+    }
+
+    /// Forgets the message parsed so far, to parse the next one from the
+    /// start of a buffer.
+    pub fn reset(&mut self) {
+        self.state = State::StartLine;
+        self.start_line = None;
+        self.headers = 0;
+        self.content_length = None;
+        self.section = 0;
+        self.pos = 0;
+        self.scanned = 0;
+    }
+
+    /// The offsets of the headers parsed so far.
+    #[inline]
+    pub fn headers(&self) -> &[HeaderIndices] {
+        &self.indices[..self.headers]
+    }
+
+    /// Continues parsing `buf`, which holds all the bytes of the message
+    /// received so far, unchanged since the previous call.
+    ///
+    /// Returns the length of the message once it is complete. While the
+    /// body is partial, `Status::Partial` holds the length of the whole
+    /// message.
+    pub fn parse(&mut self, buf: &[u8]) -> Result<usize> {
+        loop {
+            let progressed = match self.state {
+                State::StartLine => self.parse_start_line(buf)?,
+                State::Headers => self.parse_header(buf)?,
+                State::Body { end, .. } if end <= buf.len() => return Ok(Status::Complete(end)),
+                State::Body { end, .. } => return Ok(Status::Partial(Some(end))),
+            };
+            if !progressed {
+                return Ok(Status::Partial(None));
+            }
+        }
+    }
+
+    /// Parses the start line once it is complete, returning `false` if
+    /// more bytes are needed.
+    fn parse_start_line(&mut self, buf: &[u8]) -> result::Result<bool, Error> {
+        // skip the keepalives preceding the message
+        loop {
+            match buf[self.pos..] {
+                [b'\r', b'\n', ..] => self.pos += 2,
+                [b'\n', ..] => self.pos += 1,
+                [] | [b'\r'] => return Ok(false),
+                _ => break,
+            }
+        }
+        // the limit holds before the line ends
+        check_line_len(buf, &buf[self.pos..], self.config.max_start_line_len)?;
+        let lf = match self.find_lf(buf) {
+            Some(lf) => lf,
+            None => return Ok(false),
+        };
+        let line = &buf[self.pos..lf + 1];
+        let (len, start_line) = if line.len() >= 4 && line[..4].eq_ignore_ascii_case(b"SIP/") {
+            let mut res = Response::new(&mut []);
+            let len = match self.line_len(res.parse_status_line(&self.config, line))? {
+                Some(len) => len,
+                None => return Ok(false),
+            };
+            match res {
+                Response { version: Some(version), code: Some(code), reason: Some(reason), .. } => {
+                    let reason = span(buf, reason.as_bytes());
+                    (len, StartLine::Response { version, code, reason })
+                }
+                _ => return Ok(false),
+            }
+        } else {
+            let mut req = Request::new(&mut []);
+            let len = match self.line_len(req.parse_request_line(&self.config, line))? {
+                Some(len) => len,
+                None => return Ok(false),
+            };
+            match req {
+                Request { method: Some(method), path: Some(path), version: Some(version), .. } => {
+                    let (method, path) = (span(buf, method.as_bytes()), span(buf, path.as_bytes()));
+                    (len, StartLine::Request { method, path, version })
+                }
+                _ => return Ok(false),
+            }
+        };
+        self.start_line = Some(start_line);
+        self.advance(self.pos + len, State::Headers);
+        self.section = self.pos;
+        Ok(true)
+    }
+
+    /// Parses the header at `pos`, or the empty line closing the header
+    /// section, once it is complete, returning `false` if more bytes are
+    /// needed.
+    ///
+    /// Both go through a `HeaderIter`, so the checks and limits are those
+    /// of `Request::parse_with`.
+    fn parse_header(&mut self, buf: &[u8]) -> result::Result<bool, Error> {
+        let mut iter = HeaderIter::resume(buf, self.section, self.pos, &self.config);
+        match iter.section_end()? {
+            Status::Complete(true) => {
+                let head = iter.offset();
+                let end = head.saturating_add(self.content_length.unwrap_or(0));
+                self.advance(head, State::Body { head, end });
+                return Ok(true);
+            }
+            Status::Complete(false) => {}
+            Status::Partial(_) => return Ok(false),
+        }
+        if self.headers == self.indices.len() {
+            return Err(Error::new(ErrorKind::TooManyHeaders, self.pos));
+        }
+        // the header ends at a line end not followed by folding whitespace
+        let limit = loop {
+            let lf = match self.find_lf(buf) {
+                Some(lf) => lf,
+                None => return self.partial_header(buf),
+            };
+            match buf.get(lf + 1) {
+                Some(&b' ') | Some(&b'\t') => self.scanned = lf + 1,
+                Some(_) => break lf + 2,
+                None => {
+                    self.scanned = lf;
+                    return self.partial_header(buf);
+                }
+            }
+        };
+        let mut iter = HeaderIter::resume(&buf[..limit], self.section, self.pos, &self.config);
+        let header = match iter.raw_header()? {
+            Status::Complete(header) => header,
+            Status::Partial(_) => return Ok(false),
+        };
+        let end = iter.offset();
+        if names_eq(header.name, "Content-Length") {
+            match scan::content_length(Some(header.value.as_bytes())) {
+                Ok(Some(len)) if self.content_length.is_none_or(|other| other == len) => {
+                    self.content_length = Some(len);
+                }
+                _ => return Err(Error::new(ErrorKind::ContentLength, self.pos)),
+            }
+        }
+        self.indices[self.headers] = HeaderIndices {
+            name: span(buf, header.name.as_bytes()),
+            value: span(buf, header.value.as_bytes()),
+        };
+        self.headers += 1;
+        self.advance(end, State::Headers);
+        Ok(true)
+    }
+
+    /// Checks the header at `pos`, which needs more bytes, against the
+    /// limits of the config, returning `false`.
+    ///
+    /// The limits hold however the buffer ends, as for `Request::parse_with`;
+    /// with any of them set, the bytes of a partial header are parsed again
+    /// on every call.
+    fn partial_header(&self, buf: &[u8]) -> result::Result<bool, Error> {
+        if self.config.limits_headers() {
+            HeaderIter::resume(buf, self.section, self.pos, &self.config).raw_header()?;
+        }
+        Ok(false)
+    }
+
+    /// Finds the next `LF` from `pos`, searching only the bytes not
+    /// searched yet.
+    #[inline]
+    fn find_lf(&mut self, buf: &[u8]) -> Option<usize> {
+        let from = self.scanned.max(self.pos);
+        match memchr(b'\n', &buf[from..]) {
+            Some(lf) => Some(from + lf),
+            None => {
+                self.scanned = buf.len();
+                None
+            }
+        }
+    }
+
+    /// The length of a start line parsed from `pos`, with errors located
+    /// in the whole buffer.
+    fn line_len(&self, result: Result<usize>) -> result::Result<Option<usize>, Error> {
+        match result {
+            Ok(Status::Complete(len)) => Ok(Some(len)),
+            Ok(Status::Partial(_)) => Ok(None),
+            Err(err) => Err(Error::new(err.kind(), self.pos + err.offset())),
+        }
+    }
+
+    #[inline]
+    fn advance(&mut self, pos: usize, state: State) {
+        self.pos = pos;
+        self.scanned = pos;
+        self.state = state;
+    }
+
+    /// The parsed message, borrowing from `buf`, with its headers stored
+    /// in `headers`.
+    ///
+    /// Returns `None` until `parse` found the message complete, or if
+    /// `headers` is too short.
+    pub fn message<'h, 'b>(&self, buf: &'b [u8], headers: &'h mut [Header<'b>])
+                           -> Option<Message<'h, 'b>> {
+        let (head, end) = match self.state {
+            State::Body { head, end } if end <= buf.len() => (head, end),
+            _ => return None,
+        };
+        let headers = headers.get_mut(..self.headers)?;
+        for (header, indices) in headers.iter_mut().zip(self.indices.iter()) {
+            let mut name = str::from_utf8(&buf[indices.name.0..indices.name.1]).ok()?;
+            if self.config.expand_compact_headers {
+                name = expand_compact(name);
+            }
+            let value = HeaderValue::from_parsed(&buf[indices.value.0..indices.value.1]);
+            *header = Header::new(name, value);
+        }
+        let text = |(start, end): (usize, usize)| str::from_utf8(&buf[start..end]).ok();
+        let body = Some(&buf[head..end]);
+        match self.start_line? {
+            StartLine::Request { method, path, version } => {
+                let mut req = Request::new(headers);
+                req.method = Some(text(method)?);
+                req.path = Some(text(path)?);
+                req.version = Some(version);
+                req.body = body;
+                Some(Message::Request(req))
+            }
+            StartLine::Response { version, code, reason } => {
+                let mut res = Response::new(headers);
+                res.version = Some(version);
+                res.code = Some(code);
+                res.reason = Some(text(reason)?);
+                res.body = body;
+                Some(Message::Response(res))
+            }
+        }
+    }
+}
+
+/// The start and end offsets of `part` within `buf`.
+#[inline]
+fn span(buf: &[u8], part: &[u8]) -> (usize, usize) {
+    let start = part.as_ptr() as usize - buf.as_ptr() as usize;
+    (start, start + part.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use sip::{Error, ErrorKind, Message, ParserConfig, Request, Status, EMPTY_HEADER};
+    use sip::Result;
+    use super::{MessageParser, EMPTY_INDICES};
+
+    const INVITE: &[u8] = b"\r\nINVITE sip:bob@biloxi.com SIP/2.0\r\n\
+                            v: SIP/2.0/UDP pc33.atlanta.com\r\n\
+                            Subject: lunch\r\n at noon\r\n\
+                            l: 4\r\n\
+                            \r\n\
+                            body";
+
+    #[test]
+    fn test_fed_byte_by_byte() {
+        let mut config = ParserConfig::new();
+        config.expand_compact_headers(true);
+        let mut indices = [EMPTY_INDICES; 4];
+        let mut parser = MessageParser::with_config(config, &mut indices);
+        for len in 0..INVITE.len() {
+            let buf = INVITE[..len].to_vec();
+            assert!(parser.parse(&buf).unwrap().is_partial());
+        }
+        assert_eq!(parser.parse(INVITE), Ok(Status::Complete(INVITE.len())));
+        assert_eq!(parser.headers().len(), 3);
+
+        let mut expected_headers = [EMPTY_HEADER; 4];
+        let mut expected = Request::new(&mut expected_headers);
+        expected.parse_message_with(&config, INVITE).unwrap();
+        let mut headers = [EMPTY_HEADER; 4];
+        match parser.message(INVITE, &mut headers) {
+            Some(Message::Request(req)) => assert_eq!(req, expected),
+            msg => panic!("unexpected {:?}", msg),
+        }
+        assert!(parser.message(INVITE, &mut [EMPTY_HEADER; 2]).is_none());
+    }
+
+    #[test]
+    fn test_lf_line_endings() {
+        let buf = b"MESSAGE sip:bob@biloxi.com SIP/2.0\nl: 2\nSubject: a\n b\n\nhi";
+        let mut config = ParserConfig::new();
+        config.allow_lf_line_endings(true);
+        let mut indices = [EMPTY_INDICES; 4];
+        let mut parser = MessageParser::with_config(config, &mut indices);
+        for end in 0..buf.len() {
+            assert!(parser.parse(&buf[..end]).unwrap().is_partial());
+        }
+        assert_eq!(parser.parse(buf), Ok(Status::Complete(buf.len())));
+        let mut headers = [EMPTY_HEADER; 4];
+        match parser.message(buf, &mut headers) {
+            Some(Message::Request(req)) => assert_eq!(req.body, Some(&b"hi"[..])),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_partial_body() {
+        let mut indices = [EMPTY_INDICES; 4];
+        let mut parser = MessageParser::new(&mut indices);
+        assert_eq!(parser.parse(&INVITE[..INVITE.len() - 1]),
+                   Ok(Status::Partial(Some(INVITE.len()))));
+        let mut headers = [EMPTY_HEADER; 4];
+        assert!(parser.message(INVITE, &mut headers).is_some());
+    }
+
+    #[test]
+    fn test_reset() {
+        let buf = b"SIP/2.0 200 OK\r\n\r\n";
+        let mut indices = [EMPTY_INDICES; 4];
+        let mut parser = MessageParser::new(&mut indices);
+        assert_eq!(parser.parse(INVITE), Ok(Status::Complete(INVITE.len())));
+        parser.reset();
+        assert_eq!(parser.parse(buf), Ok(Status::Complete(buf.len())));
+        let mut headers = [EMPTY_HEADER; 4];
+        match parser.message(buf, &mut headers) {
+            Some(Message::Response(res)) => {
+                assert_eq!((res.code, res.reason), (Some(200), Some("OK")));
+                assert_eq!(res.body, Some(&b""[..]));
+            }
+            msg => panic!("unexpected {:?}", msg),
+        }
+    }
+
+    /// Parses `buf` whole, in one shot and in a `MessageParser`.
+    fn parse_both(config: &ParserConfig, buf: &[u8]) -> (Result<usize>, Result<usize>) {
+        let mut headers = [EMPTY_HEADER; 4];
+        let expected = Request::new(&mut headers).parse_with(config, buf);
+        let mut indices = [EMPTY_INDICES; 4];
+        let mut parser = MessageParser::with_config(*config, &mut indices);
+        (parser.parse(buf), expected)
+    }
+
+    #[test]
+    fn test_same_as_one_shot() {
+        let mut config = ParserConfig::new();
+        config.max_header_section_len(Some(40)).max_continuation_lines(Some(1));
+        let inputs: [&[u8]; 5] = [
+            b"OPTIONS sip:a SIP/2.0\r\nTo: <sip:a>\r\nFrom: <sip:b>\r\n\r\n",
+            b"OPTIONS sip:a SIP/2.0\r\nTo: <sip:a>\r\nFrom: <sip:b>\r\nCall-ID: 1\r\n\r\n",
+            b"OPTIONS sip:a SIP/2.0\r\nSubject: a\r\n b\r\n\r\n",
+            b"OPTIONS sip:a SIP/2.0\r\nSubject: a\r\n b\r\n c\r\n\r\n",
+            b"OPTIONS sip:a SIP/2.0\r\nTo : <sip:a>\r\nX\r\n\r\n",
+        ];
+        let results: Vec<_> = inputs.iter().map(|buf| parse_both(&config, buf)).collect();
+        for &(incremental, one_shot) in &results {
+            assert_eq!(incremental, one_shot);
+        }
+        assert_eq!(results[0].0, Ok(Status::Complete(inputs[0].len())));
+        assert_eq!(results[1].0, Err(Error::new(ErrorKind::TooLong, 63)));
+        assert!(results[2].0.is_ok());
+        assert_eq!(results[3].0.map_err(|err| err.kind()), Err(ErrorKind::TooLong));
+        assert_eq!(results[4].0.map_err(|err| err.kind()), Err(ErrorKind::HeaderName));
+    }
+
+    #[test]
+    fn test_limits_in_chunks() {
+        // every prefix parses as it does in one shot
+        let fed_in_chunks = |config: &ParserConfig, buf: &[u8]| {
+            let mut indices = [EMPTY_INDICES; 8];
+            let mut parser = MessageParser::with_config(*config, &mut indices);
+            let mut result = Ok(Status::Partial(None));
+            for end in (1..buf.len()).step_by(7).chain(Some(buf.len())) {
+                let mut headers = [EMPTY_HEADER; 8];
+                let expected = Request::new(&mut headers).parse_with(config, &buf[..end]);
+                result = parser.parse(&buf[..end]);
+                assert_eq!(result, expected);
+                if result.is_err() {
+                    break;
+                }
+            }
+            result.map_err(|err| (err.kind(), err.offset()))
+        };
+        let too_long = |offset| Err((ErrorKind::TooLong, offset));
+        let mut config = ParserConfig::new();
+        config.max_start_line_len(Some(20)).max_header_section_len(Some(20));
+        let buf = b"BYE sip:a SIP/2.0\r\nA: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\nE: 5\r\n\r\n";
+        assert_eq!(fed_in_chunks(&config, buf), too_long(39));
+
+        let mut flood = b"OPTIONS sip:".to_vec();
+        flood.extend_from_slice(&[b'a'; 100]);
+        assert_eq!(fed_in_chunks(&config, &flood), too_long(20));
+
+        let mut config = ParserConfig::new();
+        config.max_header_name_len(Some(8)).max_header_value_len(Some(100));
+        let mut value = b"OPTIONS sip:a SIP/2.0\r\nSubject: ".to_vec();
+        value.extend_from_slice(&[b'x'; 1000]);
+        assert_eq!(fed_in_chunks(&config, &value), too_long(132));
+        let mut name = b"OPTIONS sip:a SIP/2.0\r\nX-".to_vec();
+        name.extend_from_slice(&[b'n'; 1000]);
+        assert_eq!(fed_in_chunks(&config, &name), too_long(31));
+        let mut padded = b"OPTIONS sip:a SIP/2.0\r\nTo: a".to_vec();
+        padded.extend_from_slice(&[b' '; 1000]);
+        padded.extend_from_slice(b"\r\n\r\n");
+        assert_eq!(fed_in_chunks(&config, &padded), Ok(Status::Complete(padded.len())));
+    }
+
+    #[test]
+    fn test_errors() {
+        let parse = |buf: &[u8], split: usize| {
+            let mut indices = [EMPTY_INDICES; 2];
+            let mut parser = MessageParser::new(&mut indices);
+            assert!(parser.parse(&buf[..split]).unwrap().is_partial());
+            parser.parse(buf)
+        };
+        assert_eq!(parse(b"SIP/2.0 2OO OK\r\n\r\n", 5), Err(Error::new(ErrorKind::Status, 8)));
+        assert_eq!(parse(b"MESSAGE sip:a SIP/2.0\r\nSubject: a\x00b\r\n\r\n", 30),
+                   Err(Error::new(ErrorKind::HeaderValue, 32)));
+        assert_eq!(parse(b"MESSAGE sip:a SIP/2.0\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n", 30),
+                   Err(Error::new(ErrorKind::TooManyHeaders, 35)));
+        assert_eq!(parse(b"MESSAGE sip:a SIP/2.0\r\nl: 5\r\nl: 6\r\n\r\nhello", 25),
+                   Err(Error::new(ErrorKind::ContentLength, 29)));
+    }
+}
@@ -0,0 +1,192 @@
+//! Conversions from and to the types of higher-level SIP crates.
+//!
+//! Each integration lives behind a cargo feature named after the crate,
+//! so parsip can be used as a fast front-end parser without hand-written
+//! glue:
+//!
+//! - `rsip`: `TryFrom<&Request>` for `rsip::Request`,
+//!   `TryFrom<&Response>` for `rsip::Response`, and `TryFrom` of both rsip
+//!   messages for `owned::OwnedMessage`. Only SIP/2.0 (and SIP/1.x)
+//!   messages convert, as those are the versions rsip can represent.
+//!
+//! Conversions for the sip-types crate are deliberately out of scope for
+//! now; rsip is the only integration.
+
+#[cfg(feature = "rsip")]
+mod rsip_impls {
+    use std::convert::{TryFrom, TryInto};
+    use rsip;
+    use owned::OwnedMessage;
+    use sip::{Header, Request, Response, SipVersion, Status, EMPTY_HEADER};
+
+    fn version(version: Option<SipVersion>) -> Result<rsip::Version, rsip::Error> {
+        match version {
+            Some(SipVersion(1, _)) => Ok(rsip::Version::V1),
+            Some(SipVersion(2, 0)) => Ok(rsip::Version::V2),
+            Some(_) => Err(rsip::Error::ParseError("Unrecognized SIP version".into())),
+            None => Err(incomplete()),
+        }
+    }
+
+    fn headers(headers: &[Header]) -> Result<rsip::Headers, rsip::Error> {
+        headers.iter()
+            .map(|header| {
+                rsip::headers::header::Tokenizer::from((header.name.as_bytes(), header.value.as_bytes()))
+                    .try_into()
+            })
+            .collect::<Result<Vec<rsip::Header>, rsip::Error>>()
+            .map(rsip::Headers::from)
+    }
+
+    fn incomplete() -> rsip::Error {
+        rsip::Error::ParseError("incomplete message".into())
+    }
+
+    impl<'a, 'h, 'b> TryFrom<&'a Request<'h, 'b>> for rsip::Request {
+        type Error = rsip::Error;
+
+        fn try_from(req: &'a Request<'h, 'b>) -> Result<rsip::Request, rsip::Error> {
+            Ok(rsip::Request {
+                method: req.method.ok_or_else(incomplete)?.parse()?,
+                uri: rsip::Uri::try_from(req.path.ok_or_else(incomplete)?)?,
+                version: version(req.version)?,
+                headers: headers(req.headers)?,
+                body: req.body.map_or_else(Vec::new, <[u8]>::to_vec),
+            })
+        }
+    }
+
+    impl<'a, 'h, 'b> TryFrom<&'a Response<'h, 'b>> for rsip::Response {
+        type Error = rsip::Error;
+
+        fn try_from(res: &'a Response<'h, 'b>) -> Result<rsip::Response, rsip::Error> {
+            let code = res.code.ok_or_else(incomplete)?;
+            let status_code = match rsip::StatusCode::from(code) {
+                rsip::StatusCode::Other(code, _) => {
+                    rsip::StatusCode::Other(code, res.reason.unwrap_or("").into())
+                }
+                status_code => status_code,
+            };
+            Ok(rsip::Response {
+                status_code,
+                version: version(res.version)?,
+                headers: headers(res.headers)?,
+                body: res.body.map_or_else(Vec::new, <[u8]>::to_vec),
+            })
+        }
+    }
+
+    fn not_sip() -> rsip::Error {
+        rsip::Error::ParseError("rsip message is not valid SIP".into())
+    }
+
+    impl<'a> TryFrom<&'a rsip::Request> for OwnedMessage {
+        type Error = rsip::Error;
+
+        fn try_from(req: &'a rsip::Request) -> Result<OwnedMessage, rsip::Error> {
+            let rendered = req.to_string();
+            let mut headers = vec![EMPTY_HEADER; req.headers.iter().count()];
+            let mut parsed = Request::new(&mut headers);
+            match parsed.parse_message(rendered.as_bytes()) {
+                Ok(Status::Complete(len)) if len == rendered.len() => {
+                    Ok(OwnedMessage::from(&parsed))
+                }
+                _ => Err(not_sip()),
+            }
+        }
+    }
+
+    impl<'a> TryFrom<&'a rsip::Response> for OwnedMessage {
+        type Error = rsip::Error;
+
+        fn try_from(res: &'a rsip::Response) -> Result<OwnedMessage, rsip::Error> {
+            let rendered = res.to_string();
+            let mut headers = vec![EMPTY_HEADER; res.headers.iter().count()];
+            let mut parsed = Response::new(&mut headers);
+            match parsed.parse_message(rendered.as_bytes()) {
+                Ok(Status::Complete(len)) if len == rendered.len() => {
+                    Ok(OwnedMessage::from(&parsed))
+                }
+                _ => Err(not_sip()),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::convert::TryFrom;
+        use rsip;
+        use rsip::prelude::UntypedHeader;
+        use owned::OwnedMessage;
+        use sip::{Request, Response, EMPTY_HEADER};
+
+        #[test]
+        fn test_request_into_rsip() {
+            let buf = b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+                        Call-ID: a84b4c76e66710\r\n\
+                        X-Custom: yes\r\n\r\n";
+            let mut headers = [EMPTY_HEADER; 4];
+            let mut req = Request::new(&mut headers);
+            req.parse(buf).unwrap();
+
+            let converted = rsip::Request::try_from(&req).unwrap();
+            assert_eq!(converted.method, rsip::Method::Invite);
+            assert_eq!(converted.uri.to_string(), "sip:bob@biloxi.com");
+            assert_eq!(converted.version, rsip::Version::V2);
+            let headers: Vec<_> = converted.headers.iter().cloned().collect();
+            assert_eq!(headers[0],
+                       rsip::Header::CallId(rsip::headers::CallId::new("a84b4c76e66710")));
+            assert_eq!(headers[1], rsip::Header::Other("X-Custom".into(), "yes".into()));
+        }
+
+        #[test]
+        fn test_response_into_rsip() {
+            let buf = b"SIP/2.0 299 Almost OK\r\n\r\n";
+            let mut headers = [EMPTY_HEADER; 4];
+            let mut res = Response::new(&mut headers);
+            res.parse(buf).unwrap();
+
+            let converted = rsip::Response::try_from(&res).unwrap();
+            assert_eq!(converted.status_code, rsip::StatusCode::Other(299, "Almost OK".into()));
+
+            let buf = b"SIP/2.1 200 OK\r\n\r\n";
+            let mut headers = [EMPTY_HEADER; 4];
+            let mut res = Response::new(&mut headers);
+            res.parse(buf).unwrap();
+            assert!(rsip::Response::try_from(&res).is_err());
+        }
+
+        #[test]
+        fn test_incomplete_request_into_rsip() {
+            let mut headers = [EMPTY_HEADER; 4];
+            let req = Request::new(&mut headers);
+            assert!(rsip::Request::try_from(&req).is_err());
+        }
+
+        #[test]
+        fn test_rsip_into_owned() {
+            let res = rsip::Response {
+                status_code: rsip::StatusCode::Ringing,
+                version: rsip::Version::V2,
+                headers: rsip::Header::CallId(rsip::headers::CallId::new("abc")).into(),
+                body: b"hello".to_vec(),
+            };
+            let owned = OwnedMessage::try_from(&res).unwrap();
+            assert_eq!(owned.code(), Some(180));
+            assert_eq!(owned.reason(), Some("Ringing"));
+            assert_eq!(owned.header("Call-ID"), Some(&b"abc"[..]));
+            assert_eq!(owned.body(), b"hello");
+
+            let req = rsip::Request {
+                method: rsip::Method::Message,
+                uri: rsip::Uri::try_from("sip:bob@biloxi.com").unwrap(),
+                version: rsip::Version::V2,
+                headers: rsip::Header::ContentLength(5.into()).into(),
+                body: b"hello".to_vec(),
+            };
+            let owned = OwnedMessage::try_from(&req).unwrap();
+            assert_eq!(owned.method(), Some("MESSAGE"));
+            assert_eq!(owned.body(), b"hello");
+        }
+    }
+}
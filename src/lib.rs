@@ -1,15 +1,40 @@
 #![cfg_attr(not(feature = "std"),  no_std)]
-// FIXME: unnecessary parentheses around function argument caused by do_parse!
-// #![cfg_attr(test, deny(warnings))]
+#![cfg_attr(test, deny(warnings))]
 #![deny(missing_docs)]
 #![deny(dead_code)]
 //! # parsip
 //!
 //! A push library for parsing SIP requests and responses.
 //!
+//! ## Optional features
+//!
+//! - `std` (default): allocating helpers, such as `owned::OwnedMessage`.
+//! - `rsip`: conversions from and to the types of the
+//!   [rsip](https://crates.io/crates/rsip) crate.
+//! - `embedded-io`: `embedded::MessageReader`, receiving messages from an
+//!   [embedded-io](https://crates.io/crates/embedded-io) source without std.
+//! - `async`: `stream::MessageStream`, a `futures_core::Stream` of the
+//!   messages read from any `futures_io::AsyncRead`.
+//! - `generator`: `generate::Generator`, writing families of valid messages
+//!   for load tests and benchmarks.
+//! - `sdp`: `sdp::SdpSession`, a zero-copy parser of the SDP bodies carried
+//!   by INVITEs.
+//! - `serde`: `Serialize` for the parsed messages, headers and typed
+//!   headers, and `Deserialize` for the owned messages.
+//!
 
+extern crate memchr;
+#[cfg(feature = "rsip")]
+extern crate rsip;
+#[cfg(feature = "embedded-io")]
+extern crate embedded_io;
+#[cfg(feature = "async")]
+extern crate futures_core;
+#[cfg(feature = "async")]
+extern crate futures_io;
+#[cfg(feature = "serde")]
 #[macro_use]
-extern crate nom;
+extern crate serde;
 
 #[cfg(not(feature = "std"))]
 mod std {
@@ -17,6 +42,42 @@ mod std {
 }
 
 mod sip;
+mod method;
+mod status;
 mod lookup;
+mod scan;
+#[cfg(feature = "serde")]
+mod ser;
+pub mod framing;
+pub mod capture;
+pub mod classify;
+pub mod correlation;
+pub mod encode;
+pub mod escape;
+#[cfg(feature = "generator")]
+pub mod generate;
+pub mod headers;
+pub mod incremental;
+pub mod multipart;
+pub mod params;
+pub mod arena;
+pub mod rewrite;
+#[cfg(feature = "sdp")]
+pub mod sdp;
+pub mod registrar;
+pub mod routing;
+pub mod sips;
+pub mod transport;
+pub mod uri;
+pub mod validate;
+#[cfg(feature = "std")]
+pub mod owned;
+mod interop;
+#[cfg(feature = "embedded-io")]
+pub mod embedded;
+#[cfg(feature = "async")]
+pub mod stream;
 
 pub use sip::*;
+pub use method::Method;
+pub use status::{StatusClass, StatusCode};
@@ -1,11 +1,31 @@
 
+/// A set of bytes packed into a 256-bit bitmap, so that several character
+/// classes consulted in the same loop fit in a single cache line.
+type ByteSet = [u64; 4];
+
 macro_rules! byte_map {
-    ($($flag:expr,)*) => ([
-        $($flag != 0,)*
-    ])
+    ($($flag:expr,)*) => (pack(&[$($flag != 0,)*]))
+}
+
+/// Packs one flag per byte value into a `ByteSet`, at compile time.
+const fn pack(flags: &[bool; 256]) -> ByteSet {
+    let mut set = [0; 4];
+    let mut b = 0;
+    while b < 256 {
+        if flags[b] {
+            set[b >> 6] |= 1 << (b & 63);
+        }
+        b += 1;
+    }
+    set
+}
+
+#[inline]
+const fn contains(set: &ByteSet, b: u8) -> bool {
+    set[(b >> 6) as usize] >> (b & 63) & 1 != 0
 }
 
-static TOKEN_MAP: [bool; 256] = byte_map![
+const TOKEN_MAP: ByteSet = byte_map![
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     0, 1, 0, 0, 0, 1, 0, 1, 0, 0, 1, 1, 0, 1, 1, 0,
@@ -24,13 +44,13 @@ static TOKEN_MAP: [bool; 256] = byte_map![
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
 ];
 
-static REQUEST_URI_MAP: [bool; 256] = byte_map![
+const REQUEST_URI_MAP: ByteSet = byte_map![
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     0, 1, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 1, 0, 1,
     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
-    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 1, 0, 1,
     0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 1, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -43,7 +63,7 @@ static REQUEST_URI_MAP: [bool; 256] = byte_map![
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
 ];
 
-static REASON_PHRASE_MAP: [bool; 256] = byte_map![
+const REASON_PHRASE_MAP: ByteSet = byte_map![
     0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     1, 1, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
@@ -62,8 +82,8 @@ static REASON_PHRASE_MAP: [bool; 256] = byte_map![
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
 ];
 
-static HEADER_VALUE_MAP: [bool; 256] = byte_map![
-    1, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 0, 0, 1, 0, 0,
+const HEADER_VALUE_MAP: ByteSet = byte_map![
+    0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 0, 0, 1, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
@@ -83,22 +103,22 @@ static HEADER_VALUE_MAP: [bool; 256] = byte_map![
 
 /// Determines if byte is a token char.
 #[inline]
-pub fn is_token(b: u8) -> bool {
-    TOKEN_MAP[b as usize]
+pub const fn is_token(b: u8) -> bool {
+    contains(&TOKEN_MAP, b)
 }
 
 #[inline]
-pub fn is_request_uri(b: u8) -> bool {
-    REQUEST_URI_MAP[b as usize]
+pub const fn is_request_uri(b: u8) -> bool {
+    contains(&REQUEST_URI_MAP, b)
 }
 
 #[inline]
-pub fn is_reason_phrase(b: u8) -> bool {
-    REASON_PHRASE_MAP[b as usize]
+pub const fn is_reason_phrase(b: u8) -> bool {
+    contains(&REASON_PHRASE_MAP, b)
 }
 
 /// Determines if byte is a header value char.
 #[inline]
-pub fn is_header_value(b: u8) -> bool {
-    HEADER_VALUE_MAP[b as usize]
+pub const fn is_header_value(b: u8) -> bool {
+    contains(&HEADER_VALUE_MAP, b)
 }
@@ -0,0 +1,128 @@
+//! Request methods.
+
+use std::fmt;
+use sip::Request;
+
+/// A request method, with the ones of RFC 3261 and its extensions known.
+///
+/// Methods are case-sensitive, so `invite` is an extension method.
+///
+/// # Example
+///
+/// ```
+/// use parsip::Method;
+///
+/// let mut headers = [parsip::EMPTY_HEADER; 4];
+/// let mut req = parsip::Request::new(&mut headers);
+/// req.parse(b"PUBLISH sip:presentity@example.com SIP/2.0\r\n\r\n").unwrap();
+/// assert_eq!(req.typed_method(), Some(Method::Publish));
+/// assert_eq!(Method::parse("FOO"), Method::Extension("FOO"));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Method<'a> {
+    /// `INVITE`.
+    Invite,
+    /// `ACK`.
+    Ack,
+    /// `BYE`.
+    Bye,
+    /// `CANCEL`.
+    Cancel,
+    /// `REGISTER`.
+    Register,
+    /// `OPTIONS`.
+    Options,
+    /// `SUBSCRIBE`, see [RFC 6665](https://tools.ietf.org/html/rfc6665).
+    Subscribe,
+    /// `NOTIFY`, see [RFC 6665](https://tools.ietf.org/html/rfc6665).
+    Notify,
+    /// `REFER`, see [RFC 3515](https://tools.ietf.org/html/rfc3515).
+    Refer,
+    /// `INFO`, see [RFC 6086](https://tools.ietf.org/html/rfc6086).
+    Info,
+    /// `MESSAGE`, see [RFC 3428](https://tools.ietf.org/html/rfc3428).
+    Message,
+    /// `UPDATE`, see [RFC 3311](https://tools.ietf.org/html/rfc3311).
+    Update,
+    /// `PRACK`, see [RFC 3262](https://tools.ietf.org/html/rfc3262).
+    Prack,
+    /// `PUBLISH`, see [RFC 3903](https://tools.ietf.org/html/rfc3903).
+    Publish,
+    /// Any other method.
+    Extension(&'a str),
+}
+
+impl<'a> Method<'a> {
+    /// Looks up a method by its name.
+    pub fn parse(name: &'a str) -> Method<'a> {
+        match name {
+            "INVITE" => Method::Invite,
+            "ACK" => Method::Ack,
+            "BYE" => Method::Bye,
+            "CANCEL" => Method::Cancel,
+            "REGISTER" => Method::Register,
+            "OPTIONS" => Method::Options,
+            "SUBSCRIBE" => Method::Subscribe,
+            "NOTIFY" => Method::Notify,
+            "REFER" => Method::Refer,
+            "INFO" => Method::Info,
+            "MESSAGE" => Method::Message,
+            "UPDATE" => Method::Update,
+            "PRACK" => Method::Prack,
+            "PUBLISH" => Method::Publish,
+            name => Method::Extension(name),
+        }
+    }
+
+    /// The name of the method.
+    pub fn as_str(&self) -> &'a str {
+        match *self {
+            Method::Invite => "INVITE",
+            Method::Ack => "ACK",
+            Method::Bye => "BYE",
+            Method::Cancel => "CANCEL",
+            Method::Register => "REGISTER",
+            Method::Options => "OPTIONS",
+            Method::Subscribe => "SUBSCRIBE",
+            Method::Notify => "NOTIFY",
+            Method::Refer => "REFER",
+            Method::Info => "INFO",
+            Method::Message => "MESSAGE",
+            Method::Update => "UPDATE",
+            Method::Prack => "PRACK",
+            Method::Publish => "PUBLISH",
+            Method::Extension(name) => name,
+        }
+    }
+}
+
+impl<'a> fmt::Display for Method<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<'h, 'b> Request<'h, 'b> {
+    /// The method of the request, once parsed.
+    #[inline]
+    pub fn typed_method(&self) -> Option<Method<'b>> {
+        self.method.map(Method::parse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Method;
+
+    #[test]
+    fn test_round_trip() {
+        let methods = [Method::Invite, Method::Ack, Method::Bye, Method::Cancel, Method::Register,
+                       Method::Options, Method::Subscribe, Method::Notify, Method::Refer,
+                       Method::Info, Method::Message, Method::Update, Method::Prack,
+                       Method::Publish, Method::Extension("invite")];
+        for method in &methods {
+            assert_eq!(Method::parse(method.as_str()), *method);
+        }
+        assert_eq!(Method::Prack.to_string(), "PRACK");
+    }
+}
@@ -0,0 +1,255 @@
+//! Splitting `multipart/mixed` bodies, such as an INVITE carrying SDP
+//! along with ISUP or XML parts, into their parts.
+//!
+//! From [RFC 2046](https://tools.ietf.org/html/rfc2046#section-5.1.1):
+//!
+//! > ```notrust
+//! > multipart-body := [preamble CRLF]
+//! >                   dash-boundary transport-padding CRLF
+//! >                   body-part *encapsulation
+//! >                   close-delimiter transport-padding
+//! >                   [CRLF epilogue]
+//! > encapsulation := delimiter transport-padding
+//! >                  CRLF body-part
+//! > delimiter := CRLF dash-boundary
+//! > ```
+//!
+//! # Example
+//!
+//! ```
+//! use parsip::multipart::{boundary, parts};
+//!
+//! let content_type = b"multipart/mixed;boundary=unique-boundary-1";
+//! let body = b"--unique-boundary-1\r\n\
+//!              Content-Type: application/sdp\r\n\
+//!              \r\n\
+//!              v=0\r\n\
+//!              \r\n\
+//!              --unique-boundary-1\r\n\
+//!              Content-Type: application/isup\r\n\
+//!              \r\n\
+//!              \x01\x00\r\n\
+//!              --unique-boundary-1--\r\n";
+//!
+//! let boundary = boundary(content_type).unwrap();
+//! let mut parts = parts(body, boundary);
+//! let sdp = parts.next().unwrap().unwrap();
+//! assert_eq!(sdp.body, b"v=0\r\n");
+//!
+//! let isup = parts.next().unwrap().unwrap();
+//! let mut headers = [parsip::EMPTY_HEADER; 4];
+//! let headers = isup.headers(&mut headers).unwrap();
+//! assert_eq!(headers[0].value, b"application/isup");
+//! assert_eq!(isup.body, b"\x01\x00");
+//! assert!(parts.next().is_none());
+//! ```
+
+use memchr::memmem;
+use params;
+use sip::{parse_headers, Error, ErrorKind, Header, Status};
+
+/// Errors in splitting a multipart body.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MultipartError {
+    /// The body holds no delimiter line.
+    NoDelimiter,
+    /// The last part is not followed by a close-delimiter.
+    Unterminated,
+    /// A part has no empty line ending its headers.
+    Head,
+}
+
+/// The `boundary` parameter of a Content-Type header value, without
+/// quotes.
+///
+/// # Example
+///
+/// ```
+/// let content_type = b"multipart/mixed; boundary=\"simple boundary\"";
+/// assert_eq!(parsip::multipart::boundary(content_type), Some(&b"simple boundary"[..]));
+/// ```
+pub fn boundary(content_type: &[u8]) -> Option<&[u8]> {
+    let semi = content_type.iter().position(|&b| b == b';')?;
+    let value = params::parse(&content_type[semi..])
+        .find(|&(name, _)| name.eq_ignore_ascii_case(b"boundary"))?
+        .1?;
+    // boundaries hold neither quotes nor backslashes
+    let value = match value {
+        [b'"', inner @ .., b'"'] => inner,
+        value => value,
+    };
+    if value.is_empty() {
+        return None;
+    }
+    Some(value)
+}
+
+/// Iterates over the parts of a multipart `body` delimited by `boundary`.
+///
+/// The iterator stops after the close-delimiter or the first error.
+#[inline]
+pub fn parts<'a>(body: &'a [u8], boundary: &'a [u8]) -> Parts<'a> {
+    Parts { rest: body, boundary, started: false, done: false }
+}
+
+/// A part of a multipart body.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Part<'a> {
+    /// The header section of the part, up to and including the empty line
+    /// ending it.
+    pub head: &'a [u8],
+    /// The body of the part, without the `CRLF` preceding the next
+    /// delimiter.
+    pub body: &'a [u8],
+}
+
+impl<'a> Part<'a> {
+    /// Parses the header section of the part into `headers`.
+    pub fn headers<'h>(&self, headers: &'h mut [Header<'a>]) -> Result<&'h [Header<'a>], Error> {
+        match parse_headers(self.head, headers)? {
+            Status::Complete((_, headers)) => Ok(headers),
+            Status::Partial(_) => Err(Error::new(ErrorKind::NewLine, self.head.len())),
+        }
+    }
+}
+
+/// An iterator over the parts of a multipart body, see `parts`.
+#[derive(Clone, Debug)]
+pub struct Parts<'a> {
+    rest: &'a [u8],
+    boundary: &'a [u8],
+    started: bool,
+    done: bool,
+}
+
+impl<'a> Parts<'a> {
+    /// Consumes the bytes up to the end of the next delimiter line,
+    /// returning the bytes before the delimiter and whether it is the
+    /// close-delimiter.
+    fn delimiter(&mut self) -> Result<(&'a [u8], bool), MultipartError> {
+        let rest = self.rest;
+        // the first delimiter may start the body, with no CRLF before it
+        if !self.started && rest.starts_with(b"--") {
+            if let Some(line) = self.delimiter_line(&rest[2..]) {
+                return Ok((&rest[..0], line));
+            }
+        }
+        let mut from = 0;
+        loop {
+            let pos = match memmem::find(&rest[from..], b"\r\n--") {
+                Some(pos) => from + pos,
+                None if self.started => return Err(MultipartError::Unterminated),
+                None => return Err(MultipartError::NoDelimiter),
+            };
+            if let Some(line) = self.delimiter_line(&rest[pos + 4..]) {
+                return Ok((&rest[..pos], line));
+            }
+            from = pos + 2;
+        }
+    }
+
+    /// Checks that `line` continues a delimiter line, starting with the
+    /// boundary, and consumes it, returning whether it is the
+    /// close-delimiter.
+    fn delimiter_line(&mut self, line: &'a [u8]) -> Option<bool> {
+        if !line.starts_with(self.boundary) {
+            return None;
+        }
+        let line = &line[self.boundary.len()..];
+        if line.starts_with(b"--") {
+            self.rest = &line[2..];
+            return Some(true);
+        }
+        let padding = line.iter().take_while(|&&b| b == b' ' || b == b'\t').count();
+        match line[padding..] {
+            [b'\r', b'\n', ..] => {
+                self.rest = &line[padding + 2..];
+                Some(false)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Iterator for Parts<'a> {
+    type Item = Result<Part<'a>, MultipartError>;
+
+    fn next(&mut self) -> Option<Result<Part<'a>, MultipartError>> {
+        if self.done {
+            return None;
+        }
+        if !self.started {
+            // skip the preamble
+            match self.delimiter() {
+                Ok((_, false)) => self.started = true,
+                Ok((_, true)) => {
+                    self.done = true;
+                    return None;
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        let (content, close) = match self.delimiter() {
+            Ok(delimiter) => delimiter,
+            Err(err) => {
+                self.done = true;
+                return Some(Err(err));
+            }
+        };
+        self.done = close;
+        let head = if content.starts_with(b"\r\n") {
+            2
+        } else {
+            match memmem::find(content, b"\r\n\r\n") {
+                Some(pos) => pos + 4,
+                None => {
+                    self.done = true;
+                    return Some(Err(MultipartError::Head));
+                }
+            }
+        };
+        Some(Ok(Part { head: &content[..head], body: &content[head..] }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{boundary, parts, MultipartError, Part};
+
+    #[test]
+    fn test_boundary() {
+        assert_eq!(boundary(b"multipart/mixed ; charset=x;BOUNDARY=b1"), Some(&b"b1"[..]));
+        assert_eq!(boundary(b"multipart/mixed;boundary=\"\""), None);
+        assert_eq!(boundary(b"multipart/mixed"), None);
+    }
+
+    #[test]
+    fn test_parts() {
+        let body = b"preamble\r\n--b1 \t\r\n\r\nno headers\r\n--b1x\r\n\r\n\
+                     --b1\r\nA: 1\r\n\r\n\r\n--b1--\r\nepilogue";
+        let found: Vec<_> = parts(body, b"b1").collect();
+        assert_eq!(found,
+                   [Ok(Part { head: b"\r\n", body: b"no headers\r\n--b1x\r\n" }),
+                    Ok(Part { head: b"A: 1\r\n\r\n", body: b"" })]);
+        assert_eq!(parts(b"--b1--", b"b1").next(), None);
+    }
+
+    #[test]
+    fn test_errors() {
+        let errors = |body: &'static [u8]| -> Vec<_> {
+            parts(body, b"b1").filter_map(Result::err).collect()
+        };
+        assert_eq!(errors(b"no delimiter"), [MultipartError::NoDelimiter]);
+        assert_eq!(errors(b"--b1x\r\n"), [MultipartError::NoDelimiter]);
+        assert_eq!(errors(b"--b1 x\r\n"), [MultipartError::NoDelimiter]);
+        assert_eq!(errors(b"--b1\r\n\r\nbody"), [MultipartError::Unterminated]);
+        assert_eq!(errors(b"--b1\r\nA: 1\r\n--b1--"), [MultipartError::Head]);
+
+        let part = parts(b"--b1\r\nA\r\n\r\n\r\n--b1--", b"b1").next().unwrap().unwrap();
+        let mut headers = [::sip::EMPTY_HEADER; 1];
+        assert!(part.headers(&mut headers).is_err());
+    }
+}
@@ -0,0 +1,545 @@
+//! Owned messages, independent of the receive buffer.
+//!
+//! Parsed `Request`s and `Response`s borrow the buffer they were parsed
+//! from, which ties them to a single thread and to the lifetime of that
+//! buffer. `OwnedMessage` copies the parsed parts into one allocation plus
+//! a small index table, so it is `Send + Sync + 'static` and can be wrapped
+//! in an `Arc` to be shared across worker threads without re-parsing.
+//!
+//! `RequestOwned`, `ResponseOwned` and `HeaderOwned` instead mirror the
+//! borrowed types field by field, for messages that are stored and then
+//! modified or serialized again, such as in a transaction table.
+
+use std::str;
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use headers::names_eq;
+#[cfg(feature = "serde")]
+use ser::Buf;
+use sip::{Header, HeaderValue, Request, Response, SipVersion};
+
+/// Start and end offsets into the buffer of an `OwnedMessage`.
+type Span = (usize, usize);
+
+#[derive(Clone, Debug, PartialEq)]
+enum StartLine {
+    Request { method: Option<Span>, path: Option<Span> },
+    Response { code: Option<u16>, reason: Option<Span> },
+}
+
+/// An owned, immutable copy of a parsed message.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+/// use parsip::owned::OwnedMessage;
+///
+/// let msg = {
+///     let buf = b"OPTIONS sip:carol@chicago.com SIP/2.0\r\nCall-ID: a84b4c76e66710\r\n\r\n";
+///     let mut headers = [parsip::EMPTY_HEADER; 8];
+///     let mut req = parsip::Request::new(&mut headers);
+///     req.parse(buf).unwrap();
+///     Arc::new(OwnedMessage::from(&req))
+/// };
+///
+/// let shared = msg.clone();
+/// thread::spawn(move || {
+///     assert_eq!(shared.method(), Some("OPTIONS"));
+///     assert_eq!(shared.header("call-id"), Some(&b"a84b4c76e66710"[..]));
+/// }).join().unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnedMessage {
+    buf: Box<[u8]>,
+    start: StartLine,
+    version: Option<SipVersion>,
+    headers: Box<[(Span, Span)]>,
+    body: Span,
+}
+
+/// Accumulates copied slices into a single buffer.
+struct Builder {
+    buf: Vec<u8>,
+}
+
+impl Builder {
+    fn with_capacity(capacity: usize) -> Builder {
+        Builder { buf: Vec::with_capacity(capacity) }
+    }
+
+    fn push(&mut self, bytes: &[u8]) -> Span {
+        let start = self.buf.len();
+        self.buf.extend_from_slice(bytes);
+        (start, self.buf.len())
+    }
+
+    fn push_str(&mut self, s: Option<&str>) -> Option<Span> {
+        s.map(|s| self.push(s.as_bytes()))
+    }
+
+    fn push_headers(&mut self, headers: &[Header]) -> Box<[(Span, Span)]> {
+        headers.iter()
+            .map(|header| (self.push(header.name.as_bytes()), self.push(header.value.as_bytes())))
+            .collect::<Vec<_>>()
+            .into_boxed_slice()
+    }
+}
+
+/// Total length of the slices copied from `headers`.
+fn headers_len(headers: &[Header]) -> usize {
+    headers.iter().map(|header| header.name.len() + header.value.len()).sum()
+}
+
+impl<'a, 'h, 'b> From<&'a Request<'h, 'b>> for OwnedMessage {
+    fn from(req: &'a Request<'h, 'b>) -> OwnedMessage {
+        let len = req.method.map_or(0, str::len) + req.path.map_or(0, str::len) +
+                  headers_len(req.headers) + req.body.map_or(0, <[u8]>::len);
+        let mut builder = Builder::with_capacity(len);
+        let method = builder.push_str(req.method);
+        let path = builder.push_str(req.path);
+        let headers = builder.push_headers(req.headers);
+        let body = builder.push(req.body.unwrap_or(&[]));
+        OwnedMessage {
+            buf: builder.buf.into_boxed_slice(),
+            start: StartLine::Request { method, path },
+            version: req.version,
+            headers,
+            body,
+        }
+    }
+}
+
+impl<'a, 'h, 'b> From<&'a Response<'h, 'b>> for OwnedMessage {
+    fn from(res: &'a Response<'h, 'b>) -> OwnedMessage {
+        let len = res.reason.map_or(0, str::len) + headers_len(res.headers) +
+                  res.body.map_or(0, <[u8]>::len);
+        let mut builder = Builder::with_capacity(len);
+        let reason = builder.push_str(res.reason);
+        let headers = builder.push_headers(res.headers);
+        let body = builder.push(res.body.unwrap_or(&[]));
+        OwnedMessage {
+            buf: builder.buf.into_boxed_slice(),
+            start: StartLine::Response { code: res.code, reason },
+            version: res.version,
+            headers,
+            body,
+        }
+    }
+}
+
+impl OwnedMessage {
+    #[inline]
+    fn bytes(&self, span: Span) -> &[u8] {
+        &self.buf[span.0..span.1]
+    }
+
+    #[inline]
+    fn str(&self, span: Span) -> &str {
+        // every string span was copied from a `&str`
+        unsafe { str::from_utf8_unchecked(self.bytes(span)) }
+    }
+
+    /// Returns `true` if this is a copy of a `Request`.
+    #[inline]
+    pub fn is_request(&self) -> bool {
+        match self.start {
+            StartLine::Request { .. } => true,
+            StartLine::Response { .. } => false,
+        }
+    }
+
+    /// Returns `true` if this is a copy of a `Response`.
+    #[inline]
+    pub fn is_response(&self) -> bool {
+        !self.is_request()
+    }
+
+    /// The request method, `None` for responses.
+    pub fn method(&self) -> Option<&str> {
+        match self.start {
+            StartLine::Request { method, .. } => method.map(|span| self.str(span)),
+            StartLine::Response { .. } => None,
+        }
+    }
+
+    /// The request path, `None` for responses.
+    pub fn path(&self) -> Option<&str> {
+        match self.start {
+            StartLine::Request { path, .. } => path.map(|span| self.str(span)),
+            StartLine::Response { .. } => None,
+        }
+    }
+
+    /// The response code, `None` for requests.
+    pub fn code(&self) -> Option<u16> {
+        match self.start {
+            StartLine::Request { .. } => None,
+            StartLine::Response { code, .. } => code,
+        }
+    }
+
+    /// The response reason-phrase, `None` for requests.
+    pub fn reason(&self) -> Option<&str> {
+        match self.start {
+            StartLine::Request { .. } => None,
+            StartLine::Response { reason, .. } => reason.map(|span| self.str(span)),
+        }
+    }
+
+    /// The message version.
+    #[inline]
+    pub fn version(&self) -> Option<SipVersion> {
+        self.version
+    }
+
+    /// Iterates over the message headers, in their original order.
+    #[inline]
+    pub fn headers(&self) -> OwnedHeaders<'_> {
+        OwnedHeaders {
+            msg: self,
+            spans: self.headers.iter(),
+        }
+    }
+
+    /// Returns the value of the first header with a matching name.
+    ///
+    /// Header names are compared case-insensitively, and compact forms
+    /// match their long forms, see `headers::names_eq`.
+    pub fn header(&self, name: &str) -> Option<&[u8]> {
+        self.headers()
+            .find(|header| names_eq(header.name, name))
+            .map(|header| header.value.as_bytes())
+    }
+
+    /// The message body, empty unless parsed with `parse_message` or set by
+    /// `with_body`.
+    #[inline]
+    pub fn body(&self) -> &[u8] {
+        self.bytes(self.body)
+    }
+
+    /// Replaces the message body with a copy of `body`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use parsip::owned::OwnedMessage;
+    ///
+    /// let buf = b"MESSAGE sip:bob@biloxi.com SIP/2.0\r\nContent-Length: 5\r\n\r\nhello";
+    /// let mut headers = [parsip::EMPTY_HEADER; 8];
+    /// let mut req = parsip::Request::new(&mut headers);
+    /// if let Ok(parsip::Status::Complete(len)) = req.parse(buf) {
+    ///     let msg = OwnedMessage::from(&req).with_body(&buf[len..]);
+    ///     assert_eq!(msg.body(), b"hello");
+    /// }
+    /// ```
+    pub fn with_body(self, body: &[u8]) -> OwnedMessage {
+        let mut buf = self.buf.into_vec();
+        buf.truncate(self.body.0);
+        buf.extend_from_slice(body);
+        OwnedMessage {
+            body: (self.body.0, buf.len()),
+            buf: buf.into_boxed_slice(),
+            ..self
+        }
+    }
+}
+
+/// An iterator over the headers of an `OwnedMessage`.
+#[derive(Clone, Debug)]
+pub struct OwnedHeaders<'a> {
+    msg: &'a OwnedMessage,
+    spans: ::std::slice::Iter<'a, (Span, Span)>,
+}
+
+impl<'a> Iterator for OwnedHeaders<'a> {
+    type Item = Header<'a>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Header<'a>> {
+        self.spans.next().map(|&(name, value)| {
+            Header {
+                name: self.msg.str(name),
+                value: HeaderValue::from_parsed(self.msg.bytes(value)),
+            }
+        })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.spans.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for OwnedHeaders<'a> {}
+
+/// An owned copy of a `Header`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HeaderOwned {
+    /// The header name.
+    pub name: String,
+    value: Vec<u8>,
+}
+
+impl HeaderOwned {
+    /// The header value.
+    #[inline]
+    pub fn value(&self) -> HeaderValue<'_> {
+        HeaderValue::from_parsed(&self.value)
+    }
+
+    /// Borrows the header.
+    #[inline]
+    pub fn as_header(&self) -> Header<'_> {
+        Header { name: &self.name, value: self.value() }
+    }
+}
+
+impl<'a, 'b> From<&'a Header<'b>> for HeaderOwned {
+    fn from(header: &'a Header<'b>) -> HeaderOwned {
+        HeaderOwned { name: header.name.into(), value: header.value.as_bytes().into() }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for HeaderOwned {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_header().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for HeaderOwned {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<HeaderOwned, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "Header")]
+        struct Fields {
+            name: String,
+            value: Buf,
+        }
+
+        let Fields { name, value } = Fields::deserialize(deserializer)?;
+        // the value is borrowed without validation afterwards
+        if let Err(kind) = HeaderValue::new(&value.0) {
+            return Err(de::Error::custom(kind.description()));
+        }
+        Ok(HeaderOwned { name, value: value.0 })
+    }
+}
+
+/// An owned copy of a `Request`, see `Request::to_owned`.
+///
+/// # Example
+///
+/// ```
+/// use parsip::owned::RequestOwned;
+///
+/// let owned: RequestOwned = {
+///     let buf = b"BYE sip:alice@pc33.atlanta.com SIP/2.0\r\nCSeq: 1 BYE\r\n\r\n".to_vec();
+///     let mut headers = [parsip::EMPTY_HEADER; 4];
+///     let mut req = parsip::Request::new(&mut headers);
+///     req.parse(&buf).unwrap();
+///     req.to_owned()
+/// };
+/// assert_eq!(owned.method.as_deref(), Some("BYE"));
+///
+/// let mut headers = [parsip::EMPTY_HEADER; 4];
+/// let req = owned.as_request(&mut headers).unwrap();
+/// assert_eq!(req.headers[0].value, b"1 BYE");
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RequestOwned {
+    /// The request method, such as `INVITE`.
+    pub method: Option<String>,
+    /// The request path, such as `sip:callee@domain.com`.
+    pub path: Option<String>,
+    /// The request version, such as `SIP/2.0`.
+    pub version: Option<SipVersion>,
+    /// The request headers.
+    pub headers: Vec<HeaderOwned>,
+    /// The request body.
+    #[cfg_attr(feature = "serde",
+               serde(serialize_with = "::ser::opt_text", deserialize_with = "::ser::opt_buf"))]
+    pub body: Option<Vec<u8>>,
+}
+
+impl RequestOwned {
+    /// Borrows the request, using a slice of headers you allocate.
+    ///
+    /// Returns `None` if `headers` is too small.
+    pub fn as_request<'h, 'a>(&'a self, headers: &'h mut [Header<'a>]) -> Option<Request<'h, 'a>> {
+        let headers = borrow_headers(&self.headers, headers)?;
+        Some(Request {
+            method: self.method.as_deref(),
+            path: self.path.as_deref(),
+            version: self.version,
+            headers,
+            body: self.body.as_deref(),
+        })
+    }
+}
+
+impl<'a, 'h, 'b> From<&'a Request<'h, 'b>> for RequestOwned {
+    fn from(req: &'a Request<'h, 'b>) -> RequestOwned {
+        RequestOwned {
+            method: req.method.map(String::from),
+            path: req.path.map(String::from),
+            version: req.version,
+            headers: req.headers.iter().map(HeaderOwned::from).collect(),
+            body: req.body.map(<[u8]>::to_vec),
+        }
+    }
+}
+
+/// An owned copy of a `Response`, see `Response::to_owned`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ResponseOwned {
+    /// The response version, such as `SIP/2.0`.
+    pub version: Option<SipVersion>,
+    /// The response code, such as `200`.
+    pub code: Option<u16>,
+    /// The response reason-phrase, such as `OK`.
+    pub reason: Option<String>,
+    /// The response headers.
+    pub headers: Vec<HeaderOwned>,
+    /// The response body.
+    #[cfg_attr(feature = "serde",
+               serde(serialize_with = "::ser::opt_text", deserialize_with = "::ser::opt_buf"))]
+    pub body: Option<Vec<u8>>,
+}
+
+impl ResponseOwned {
+    /// Borrows the response, using a slice of headers you allocate.
+    ///
+    /// Returns `None` if `headers` is too small.
+    pub fn as_response<'h, 'a>(&'a self,
+                               headers: &'h mut [Header<'a>])
+                               -> Option<Response<'h, 'a>> {
+        let headers = borrow_headers(&self.headers, headers)?;
+        Some(Response {
+            version: self.version,
+            code: self.code,
+            reason: self.reason.as_deref(),
+            headers,
+            body: self.body.as_deref(),
+        })
+    }
+}
+
+impl<'a, 'h, 'b> From<&'a Response<'h, 'b>> for ResponseOwned {
+    fn from(res: &'a Response<'h, 'b>) -> ResponseOwned {
+        ResponseOwned {
+            version: res.version,
+            code: res.code,
+            reason: res.reason.map(String::from),
+            headers: res.headers.iter().map(HeaderOwned::from).collect(),
+            body: res.body.map(<[u8]>::to_vec),
+        }
+    }
+}
+
+impl<'h, 'b> Request<'h, 'b> {
+    /// Copies the request, so that it no longer borrows the buffer.
+    #[inline]
+    pub fn to_owned(&self) -> RequestOwned {
+        RequestOwned::from(self)
+    }
+}
+
+impl<'h, 'b> Response<'h, 'b> {
+    /// Copies the response, so that it no longer borrows the buffer.
+    #[inline]
+    pub fn to_owned(&self) -> ResponseOwned {
+        ResponseOwned::from(self)
+    }
+}
+
+/// Borrows `owned` into the start of `headers`.
+fn borrow_headers<'h, 'a>(owned: &'a [HeaderOwned],
+                          headers: &'h mut [Header<'a>])
+                          -> Option<&'h mut [Header<'a>]> {
+    let headers = headers.get_mut(..owned.len())?;
+    for (header, owned) in headers.iter_mut().zip(owned) {
+        *header = owned.as_header();
+    }
+    Some(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use sip::{Header, HeaderValue, Response, SipVersion, EMPTY_HEADER};
+    use super::{OwnedMessage, RequestOwned, ResponseOwned};
+
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+    #[test]
+    fn test_owned_message_is_send_sync() {
+        assert_send_sync::<OwnedMessage>();
+        assert_send_sync::<RequestOwned>();
+        assert_send_sync::<ResponseOwned>();
+    }
+
+    #[test]
+    fn test_response_owned() {
+        let owned = {
+            let buf = b"SIP/2.0 180 Ringing\r\nTo: <sip:b>\r\nl: 2\r\n\r\nhi".to_vec();
+            let mut headers = [EMPTY_HEADER; 4];
+            let mut res = Response::new(&mut headers);
+            res.parse_message(&buf).unwrap();
+            res.to_owned()
+        };
+        assert_eq!((owned.code, owned.reason.as_ref().unwrap().as_str()), (Some(180), "Ringing"));
+        assert_eq!(owned.headers[1].name, "l");
+        assert_eq!(owned.headers[1].value(), b"2");
+        assert_eq!(owned.body, Some(b"hi".to_vec()));
+
+        let mut headers = [EMPTY_HEADER; 2];
+        let res = owned.as_response(&mut headers).unwrap();
+        assert_eq!(res.to_owned(), owned);
+        assert!(owned.as_response(&mut [EMPTY_HEADER; 1]).is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use sip::Request;
+        use super::HeaderOwned;
+
+        let buf = b"MESSAGE sip:b SIP/2.0\r\nSubject: caf\xc3\xa9\r\nl: 2\r\n\r\n\xff\x00";
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse_message(buf).unwrap();
+        let json = serde_json::to_string(&req).unwrap();
+        assert_eq!(json,
+                   "{\"method\":\"MESSAGE\",\"path\":\"sip:b\",\"version\":[2,0],\
+                    \"headers\":[{\"name\":\"Subject\",\"value\":\"caf\u{e9}\"},\
+                    {\"name\":\"l\",\"value\":\"2\"}],\"body\":[255,0]}");
+        assert_eq!(serde_json::from_str::<RequestOwned>(&json).unwrap(), req.to_owned());
+
+        let invalid = "{\"name\":\"A\",\"value\":\"\\r\\n\"}";
+        assert!(serde_json::from_str::<HeaderOwned>(invalid).is_err());
+    }
+
+    #[test]
+    fn test_owned_message_from_response() {
+        let msg = {
+            let buf = b"SIP/2.0 200 OK\r\nVia: SIP/2.0/UDP a\r\nTo: <sip:b>\r\n\r\n".to_vec();
+            let mut headers = [EMPTY_HEADER; 4];
+            let mut res = Response::new(&mut headers);
+            res.parse(&buf).unwrap();
+            OwnedMessage::from(&res)
+        };
+        assert!(msg.is_response());
+        assert_eq!(msg.method(), None);
+        assert_eq!(msg.code(), Some(200));
+        assert_eq!(msg.reason(), Some("OK"));
+        assert_eq!(msg.version(), Some(SipVersion(2, 0)));
+        assert_eq!(msg.headers().len(), 2);
+        assert_eq!(msg.headers().nth(1), Some(Header { name: "To", value: HeaderValue::from_static(b"<sip:b>") }));
+        assert_eq!(msg.header("via"), Some(&b"SIP/2.0/UDP a"[..]));
+    }
+}
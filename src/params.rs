@@ -0,0 +1,152 @@
+//! Parsing of the parameters ending most header values, such as the
+//! `;branch=z9hG4bK776asdhds;rport` of a Via.
+//!
+//! From [RFC 3261](https://tools.ietf.org/html/rfc3261#section-25.1):
+//!
+//! > ```notrust
+//! > generic-param  =  token [ EQUAL gen-value ]
+//! > gen-value      =  token / host / quoted-string
+//! > quoted-string  =  SWS DQUOTE *(qdtext / quoted-pair ) DQUOTE
+//! > quoted-pair    =  "\" (%x00-09 / %x0B-0C / %x0E-7F)
+//! > SEMI           =  SWS ";" SWS
+//! > EQUAL          =  SWS "=" SWS
+//! > ```
+
+use scan::trim;
+
+/// Iterates over the parameters in `params`, as names and values.
+///
+/// A leading `;`, as in the parameters following a URI, is optional, and
+/// linear whitespace around names, values and separators is skipped.
+/// Values are returned as written: quoted strings keep their quotes and
+/// quoted-pairs, and can be decoded with `unquote_into`. A `;` inside a
+/// quoted string does not end the parameter.
+///
+/// # Example
+///
+/// ```
+/// let mut params = parsip::params::parse(b";tag=1928301774 ; lr; foo=\"a;b\"");
+/// assert_eq!(params.next(), Some((&b"tag"[..], Some(&b"1928301774"[..]))));
+/// assert_eq!(params.next(), Some((&b"lr"[..], None)));
+/// assert_eq!(params.next(), Some((&b"foo"[..], Some(&b"\"a;b\""[..]))));
+/// assert_eq!(params.next(), None);
+/// ```
+#[inline]
+pub fn parse<'a>(params: &'a [u8]) -> Params<'a> {
+    Params { rest: params }
+}
+
+/// An iterator over parameters, see `parse`.
+#[derive(Clone, Debug)]
+pub struct Params<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for Params<'a> {
+    type Item = (&'a [u8], Option<&'a [u8]>);
+
+    fn next(&mut self) -> Option<(&'a [u8], Option<&'a [u8]>)> {
+        while !self.rest.is_empty() {
+            let end = param_end(self.rest);
+            let param = trim(&self.rest[..end]);
+            self.rest = self.rest.get(end + 1..).unwrap_or(b"");
+            if param.is_empty() {
+                continue;
+            }
+            // names are tokens, so the first `=` ends the name
+            return Some(match param.iter().position(|&b| b == b'=') {
+                Some(eq) => (trim(&param[..eq]), Some(trim(&param[eq + 1..]))),
+                None => (param, None),
+            });
+        }
+        None
+    }
+}
+
+/// Offset of the `;` ending the first parameter, or the length of `params`.
+fn param_end(params: &[u8]) -> usize {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (idx, &b) in params.iter().enumerate() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match b {
+            b'\\' if quoted => escaped = true,
+            b'"' => quoted = !quoted,
+            b';' if !quoted => return idx,
+            _ => {}
+        }
+    }
+    params.len()
+}
+
+/// Decodes a parameter value into `dst`, removing the quotes and resolving
+/// the quoted-pairs of a quoted string. Other values are copied as they
+/// are.
+///
+/// Returns the number of bytes written, or `None` if a quoted string is
+/// not terminated or `dst` is too small.
+///
+/// # Example
+///
+/// ```
+/// let mut buf = [0u8; 16];
+/// let len = parsip::params::unquote_into(b"\"Bob \\\"B\\\"\"", &mut buf).unwrap();
+/// assert_eq!(&buf[..len], b"Bob \"B\"");
+/// ```
+pub fn unquote_into(value: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let inner = match value {
+        [b'"', inner @ ..] => inner,
+        _ => {
+            dst.get_mut(..value.len())?.copy_from_slice(value);
+            return Some(value.len());
+        }
+    };
+    let mut len = 0;
+    let mut idx = 0;
+    loop {
+        let b = match *inner.get(idx)? {
+            b'"' if idx + 1 == inner.len() => return Some(len),
+            b'"' => return None,
+            b'\\' => {
+                idx += 1;
+                *inner.get(idx)?
+            }
+            b => b,
+        };
+        idx += 1;
+        *dst.get_mut(len)? = b;
+        len += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, unquote_into};
+
+    #[test]
+    fn test_parse() {
+        let params: Vec<_> = parse(b"branch = z9hG4bK;;received=\"a\\\";b\" ;\r\n rport").collect();
+        assert_eq!(params,
+                   [(&b"branch"[..], Some(&b"z9hG4bK"[..])),
+                    (&b"received"[..], Some(&b"\"a\\\";b\""[..])),
+                    (&b"rport"[..], None)]);
+        assert_eq!(parse(b"expires=").next(), Some((&b"expires"[..], Some(&b""[..]))));
+        assert_eq!(parse(b" ; ").next(), None);
+    }
+
+    #[test]
+    fn test_unquote_into() {
+        let mut buf = [0u8; 8];
+        assert_eq!(unquote_into(b"token", &mut buf), Some(5));
+        assert_eq!(&buf[..5], b"token");
+        assert_eq!(unquote_into(b"\"\"", &mut buf), Some(0));
+        assert_eq!(unquote_into(b"\"a\\\\b\"", &mut buf), Some(3));
+        assert_eq!(&buf[..3], b"a\\b");
+        assert_eq!(unquote_into(b"\"open", &mut buf), None);
+        assert_eq!(unquote_into(b"\"a\"b\"", &mut buf), None);
+        assert_eq!(unquote_into(b"\"too long value\"", &mut buf), None);
+    }
+}
@@ -0,0 +1,294 @@
+//! Extracting the bindings a REGISTER request asks for.
+//!
+//! A registrar maps the address-of-record in the To header of a REGISTER
+//! to the contact addresses listed in its Contact headers, each for the
+//! time given by its `expires` parameter or the Expires header, see
+//! [RFC 3261](https://tools.ietf.org/html/rfc3261#section-10.3).
+
+use std::slice;
+use headers::names_eq;
+use scan;
+use sip::{Header, Request};
+
+/// Errors found in a REGISTER request; the registrar should reject it with
+/// a 400 (Bad Request) response.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RegistrationError {
+    /// The request is not a REGISTER.
+    NotRegister,
+    /// The To header is missing or empty.
+    MissingTo,
+    /// An Expires header or `expires` parameter is not a number.
+    Expires,
+    /// The `*` Contact comes with other contacts or a non-zero expiry.
+    Wildcard,
+}
+
+/// The bindings a REGISTER request asks for.
+///
+/// # Example
+///
+/// ```
+/// use parsip::registrar::Registration;
+///
+/// let buf = b"REGISTER sip:registrar.biloxi.com SIP/2.0\r\n\
+///             To: Bob <sip:bob@biloxi.com>\r\n\
+///             Contact: <sip:bob@192.0.2.4>;expires=60, <sip:bob@198.51.100.7>\r\n\
+///             Expires: 7200\r\n\r\n";
+/// let mut headers = [parsip::EMPTY_HEADER; 8];
+/// let mut req = parsip::Request::new(&mut headers);
+/// req.parse(buf).unwrap();
+///
+/// let registration = Registration::new(&req).unwrap();
+/// assert_eq!(registration.aor(), b"sip:bob@biloxi.com");
+/// let expiries: Vec<_> = registration.bindings().map(|b| (b.uri, b.expires)).collect();
+/// assert_eq!(expiries, [(&b"sip:bob@192.0.2.4"[..], Some(60)),
+///                       (&b"sip:bob@198.51.100.7"[..], Some(7200))]);
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct Registration<'h, 'b: 'h> {
+    aor: &'b [u8],
+    expires: Option<u32>,
+    wildcard: bool,
+    headers: &'h [Header<'b>],
+}
+
+impl<'h, 'b> Registration<'h, 'b> {
+    /// Checks the Contact, To and Expires headers of `req`.
+    pub fn new(req: &'h Request<'_, 'b>) -> Result<Registration<'h, 'b>, RegistrationError> {
+        if req.method != Some("REGISTER") {
+            return Err(RegistrationError::NotRegister);
+        }
+        let headers = &*req.headers;
+        let aor = headers.iter()
+            .find(|header| names_eq(header.name, "To"))
+            .map(|header| scan::split_addr(header.value.as_bytes()).0)
+            .filter(|aor| !aor.is_empty())
+            .ok_or(RegistrationError::MissingTo)?;
+        let expires = match headers.iter().find(|header| names_eq(header.name, "Expires")) {
+            Some(header) => Some(delta_seconds(&header.value)?),
+            None => None,
+        };
+
+        let mut registration = Registration {
+            aor,
+            expires,
+            wildcard: false,
+            headers,
+        };
+        let mut count = 0;
+        for binding in registration.elements() {
+            count += 1;
+            if binding.0 == b"*" {
+                registration.wildcard = true;
+            } else if let Some(expires) = scan::param(binding.1, b"expires") {
+                delta_seconds(expires)?;
+            }
+        }
+        if registration.wildcard && (count > 1 || expires != Some(0)) {
+            return Err(RegistrationError::Wildcard);
+        }
+        Ok(registration)
+    }
+
+    /// The address-of-record, the URI of the To header.
+    #[inline]
+    pub fn aor(&self) -> &'b [u8] {
+        self.aor
+    }
+
+    /// The value of the Expires header, if any.
+    #[inline]
+    pub fn expires(&self) -> Option<u32> {
+        self.expires
+    }
+
+    /// Returns `true` if the request removes all the bindings of the
+    /// address-of-record, with a `*` Contact.
+    #[inline]
+    pub fn is_wildcard(&self) -> bool {
+        self.wildcard
+    }
+
+    /// Returns `true` if the request only queries the current bindings,
+    /// having no Contact header.
+    #[inline]
+    pub fn is_query(&self) -> bool {
+        self.elements().next().is_none()
+    }
+
+    /// The bindings to add, refresh or remove, in order.
+    ///
+    /// Empty when the request is a wildcard.
+    pub fn bindings(&self) -> Bindings<'h, 'b> {
+        Bindings {
+            elements: self.elements(),
+            expires: if self.wildcard { None } else { Some(self.expires) },
+        }
+    }
+
+    fn elements(&self) -> Elements<'h, 'b> {
+        Elements {
+            headers: self.headers.iter(),
+            rest: None,
+        }
+    }
+}
+
+/// A contact address to bind to the address-of-record.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Binding<'b> {
+    /// The contact URI.
+    pub uri: &'b [u8],
+    /// The header parameters following the URI, such as `;q=0.7`.
+    pub params: &'b [u8],
+    /// The effective expiry in seconds: the `expires` parameter if present,
+    /// otherwise the Expires header. `None` when neither is present, and
+    /// the registrar picks its default.
+    pub expires: Option<u32>,
+}
+
+/// An iterator over the bindings of a `Registration`.
+#[derive(Clone, Debug)]
+pub struct Bindings<'h, 'b: 'h> {
+    elements: Elements<'h, 'b>,
+    // `None` for a wildcard
+    expires: Option<Option<u32>>,
+}
+
+impl<'h, 'b> Iterator for Bindings<'h, 'b> {
+    type Item = Binding<'b>;
+
+    fn next(&mut self) -> Option<Binding<'b>> {
+        let default = self.expires?;
+        let (uri, params) = self.elements.next()?;
+        // validated by `Registration::new`
+        let expires = match scan::param(params, b"expires") {
+            Some(expires) => delta_seconds(expires).ok(),
+            None => default,
+        };
+        Some(Binding {
+            uri,
+            params,
+            expires,
+        })
+    }
+}
+
+/// An iterator over the URIs and parameters of all Contact elements.
+#[derive(Clone, Debug)]
+struct Elements<'h, 'b: 'h> {
+    headers: slice::Iter<'h, Header<'b>>,
+    rest: Option<&'b [u8]>,
+}
+
+impl<'h, 'b> Iterator for Elements<'h, 'b> {
+    type Item = (&'b [u8], &'b [u8]);
+
+    fn next(&mut self) -> Option<(&'b [u8], &'b [u8])> {
+        loop {
+            if let Some(value) = self.rest {
+                let (element, rest) = scan::split_first(value);
+                self.rest = rest;
+                if !element.is_empty() {
+                    return Some(scan::split_addr(element));
+                }
+                continue;
+            }
+            let header = self.headers.next()?;
+            if names_eq(header.name, "Contact") {
+                self.rest = Some(header.value.as_bytes());
+            }
+        }
+    }
+}
+
+/// Parses delta-seconds, saturating at `u32::MAX` as RFC 3261 requires.
+fn delta_seconds(value: &[u8]) -> Result<u32, RegistrationError> {
+    let value = scan::trim(value);
+    if value.is_empty() || !value.iter().all(u8::is_ascii_digit) {
+        return Err(RegistrationError::Expires);
+    }
+    Ok(value.iter().fold(0u32, |acc, &b| acc.saturating_mul(10).saturating_add((b - b'0') as u32)))
+}
+
+#[cfg(test)]
+mod tests {
+    use sip::{Request, EMPTY_HEADER};
+    use super::{Binding, Registration, RegistrationError};
+
+    fn register<F: FnOnce(Result<Registration, RegistrationError>)>(buf: &[u8], f: F) {
+        let mut headers = [EMPTY_HEADER; 8];
+        let mut req = Request::new(&mut headers);
+        req.parse(buf).unwrap();
+        f(Registration::new(&req));
+    }
+
+    #[test]
+    fn test_bindings() {
+        register(b"REGISTER sip:biloxi.com SIP/2.0\r\n\
+                   t: sip:bob@biloxi.com;tag=1\r\n\
+                   m: \"Bob, desk\" <sip:bob@192.0.2.4>;q=0.7;EXPIRES=99999999999\r\n\
+                   Contact: sip:bob@198.51.100.7;expires=0\r\n\r\n",
+                 |registration| {
+            let registration = registration.unwrap();
+            assert_eq!(registration.aor(), b"sip:bob@biloxi.com");
+            assert_eq!(registration.expires(), None);
+            assert!(!registration.is_wildcard() && !registration.is_query());
+            let mut bindings = registration.bindings();
+            assert_eq!(bindings.next(),
+                       Some(Binding {
+                           uri: b"sip:bob@192.0.2.4",
+                           params: b";q=0.7;EXPIRES=99999999999",
+                           expires: Some(u32::MAX),
+                       }));
+            assert_eq!(bindings.next(),
+                       Some(Binding {
+                           uri: b"sip:bob@198.51.100.7",
+                           params: b";expires=0",
+                           expires: Some(0),
+                       }));
+            assert_eq!(bindings.next(), None);
+        });
+    }
+
+    #[test]
+    fn test_query() {
+        register(b"REGISTER sip:biloxi.com SIP/2.0\r\nTo: <sip:bob@biloxi.com>\r\n\r\n",
+                 |registration| {
+            let registration = registration.unwrap();
+            assert!(registration.is_query());
+            assert_eq!(registration.bindings().next(), None);
+        });
+    }
+
+    #[test]
+    fn test_wildcard() {
+        register(b"REGISTER sip:biloxi.com SIP/2.0\r\n\
+                   To: <sip:bob@biloxi.com>\r\nContact: *\r\nExpires: 0\r\n\r\n",
+                 |registration| {
+            let registration = registration.unwrap();
+            assert!(registration.is_wildcard());
+            assert_eq!(registration.bindings().next(), None);
+        });
+        register(b"REGISTER sip:biloxi.com SIP/2.0\r\n\
+                   To: <sip:bob@biloxi.com>\r\nContact: *\r\n\r\n",
+                 |registration| assert_eq!(registration.err(), Some(RegistrationError::Wildcard)));
+        register(b"REGISTER sip:biloxi.com SIP/2.0\r\n\
+                   To: <sip:bob@biloxi.com>\r\nContact: *, <sip:b@x>\r\nExpires: 0\r\n\r\n",
+                 |registration| assert_eq!(registration.err(), Some(RegistrationError::Wildcard)));
+    }
+
+    #[test]
+    fn test_invalid() {
+        register(b"INVITE sip:biloxi.com SIP/2.0\r\nTo: <sip:bob@biloxi.com>\r\n\r\n",
+                 |registration| {
+            assert_eq!(registration.err(), Some(RegistrationError::NotRegister))
+        });
+        register(b"REGISTER sip:biloxi.com SIP/2.0\r\nExpires: 60\r\n\r\n",
+                 |registration| assert_eq!(registration.err(), Some(RegistrationError::MissingTo)));
+        register(b"REGISTER sip:biloxi.com SIP/2.0\r\n\
+                   To: <sip:bob@biloxi.com>\r\nContact: <sip:b@x>;expires=soon\r\n\r\n",
+                 |registration| assert_eq!(registration.err(), Some(RegistrationError::Expires)));
+    }
+}
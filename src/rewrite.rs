@@ -0,0 +1,1076 @@
+//! Re-serializing parsed messages, as proxies forwarding them need.
+//!
+//! Every operation takes the buffer a message was parsed from along with
+//! the parsed message, and writes the rewritten message into a `Writer`.
+//! Only the parts being changed are serialized again; everything else,
+//! including the body, is copied byte-exactly from the original buffer.
+//! When an operation fails, the writer holds a partial message.
+
+use std::fmt;
+use std::str;
+use headers::names_eq;
+use scan;
+use sip::{Header, HeaderValue, Request, Response};
+use lookup::is_token;
+use memchr::memchr;
+
+/// Errors of the rewriting operations.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WriteError {
+    /// The output buffer is too small.
+    BufferTooSmall,
+    /// Invalid byte in a header name.
+    HeaderName,
+    /// Invalid byte in a header value.
+    HeaderValue,
+    /// The message was not completely parsed.
+    Incomplete,
+    /// The message has no Via header.
+    MissingVia,
+    /// Invalid byte in a Request-URI.
+    RequestUri,
+    /// A part of the message does not point into the given buffer.
+    NotInBuffer,
+    /// Invalid byte in a request method.
+    Method,
+    /// A status code out of `100..=699`, or an invalid reason-phrase byte.
+    Status,
+}
+
+impl WriteError {
+    /// A short description of the error.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            WriteError::BufferTooSmall => "output buffer too small",
+            WriteError::HeaderName => "invalid header name",
+            WriteError::HeaderValue => "invalid header value",
+            WriteError::Incomplete => "incomplete message",
+            WriteError::MissingVia => "missing Via header",
+            WriteError::RequestUri => "invalid Request-URI",
+            WriteError::NotInBuffer => "message part not in buffer",
+            WriteError::Method => "invalid method",
+            WriteError::Status => "invalid status",
+        }
+    }
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for WriteError {}
+
+/// A cursor writing into a caller-provided output buffer.
+///
+/// # Example
+///
+/// ```
+/// use parsip::HeaderValue;
+/// use parsip::rewrite::Writer;
+///
+/// let mut out = [0u8; 64];
+/// let mut writer = Writer::new(&mut out);
+/// writer.write_header("Max-Forwards", HeaderValue::from_static(b"70")).unwrap();
+/// assert_eq!(writer.written(), b"Max-Forwards: 70\r\n");
+/// ```
+#[derive(Debug)]
+pub struct Writer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    /// Creates a writer filling `buf` from its start.
+    #[inline]
+    pub fn new(buf: &'a mut [u8]) -> Writer<'a> {
+        Writer { buf, len: 0 }
+    }
+
+    /// Number of bytes written so far.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if nothing was written yet.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The bytes written so far.
+    #[inline]
+    pub fn written(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// Appends raw bytes.
+    pub fn write(&mut self, bytes: &[u8]) -> Result<(), WriteError> {
+        let end = self.len + bytes.len();
+        if end > self.buf.len() {
+            return Err(WriteError::BufferTooSmall);
+        }
+        self.buf[self.len..end].copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    /// Appends a `name: value` header line.
+    ///
+    /// The value is already known to be legal; the name is checked to be a
+    /// token.
+    pub fn write_header(&mut self, name: &str, value: HeaderValue) -> Result<(), WriteError> {
+        if name.is_empty() || !name.bytes().all(is_token) {
+            return Err(WriteError::HeaderName);
+        }
+        self.write(name.as_bytes())?;
+        self.write(b": ")?;
+        self.write(&value)?;
+        self.write(b"\r\n")
+    }
+}
+
+/// A `branch` parameter carrying the RFC 3261 magic cookie.
+///
+/// It is derived deterministically from the request, as
+/// [RFC 3261](https://tools.ietf.org/html/rfc3261#section-16.11) requires
+/// of stateless proxies, so retransmissions get the same branch.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Branch([u8; 23]);
+
+impl Branch {
+    /// Computes the branch for forwarding `req`.
+    ///
+    /// It covers the Request-URI and the top Via, From, To, Call-ID and
+    /// CSeq values.
+    pub fn new(req: &Request) -> Branch {
+        let mut hash = Fnv::new();
+        hash.write(req.path.unwrap_or("").as_bytes());
+        for name in &["Via", "From", "To", "Call-ID", "CSeq"] {
+            let value = req.headers
+                .iter()
+                .find(|header| names_eq(header.name, name))
+                .map_or(&b""[..], |header| scan::split_first(&header.value).0);
+            hash.write(value);
+        }
+        let mut branch = *b"z9hG4bK0000000000000000";
+        for (idx, digit) in branch[7..].iter_mut().enumerate() {
+            *digit = b"0123456789abcdef"[(hash.0 >> (60 - idx * 4)) as usize & 0xf];
+        }
+        Branch(branch)
+    }
+
+    /// The branch as a string.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        // made of ASCII only
+        unsafe { str::from_utf8_unchecked(&self.0) }
+    }
+}
+
+impl fmt::Debug for Branch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Branch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 64-bit FNV-1a.
+pub(crate) struct Fnv(pub(crate) u64);
+
+impl Fnv {
+    #[inline]
+    pub(crate) fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    /// Hashes `bytes`, followed by a separator so that consecutive writes
+    /// cannot be confused.
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes.iter().chain(&[0xff]) {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// Offset of `part` within `buf`.
+pub(crate) fn offset(buf: &[u8], part: &[u8]) -> Result<usize, WriteError> {
+    let start = buf.as_ptr() as usize;
+    let pos = part.as_ptr() as usize;
+    if pos < start || pos + part.len() > start + buf.len() {
+        return Err(WriteError::NotInBuffer);
+    }
+    Ok(pos - start)
+}
+
+/// Span of the start line within `buf`, past any leading empty lines and
+/// including its line terminator.
+pub(crate) fn start_line(buf: &[u8]) -> Result<(usize, usize), WriteError> {
+    let begin = buf.iter().position(|&b| b != b'\r' && b != b'\n').unwrap_or(buf.len());
+    match memchr(b'\n', &buf[begin..]) {
+        Some(lf) => Ok((begin, begin + lf + 1)),
+        None => Err(WriteError::Incomplete),
+    }
+}
+
+/// Span of a whole header line within `buf`, from its name to its line
+/// terminator inclusive.
+pub(crate) fn header_line(buf: &[u8], header: &Header) -> Result<(usize, usize), WriteError> {
+    let start = offset(buf, header.name.as_bytes())?;
+    let mut end = offset(buf, &header.value)? + header.value.len();
+    loop {
+        match memchr(b'\n', &buf[end..]) {
+            Some(lf) => end += lf + 1,
+            None => return Err(WriteError::Incomplete),
+        }
+        match buf.get(end) {
+            // a blank folded line
+            Some(&b' ') | Some(&b'\t') => {}
+            _ => return Ok((start, end)),
+        }
+    }
+}
+
+/// Start and end offsets of a part of a message buffer.
+pub type Span = (usize, usize);
+
+/// Where the parts of a parsed header lie in the message buffer.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeaderSpan {
+    /// The whole header line, including its line terminator.
+    pub line: Span,
+    /// The header name.
+    pub name: Span,
+    /// The header value.
+    pub value: Span,
+}
+
+/// An empty `HeaderSpan`, useful for constructing an array to pass to
+/// `ParseInfo::new`.
+pub const EMPTY_SPAN: HeaderSpan = HeaderSpan {
+    line: (0, 0),
+    name: (0, 0),
+    value: (0, 0),
+};
+
+/// Offsets of the parts of a parsed message within its buffer.
+///
+/// Unlike the parsed message, it does not borrow the buffer, which can
+/// then be edited in place.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseInfo<'s> {
+    /// The start line, including its line terminator.
+    pub start_line: Span,
+    /// The headers, in their original order.
+    pub headers: &'s [HeaderSpan],
+}
+
+impl<'s> ParseInfo<'s> {
+    /// Records where `headers`, parsed from `buf`, lie in it, storing the
+    /// spans in `spans`.
+    ///
+    /// Fails with `BufferTooSmall` if `spans` is shorter than `headers`.
+    pub fn new(buf: &[u8],
+               headers: &[Header],
+               spans: &'s mut [HeaderSpan])
+               -> Result<ParseInfo<'s>, WriteError> {
+        if headers.len() > spans.len() {
+            return Err(WriteError::BufferTooSmall);
+        }
+        let spans = &mut spans[..headers.len()];
+        for (span, header) in spans.iter_mut().zip(headers) {
+            let name = offset(buf, header.name.as_bytes())?;
+            let value = offset(buf, &header.value)?;
+            *span = HeaderSpan {
+                line: header_line(buf, header)?,
+                name: (name, name + header.name.len()),
+                value: (value, value + header.value.len()),
+            };
+        }
+        Ok(ParseInfo {
+            start_line: start_line(buf)?,
+            headers: spans,
+        })
+    }
+
+    /// Index of the first header called `name` in `buf`, compared
+    /// case-insensitively and treating compact forms as their long form.
+    pub fn position(&self, buf: &[u8], name: &str) -> Option<usize> {
+        self.headers.iter().position(|span| {
+            str::from_utf8(&buf[span.name.0..span.name.1])
+                .map(|header| names_eq(header, name))
+                .unwrap_or(false)
+        })
+    }
+}
+
+/// Replaces the value of header `index` of the message in `buf`, without
+/// moving anything else.
+///
+/// A shorter value is padded with trailing spaces, which parsers ignore.
+/// Fails with `BufferTooSmall` if `value` is longer than the current one,
+/// in which case the message has to be rebuilt with `replace_value`.
+///
+/// # Panics
+///
+/// If `index` is out of bounds.
+///
+/// # Example
+///
+/// ```
+/// use parsip::HeaderValue;
+/// use parsip::rewrite::{replace_value_in_place, ParseInfo, EMPTY_SPAN};
+///
+/// let mut buf = *b"SIP/2.0 200 OK\r\nContact: <sip:alice@10.0.0.1:5060>\r\n\r\n";
+/// let mut spans = [EMPTY_SPAN; 8];
+/// let info = {
+///     let mut headers = [parsip::EMPTY_HEADER; 8];
+///     let mut res = parsip::Response::new(&mut headers);
+///     res.parse(&buf).unwrap();
+///     ParseInfo::new(&buf, res.headers, &mut spans).unwrap()
+/// };
+///
+/// let contact = info.position(&buf, "m").unwrap();
+/// let value = HeaderValue::from_static(b"<sip:alice@192.0.2.4>");
+/// replace_value_in_place(&mut buf, &info, contact, value).unwrap();
+/// assert_eq!(&buf[..],
+///            &b"SIP/2.0 200 OK\r\nContact: <sip:alice@192.0.2.4>    \r\n\r\n"[..]);
+/// ```
+pub fn replace_value_in_place(buf: &mut [u8],
+                              info: &ParseInfo,
+                              index: usize,
+                              value: HeaderValue)
+                              -> Result<(), WriteError> {
+    let (start, end) = info.headers[index].value;
+    if value.len() > end - start {
+        return Err(WriteError::BufferTooSmall);
+    }
+    let (head, padding) = buf[start..end].split_at_mut(value.len());
+    head.copy_from_slice(&value);
+    for b in padding {
+        *b = b' ';
+    }
+    Ok(())
+}
+
+/// Writes the message in `buf` with the value of header `index` replaced.
+///
+/// # Panics
+///
+/// If `index` is out of bounds.
+pub fn replace_value(out: &mut Writer,
+                     buf: &[u8],
+                     info: &ParseInfo,
+                     index: usize,
+                     value: HeaderValue)
+                     -> Result<(), WriteError> {
+    let (start, end) = info.headers[index].value;
+    out.write(&buf[info.start_line.0..start])?;
+    out.write(&value)?;
+    out.write(&buf[end..])
+}
+
+/// Writes `req`, parsed from `buf`, with a new top Via header added, as a
+/// proxy forwarding it does.
+///
+/// The new header is `Via: <sent_protocol> <sent_by>;branch=<branch>`,
+/// with the branch computed by `Branch::new`.
+///
+/// # Example
+///
+/// ```
+/// use parsip::rewrite::{push_via, Writer};
+///
+/// let buf = b"OPTIONS sip:carol@chicago.com SIP/2.0\r\n\
+///             Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKhjhs8ass877\r\n\r\n";
+/// let mut headers = [parsip::EMPTY_HEADER; 8];
+/// let mut req = parsip::Request::new(&mut headers);
+/// req.parse(buf).unwrap();
+///
+/// let mut out = [0u8; 256];
+/// let mut writer = Writer::new(&mut out);
+/// push_via(&mut writer, buf, &req, "SIP/2.0/UDP", "proxy.biloxi.com").unwrap();
+/// assert!(writer.written().starts_with(b"OPTIONS sip:carol@chicago.com SIP/2.0\r\n\
+///                                        Via: SIP/2.0/UDP proxy.biloxi.com;branch=z9hG4bK"));
+/// assert!(writer.written().ends_with(b"branch=z9hG4bKhjhs8ass877\r\n\r\n"));
+/// ```
+pub fn push_via(out: &mut Writer,
+                buf: &[u8],
+                req: &Request,
+                sent_protocol: &str,
+                sent_by: &str)
+                -> Result<(), WriteError> {
+    if req.method.is_none() || req.path.is_none() {
+        return Err(WriteError::Incomplete);
+    }
+    let (begin, end) = start_line(buf)?;
+    out.write(&buf[begin..end])?;
+    write_via(out, sent_protocol, sent_by, Branch::new(req))?;
+    out.write(&buf[end..])
+}
+
+/// Writes a `Via: <sent_protocol> <sent_by>;branch=<branch>` header line.
+pub(crate) fn write_via(out: &mut Writer,
+                        sent_protocol: &str,
+                        sent_by: &str,
+                        branch: Branch)
+                        -> Result<(), WriteError> {
+    for part in &[sent_protocol, sent_by] {
+        if part.is_empty() || !part.bytes().all(|b| b > b' ' && b != b',' && b != 0x7f) {
+            return Err(WriteError::HeaderValue);
+        }
+    }
+    out.write(b"Via: ")?;
+    out.write(sent_protocol.as_bytes())?;
+    out.write(b" ")?;
+    out.write(sent_by.as_bytes())?;
+    out.write(b";branch=")?;
+    out.write(branch.as_str().as_bytes())?;
+    out.write(b"\r\n")
+}
+
+/// Writes `res`, parsed from `buf`, with its top Via removed, as a proxy
+/// forwarding it upstream does.
+///
+/// When the top Via header holds several comma-separated values only the
+/// first one is removed.
+///
+/// # Example
+///
+/// ```
+/// use parsip::rewrite::{pop_via, Writer};
+///
+/// let buf = b"SIP/2.0 200 OK\r\n\
+///             Via: SIP/2.0/UDP proxy.biloxi.com;branch=z9hG4bK1, SIP/2.0/UDP pc33.atlanta.com\r\n\
+///             CSeq: 1 OPTIONS\r\n\r\n";
+/// let mut headers = [parsip::EMPTY_HEADER; 8];
+/// let mut res = parsip::Response::new(&mut headers);
+/// res.parse(buf).unwrap();
+///
+/// let mut out = [0u8; 256];
+/// let mut writer = Writer::new(&mut out);
+/// pop_via(&mut writer, buf, &res).unwrap();
+/// assert_eq!(writer.written(),
+///            &b"SIP/2.0 200 OK\r\nVia: SIP/2.0/UDP pc33.atlanta.com\r\nCSeq: 1 OPTIONS\r\n\r\n"[..]);
+/// ```
+pub fn pop_via(out: &mut Writer, buf: &[u8], res: &Response) -> Result<(), WriteError> {
+    let via = res.headers
+        .iter()
+        .find(|header| names_eq(header.name, "Via"))
+        .ok_or(WriteError::MissingVia)?;
+    let (begin, _) = start_line(buf)?;
+    match scan::split_first(&via.value) {
+        (_, Some(rest)) => {
+            let value_start = offset(buf, &via.value)?;
+            let rest = scan::trim(rest);
+            out.write(&buf[begin..value_start])?;
+            out.write(rest)?;
+            out.write(&buf[offset(buf, rest)? + rest.len()..])
+        }
+        (_, None) => {
+            let (start, end) = header_line(buf, via)?;
+            out.write(&buf[begin..start])?;
+            out.write(&buf[end..])
+        }
+    }
+}
+
+/// Writes `req`, parsed from `buf`, with the Record-Route entries of a
+/// proxy added above the existing ones.
+///
+/// `uri` addresses the interface the request is forwarded from. When the
+/// request arrived on another interface or transport, `inbound` addresses
+/// that one, and a second entry is added below the first, as described
+/// in [RFC 5658](https://tools.ietf.org/html/rfc5658). The `lr` parameter
+/// is added to URIs lacking it.
+///
+/// The entries go right above the first Record-Route header, or below the
+/// last Via header if there is none.
+///
+/// # Example
+///
+/// ```
+/// use parsip::rewrite::{record_route, Writer};
+///
+/// let buf = b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+///             Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashds8\r\n\
+///             Call-ID: a84b4c76e66710\r\n\r\n";
+/// let mut headers = [parsip::EMPTY_HEADER; 8];
+/// let mut req = parsip::Request::new(&mut headers);
+/// req.parse(buf).unwrap();
+///
+/// let mut out = [0u8; 256];
+/// let mut writer = Writer::new(&mut out);
+/// record_route(&mut writer, buf, &req, "sip:p1.example.com", None).unwrap();
+/// assert_eq!(writer.written(), &b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+///                                 Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashds8\r\n\
+///                                 Record-Route: <sip:p1.example.com;lr>\r\n\
+///                                 Call-ID: a84b4c76e66710\r\n\r\n"[..]);
+/// ```
+pub fn record_route(out: &mut Writer,
+                    buf: &[u8],
+                    req: &Request,
+                    uri: &str,
+                    inbound: Option<&str>)
+                    -> Result<(), WriteError> {
+    let (begin, end) = start_line(buf)?;
+    let at = match req.headers.iter().find(|header| names_eq(header.name, "Record-Route")) {
+        Some(header) => header_line(buf, header)?.0,
+        None => {
+            match req.headers.iter().rev().find(|header| names_eq(header.name, "Via")) {
+                Some(header) => header_line(buf, header)?.1,
+                None => end,
+            }
+        }
+    };
+    out.write(&buf[begin..at])?;
+    write_record_route(out, uri)?;
+    if let Some(inbound) = inbound {
+        write_record_route(out, inbound)?;
+    }
+    out.write(&buf[at..])
+}
+
+/// Writes a `Record-Route: <uri;lr>` header line.
+fn write_record_route(out: &mut Writer, uri: &str) -> Result<(), WriteError> {
+    if !is_valid_uri(uri.as_bytes()) {
+        return Err(WriteError::HeaderValue);
+    }
+    out.write(b"Record-Route: <")?;
+    out.write(uri.as_bytes())?;
+    if !has_uri_param(uri.as_bytes(), b"lr") {
+        out.write(b";lr")?;
+    }
+    out.write(b">\r\n")
+}
+
+/// Returns `true` if `uri` can go in a Request-URI and between the angle
+/// brackets of a name-addr.
+fn is_valid_uri(uri: &[u8]) -> bool {
+    !uri.is_empty() &&
+    uri.iter().all(|&b| b > b' ' && b != b'<' && b != b'>' && b != b',' && b != 0x7f)
+}
+
+/// Returns `true` if the parameters of `uri` include `name`.
+fn has_uri_param(uri: &[u8], name: &[u8]) -> bool {
+    // URI headers come after parameters
+    let uri = uri.split(|&b| b == b'?').next().unwrap_or(uri);
+    uri.split(|&b| b == b';')
+        .skip(1)
+        .any(|param| {
+            let param_name = param.split(|&b| b == b'=').next().unwrap_or(param);
+            param_name.eq_ignore_ascii_case(name)
+        })
+}
+
+/// Writes `req`, parsed from `buf`, retargeted to `uri`.
+///
+/// When the top Route header addresses a loose router, or there is none,
+/// `uri` simply replaces the Request-URI. When it addresses a strict
+/// router, its URI becomes the Request-URI instead, and `uri` moves to a
+/// new Route header at the end of the route set, as described in
+/// [RFC 3261](https://tools.ietf.org/html/rfc3261#section-16.6).
+///
+/// # Example
+///
+/// ```
+/// use parsip::rewrite::{retarget, Writer};
+///
+/// let buf = b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+///             Route: <sip:p2.example.com>, <sip:p3.example.com;lr>\r\n\
+///             Call-ID: a84b4c76e66710\r\n\r\n";
+/// let mut headers = [parsip::EMPTY_HEADER; 8];
+/// let mut req = parsip::Request::new(&mut headers);
+/// req.parse(buf).unwrap();
+///
+/// let mut out = [0u8; 256];
+/// let mut writer = Writer::new(&mut out);
+/// retarget(&mut writer, buf, &req, "sip:bob@192.0.2.4").unwrap();
+/// assert_eq!(writer.written(), &b"INVITE sip:p2.example.com SIP/2.0\r\n\
+///                                 Route: <sip:p3.example.com;lr>\r\n\
+///                                 Route: <sip:bob@192.0.2.4>\r\n\
+///                                 Call-ID: a84b4c76e66710\r\n\r\n"[..]);
+/// ```
+pub fn retarget(out: &mut Writer, buf: &[u8], req: &Request, uri: &str) -> Result<(), WriteError> {
+    let path = req.path.ok_or(WriteError::Incomplete)?;
+    if !is_valid_uri(uri.as_bytes()) {
+        return Err(WriteError::RequestUri);
+    }
+    let (begin, _) = start_line(buf)?;
+    let path_start = offset(buf, path.as_bytes())?;
+    out.write(&buf[begin..path_start])?;
+    let mut pos = path_start + path.len();
+
+    let mut routes = req.headers.iter().filter(|header| names_eq(header.name, "Route"));
+    let (first, last) = match (routes.next(), routes.next_back()) {
+        (Some(first), last) => (first, last.unwrap_or(first)),
+        (None, _) => return write_rest(out, uri.as_bytes(), &buf[pos..]),
+    };
+    let (element, rest) = scan::split_first(&first.value);
+    let (next_hop, _) = scan::split_addr(element);
+    if has_uri_param(next_hop, b"lr") {
+        return write_rest(out, uri.as_bytes(), &buf[pos..]);
+    }
+    if !is_valid_uri(next_hop) {
+        return Err(WriteError::RequestUri);
+    }
+
+    // strict router: it gets the Request-URI, and the target is routed last
+    out.write(next_hop)?;
+    let (start, end) = header_line(buf, first)?;
+    match rest {
+        Some(rest) => {
+            let rest = scan::trim(rest);
+            out.write(&buf[pos..offset(buf, &first.value)?])?;
+            out.write(rest)?;
+            pos = offset(buf, rest)? + rest.len();
+        }
+        None => {
+            out.write(&buf[pos..start])?;
+            pos = end;
+        }
+    }
+    let (_, at) = header_line(buf, last)?;
+    out.write(&buf[pos..at])?;
+    out.write(b"Route: <")?;
+    out.write(uri.as_bytes())?;
+    out.write(b">\r\n")?;
+    out.write(&buf[at..])
+}
+
+/// Writes `uri` followed by `rest`.
+fn write_rest(out: &mut Writer, uri: &[u8], rest: &[u8]) -> Result<(), WriteError> {
+    out.write(uri)?;
+    out.write(rest)
+}
+
+/// What `copy_filtered` does with a header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HeaderAction<'a> {
+    /// Copy the header unchanged.
+    Keep,
+    /// Leave the header out.
+    Drop,
+    /// Write the header's value under another name.
+    Rename(&'a str),
+    /// Write the header's name with another value.
+    Rewrite(HeaderValue<'a>),
+}
+
+/// Writes the message with headers `headers`, parsed from `buf`, passing
+/// each header through `filter` to decide what to do with it.
+///
+/// Kept headers are copied byte-exactly, and so are the start line, the
+/// end of the header section and the body.
+///
+/// # Example
+///
+/// ```
+/// use parsip::HeaderValue;
+/// use parsip::rewrite::{copy_filtered, HeaderAction, Writer};
+///
+/// let buf = b"BYE sip:bob@192.0.2.4 SIP/2.0\r\n\
+///             Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashds7\r\n\
+///             Call-ID: a84b4c76e66710\r\n\
+///             X-Leg:  a\r\n\r\n";
+/// let mut headers = [parsip::EMPTY_HEADER; 8];
+/// let mut req = parsip::Request::new(&mut headers);
+/// req.parse(buf).unwrap();
+///
+/// let mut out = [0u8; 256];
+/// let mut writer = Writer::new(&mut out);
+/// copy_filtered(&mut writer, buf, req.headers, |header| match header.name {
+///     "Via" => HeaderAction::Drop,
+///     "Call-ID" => HeaderAction::Rewrite(HeaderValue::from_static(b"b2b-1")),
+///     _ => HeaderAction::Keep,
+/// }).unwrap();
+/// assert_eq!(writer.written(),
+///            &b"BYE sip:bob@192.0.2.4 SIP/2.0\r\nCall-ID: b2b-1\r\nX-Leg:  a\r\n\r\n"[..]);
+/// ```
+pub fn copy_filtered<'a, F>(out: &mut Writer,
+                            buf: &[u8],
+                            headers: &[Header<'a>],
+                            mut filter: F)
+                            -> Result<(), WriteError>
+    where F: FnMut(&Header<'a>) -> HeaderAction<'a>
+{
+    let (mut pos, _) = start_line(buf)?;
+    for header in headers {
+        let (start, end) = header_line(buf, header)?;
+        out.write(&buf[pos..start])?;
+        match filter(header) {
+            HeaderAction::Keep => out.write(&buf[start..end])?,
+            HeaderAction::Drop => {}
+            HeaderAction::Rename(name) => out.write_header(name, header.value)?,
+            HeaderAction::Rewrite(value) => {
+                out.write(&buf[start..offset(buf, &header.value)?])?;
+                out.write(&value)?;
+                out.write(b"\r\n")?;
+            }
+        }
+        pos = end;
+    }
+    out.write(&buf[pos..])
+}
+
+/// Writes the message with headers `headers`, parsed from `buf`, with its
+/// topology hidden, as an SBC bridging networks does.
+///
+/// The Via headers are collapsed into a single one with value `via`, the
+/// Record-Route headers into a single one with value `record_route`, or
+/// removed if it is `None`, and the Contact headers into a single one with
+/// value `contact`. Every address behind the SBC is reached through it, so
+/// the contacts of a REGISTER or a 3xx response all become the one
+/// `contact`, written where the first Contact was. Everything else is
+/// copied byte-exactly.
+///
+/// # Example
+///
+/// ```
+/// use parsip::HeaderValue;
+/// use parsip::rewrite::{hide_topology, Writer};
+///
+/// let buf = b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+///             Via: SIP/2.0/UDP p1.atlanta.com;branch=z9hG4bK2d4790.1\r\n\
+///             Via: SIP/2.0/UDP 10.0.0.1;branch=z9hG4bK74bf9\r\n\
+///             Record-Route: <sip:p1.atlanta.com;lr>\r\n\
+///             Contact: <sip:alice@10.0.0.1>\r\n\r\n";
+/// let mut headers = [parsip::EMPTY_HEADER; 8];
+/// let mut req = parsip::Request::new(&mut headers);
+/// req.parse(buf).unwrap();
+///
+/// let mut out = [0u8; 256];
+/// let mut writer = Writer::new(&mut out);
+/// hide_topology(&mut writer,
+///               buf,
+///               req.headers,
+///               HeaderValue::from_static(b"SIP/2.0/UDP sbc.biloxi.com;branch=z9hG4bK1"),
+///               None,
+///               HeaderValue::from_static(b"<sip:a1b2@sbc.biloxi.com>"))
+///     .unwrap();
+/// assert_eq!(writer.written(), &b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+///                                 Via: SIP/2.0/UDP sbc.biloxi.com;branch=z9hG4bK1\r\n\
+///                                 Contact: <sip:a1b2@sbc.biloxi.com>\r\n\r\n"[..]);
+/// ```
+pub fn hide_topology<'a>(out: &mut Writer,
+                         buf: &[u8],
+                         headers: &[Header<'a>],
+                         via: HeaderValue<'a>,
+                         record_route: Option<HeaderValue<'a>>,
+                         contact: HeaderValue<'a>)
+                         -> Result<(), WriteError> {
+    let mut seen_via = false;
+    let mut seen_record_route = false;
+    let mut seen_contact = false;
+    copy_filtered(out, buf, headers, |header| {
+        if names_eq(header.name, "Via") {
+            if seen_via {
+                return HeaderAction::Drop;
+            }
+            seen_via = true;
+            HeaderAction::Rewrite(via)
+        } else if names_eq(header.name, "Record-Route") {
+            match record_route {
+                Some(record_route) if !seen_record_route => {
+                    seen_record_route = true;
+                    HeaderAction::Rewrite(record_route)
+                }
+                _ => HeaderAction::Drop,
+            }
+        } else if names_eq(header.name, "Contact") {
+            if seen_contact {
+                return HeaderAction::Drop;
+            }
+            seen_contact = true;
+            HeaderAction::Rewrite(contact)
+        } else {
+            HeaderAction::Keep
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sip::{Request, Response, Status, EMPTY_HEADER};
+    use sip::HeaderValue;
+    use super::{push_via, pop_via, record_route, replace_value, replace_value_in_place};
+    use super::{copy_filtered, hide_topology, retarget, HeaderAction};
+    use super::{Branch, HeaderSpan, ParseInfo, Writer, WriteError, EMPTY_SPAN};
+
+    const INVITE: &[u8] = b"\r\nINVITE sip:bob@biloxi.com SIP/2.0\r\n\
+                            Via:SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bKnashds8\r\n\
+                            To: Bob <sip:bob@biloxi.com>\r\n\
+                            Call-ID: a84b4c76e66710\r\n\
+                            CSeq: 314159 INVITE\r\n\
+                            Content-Length: 4\r\n\r\n\
+                            body";
+
+    #[test]
+    fn test_push_via() {
+        let mut headers = [EMPTY_HEADER; 8];
+        let mut req = Request::new(&mut headers);
+        req.parse(INVITE).unwrap();
+        let branch = Branch::new(&req);
+        assert_eq!(branch, Branch::new(&req));
+        assert!(branch.as_str().starts_with("z9hG4bK"));
+        assert_eq!(branch.as_str().len(), 23);
+
+        let mut out = [0u8; 512];
+        let mut writer = Writer::new(&mut out);
+        push_via(&mut writer, INVITE, &req, "SIP/2.0/TCP", "proxy.biloxi.com:5060").unwrap();
+        let expected = format!("INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+                                Via: SIP/2.0/TCP proxy.biloxi.com:5060;branch={}\r\n{}",
+                               branch,
+                               ::std::str::from_utf8(&INVITE[37..]).unwrap());
+        assert_eq!(writer.written(), expected.as_bytes());
+
+        let mut forwarded_headers = [EMPTY_HEADER; 8];
+        let mut forwarded = Request::new(&mut forwarded_headers);
+        assert_eq!(forwarded.parse(writer.written()), Ok(Status::Complete(writer.len() - 4)));
+        assert_eq!(forwarded.headers.len(), 6);
+    }
+
+    #[test]
+    fn test_push_via_errors() {
+        let mut headers = [EMPTY_HEADER; 8];
+        let mut req = Request::new(&mut headers);
+        req.parse(INVITE).unwrap();
+
+        let mut out = [0u8; 64];
+        let mut writer = Writer::new(&mut out);
+        assert_eq!(push_via(&mut writer, INVITE, &req, "SIP/2.0/UDP", "a.com"),
+                   Err(WriteError::BufferTooSmall));
+        let mut out = [0u8; 512];
+        let mut writer = Writer::new(&mut out);
+        assert_eq!(push_via(&mut writer, INVITE, &req, "SIP/2.0/UDP", "a.com\r\nX: y"),
+                   Err(WriteError::HeaderValue));
+    }
+
+    #[test]
+    fn test_record_route_above_existing() {
+        let buf = b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+                    Record-Route: <sip:p2.example.com;lr>\r\n\
+                    Via: SIP/2.0/UDP p2.example.com;branch=z9hG4bK2\r\n\r\n";
+        let mut headers = [EMPTY_HEADER; 8];
+        let mut req = Request::new(&mut headers);
+        req.parse(buf).unwrap();
+
+        let mut out = [0u8; 256];
+        let mut writer = Writer::new(&mut out);
+        record_route(&mut writer,
+                     buf,
+                     &req,
+                     "sip:10.0.0.1;transport=tcp;LR",
+                     Some("sip:192.0.2.1"))
+            .unwrap();
+        assert_eq!(writer.written(),
+                   &b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+                      Record-Route: <sip:10.0.0.1;transport=tcp;LR>\r\n\
+                      Record-Route: <sip:192.0.2.1;lr>\r\n\
+                      Record-Route: <sip:p2.example.com;lr>\r\n\
+                      Via: SIP/2.0/UDP p2.example.com;branch=z9hG4bK2\r\n\r\n"[..]);
+    }
+
+    #[test]
+    fn test_record_route_invalid_uri() {
+        let buf = b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\r\n";
+        let mut headers = [EMPTY_HEADER; 8];
+        let mut req = Request::new(&mut headers);
+        req.parse(buf).unwrap();
+        let mut out = [0u8; 256];
+        assert_eq!(record_route(&mut Writer::new(&mut out), buf, &req, "sip:a>, <sip:b", None),
+                   Err(WriteError::HeaderValue));
+    }
+
+    #[test]
+    fn test_replace_value() {
+        let mut buf = *b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+                         Via: SIP/2.0/UDP 10.0.0.1;rport;branch=z9hG4bK1\r\n\
+                         Contact:\r\n <sip:alice@10.0.0.1>\r\n\r\nbody";
+        let mut spans = [EMPTY_SPAN; 4];
+        let info = {
+            let mut headers = [EMPTY_HEADER; 4];
+            let mut req = Request::new(&mut headers);
+            req.parse(&buf).unwrap();
+            ParseInfo::new(&buf, req.headers, &mut spans).unwrap()
+        };
+        assert_eq!(info.start_line, (0, 35));
+        assert_eq!(info.headers[1],
+                   HeaderSpan { line: (84, 117), name: (84, 91), value: (92, 115) });
+        assert_eq!(info.position(&buf, "v"), Some(0));
+        assert_eq!(info.position(&buf, "To"), None);
+
+        let via = HeaderValue::from_static(b"SIP/2.0/UDP 10.0.0.1;rport=5060;received=192.0.2.1;\
+                                             branch=z9hG4bK1");
+        let mut out = [0u8; 256];
+        let mut writer = Writer::new(&mut out);
+        replace_value(&mut writer, &buf, &info, 0, via).unwrap();
+        assert_eq!(writer.written(),
+                   &b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+                      Via: SIP/2.0/UDP 10.0.0.1;rport=5060;received=192.0.2.1;branch=z9hG4bK1\r\n\
+                      Contact:\r\n <sip:alice@10.0.0.1>\r\n\r\nbody"[..]);
+
+        assert_eq!(replace_value_in_place(&mut buf, &info, 0, via),
+                   Err(WriteError::BufferTooSmall));
+        let contact = HeaderValue::from_static(b"<sip:a@192.0.2.1>");
+        replace_value_in_place(&mut buf, &info, 1, contact).unwrap();
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        assert_eq!(req.parse(&buf), Ok(Status::Complete(buf.len() - 4)));
+        assert_eq!(req.headers[1].value, b"<sip:a@192.0.2.1>");
+    }
+
+    #[test]
+    fn test_copy_filtered() {
+        let mut headers = [EMPTY_HEADER; 8];
+        let mut req = Request::new(&mut headers);
+        req.parse(INVITE).unwrap();
+
+        let mut out = [0u8; 256];
+        let mut writer = Writer::new(&mut out);
+        copy_filtered(&mut writer, INVITE, req.headers, |header| {
+            if header.name == "Via" {
+                HeaderAction::Drop
+            } else if header.name == "To" {
+                HeaderAction::Rename("t")
+            } else if header.name == "Call-ID" {
+                HeaderAction::Rewrite(HeaderValue::from_static(b"b2b-a84b"))
+            } else {
+                HeaderAction::Keep
+            }
+        }).unwrap();
+        assert_eq!(writer.written(),
+                   &b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+                      t: Bob <sip:bob@biloxi.com>\r\n\
+                      Call-ID: b2b-a84b\r\n\
+                      CSeq: 314159 INVITE\r\n\
+                      Content-Length: 4\r\n\r\n\
+                      body"[..]);
+
+        let mut writer = Writer::new(&mut out);
+        copy_filtered(&mut writer, INVITE, req.headers, |_| HeaderAction::Keep).unwrap();
+        assert_eq!(writer.written(), &INVITE[2..]);
+
+        let mut writer = Writer::new(&mut out);
+        assert_eq!(copy_filtered(&mut writer, INVITE, req.headers, |_| HeaderAction::Rename("T o")),
+                   Err(WriteError::HeaderName));
+    }
+
+    #[test]
+    fn test_hide_topology() {
+        let buf = b"SIP/2.0 180 Ringing\r\n\
+                    v: SIP/2.0/UDP p2.example.com;branch=z9hG4bK2, SIP/2.0/UDP p1.example.com\r\n\
+                    Record-Route: <sip:p2.example.com;lr>\r\n\
+                    To: <sip:bob@biloxi.com>;tag=8321234356\r\n\
+                    Record-Route: <sip:p1.example.com;lr>\r\n\
+                    Via: SIP/2.0/UDP 10.0.0.1;branch=z9hG4bK74bf9\r\n\
+                    m: <sip:bob@10.1.1.1>\r\n\
+                    Contact: <sip:bob@10.1.1.2>, <sip:bob@10.1.1.3>\r\n\r\n";
+        let mut headers = [EMPTY_HEADER; 8];
+        let mut res = Response::new(&mut headers);
+        res.parse(buf).unwrap();
+
+        let mut out = [0u8; 256];
+        let mut writer = Writer::new(&mut out);
+        hide_topology(&mut writer,
+                      buf,
+                      res.headers,
+                      HeaderValue::from_static(b"SIP/2.0/UDP sbc.example.com;branch=z9hG4bK9"),
+                      Some(HeaderValue::from_static(b"<sip:sbc.example.com;lr>")),
+                      HeaderValue::from_static(b"<sip:x7@sbc.example.com>"))
+            .unwrap();
+        assert_eq!(writer.written(),
+                   &b"SIP/2.0 180 Ringing\r\n\
+                      v: SIP/2.0/UDP sbc.example.com;branch=z9hG4bK9\r\n\
+                      Record-Route: <sip:sbc.example.com;lr>\r\n\
+                      To: <sip:bob@biloxi.com>;tag=8321234356\r\n\
+                      m: <sip:x7@sbc.example.com>\r\n\r\n"[..]);
+    }
+
+    #[test]
+    fn test_retarget_loose() {
+        let buf = b"OPTIONS sip:bob@biloxi.com SIP/2.0\r\n\
+                    Route: <sip:p1.example.com;lr>\r\n\r\n";
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse(buf).unwrap();
+
+        let mut out = [0u8; 256];
+        let mut writer = Writer::new(&mut out);
+        retarget(&mut writer, buf, &req, "sip:bob@192.0.2.4;transport=tcp").unwrap();
+        assert_eq!(writer.written(),
+                   &b"OPTIONS sip:bob@192.0.2.4;transport=tcp SIP/2.0\r\n\
+                      Route: <sip:p1.example.com;lr>\r\n\r\n"[..]);
+
+        let mut writer = Writer::new(&mut out);
+        assert_eq!(retarget(&mut writer, buf, &req, "sip:bob@biloxi.com SIP/2.0"),
+                   Err(WriteError::RequestUri));
+    }
+
+    #[test]
+    fn test_retarget_strict() {
+        let buf = b"BYE sip:bob@biloxi.com SIP/2.0\r\n\
+                    Route: <sip:p1.example.com>\r\n\
+                    Call-ID: a84b4c76e66710\r\n\
+                    Route: <sip:p2.example.com;lr>\r\n\r\n";
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse(buf).unwrap();
+
+        let mut out = [0u8; 256];
+        let mut writer = Writer::new(&mut out);
+        retarget(&mut writer, buf, &req, "sip:bob@192.0.2.4").unwrap();
+        assert_eq!(writer.written(),
+                   &b"BYE sip:p1.example.com SIP/2.0\r\n\
+                      Call-ID: a84b4c76e66710\r\n\
+                      Route: <sip:p2.example.com;lr>\r\n\
+                      Route: <sip:bob@192.0.2.4>\r\n\r\n"[..]);
+    }
+
+    #[test]
+    fn test_pop_via_whole_header() {
+        let buf = b"SIP/2.0 180 Ringing\r\n\
+                    v: SIP/2.0/UDP proxy.biloxi.com;branch=z9hG4bK1\r\n  \r\n\
+                    Via: SIP/2.0/UDP pc33.atlanta.com\r\n\r\n";
+        let mut headers = [EMPTY_HEADER; 8];
+        let mut res = Response::new(&mut headers);
+        res.parse(buf).unwrap();
+
+        let mut out = [0u8; 256];
+        let mut writer = Writer::new(&mut out);
+        pop_via(&mut writer, buf, &res).unwrap();
+        assert_eq!(writer.written(),
+                   &b"SIP/2.0 180 Ringing\r\nVia: SIP/2.0/UDP pc33.atlanta.com\r\n\r\n"[..]);
+    }
+
+    #[test]
+    fn test_pop_via_missing() {
+        let buf = b"SIP/2.0 180 Ringing\r\n\r\n";
+        let mut headers = [EMPTY_HEADER; 8];
+        let mut res = Response::new(&mut headers);
+        res.parse(buf).unwrap();
+        let mut out = [0u8; 256];
+        assert_eq!(pop_via(&mut Writer::new(&mut out), buf, &res), Err(WriteError::MissingVia));
+    }
+
+    #[test]
+    fn test_pop_via_other_buffer() {
+        let buf = b"SIP/2.0 180 Ringing\r\nVia: SIP/2.0/UDP a\r\n\r\n";
+        let mut headers = [EMPTY_HEADER; 8];
+        let mut res = Response::new(&mut headers);
+        res.parse(buf).unwrap();
+        let copy = *buf;
+        let mut out = [0u8; 256];
+        assert_eq!(pop_via(&mut Writer::new(&mut out), &copy, &res),
+                   Err(WriteError::NotInBuffer));
+    }
+}
@@ -0,0 +1,144 @@
+//! Collecting the Via hops and route sets of a message.
+//!
+//! Responses travel back along the Via hops of their request, topmost
+//! first, see [RFC 3261](https://tools.ietf.org/html/rfc3261#section-18.2.2).
+//! Requests within a dialog follow the route set recorded by the
+//! Record-Route headers of the request creating it: in order at the UAS,
+//! and reversed at the UAC, see
+//! [RFC 3261](https://tools.ietf.org/html/rfc3261#section-12.1).
+//!
+//! Each of these may be spread across several headers, and across the
+//! comma-separated elements of one header; `Routing` yields the elements
+//! of all of them in order, parsed.
+//!
+//! # Example
+//!
+//! ```
+//! use parsip::SipMessage;
+//!
+//! let buf = b"SIP/2.0 200 OK\r\n\
+//!             Via: SIP/2.0/UDP p1.example.com;branch=z9hG4bK1,\r\n \
+//!                  SIP/2.0/TCP 192.0.2.4:5080;branch=z9hG4bK2\r\n\
+//!             v: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK3\r\n\
+//!             Record-Route: <sip:p1.example.com;lr>, <sip:p2.example.com;lr>\r\n\
+//!             Record-Route: <sip:p3.example.com>\r\n\r\n";
+//! let mut headers = [parsip::EMPTY_HEADER; 8];
+//! let mut res = parsip::Response::new(&mut headers);
+//! res.parse(buf).unwrap();
+//!
+//! let routing = res.routing();
+//! let branches: Vec<_> = routing.vias().map(|via| via.unwrap().branch.unwrap()).collect();
+//! assert_eq!(branches, [&b"z9hG4bK1"[..], b"z9hG4bK2", b"z9hG4bK3"]);
+//!
+//! // the route set of the UAC
+//! let mut route_set = routing.record_routes().collect::<Result<Vec<_>, _>>().unwrap();
+//! route_set.reverse();
+//! assert_eq!(route_set[0].addr.uri, b"sip:p3.example.com");
+//! assert!(!route_set[0].lr && route_set[2].lr);
+//! ```
+
+use headers::typed::{Route, ValueError, Via};
+use headers::{Headers, Values};
+use sip::Header;
+
+/// The Via, Route and Record-Route elements of a header section.
+///
+/// Created by `SipMessage::routing`, or from the headers with `new`.
+#[derive(Copy, Clone, Debug)]
+pub struct Routing<'h, 'b: 'h> {
+    headers: Headers<'h, 'b>,
+}
+
+impl<'h, 'b> Routing<'h, 'b> {
+    /// Wraps a parsed header section.
+    #[inline]
+    pub fn new(headers: &'h [Header<'b>]) -> Routing<'h, 'b> {
+        Routing { headers: Headers::new(headers) }
+    }
+
+    /// The elements of all the Via headers, topmost first.
+    #[inline]
+    pub fn vias(&self) -> Vias<'h, 'b> {
+        Vias { values: self.headers.values("Via") }
+    }
+
+    /// The elements of all the Route headers, next hop first.
+    #[inline]
+    pub fn routes(&self) -> Routes<'h, 'b> {
+        Routes { values: self.headers.values("Route") }
+    }
+
+    /// The elements of all the Record-Route headers, in order.
+    #[inline]
+    pub fn record_routes(&self) -> Routes<'h, 'b> {
+        Routes { values: self.headers.values("Record-Route") }
+    }
+}
+
+/// An iterator over the Via elements of a header section.
+///
+/// Created by `Routing::vias`.
+#[derive(Clone, Debug)]
+pub struct Vias<'h, 'b: 'h> {
+    values: Values<'h, 'b, 'static>,
+}
+
+impl<'h, 'b> Iterator for Vias<'h, 'b> {
+    type Item = Result<Via<'b>, ValueError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Result<Via<'b>, ValueError>> {
+        self.values.next().map(Via::parse)
+    }
+}
+
+/// An iterator over the Route or Record-Route elements of a header
+/// section.
+///
+/// Created by `Routing::routes` and `Routing::record_routes`.
+#[derive(Clone, Debug)]
+pub struct Routes<'h, 'b: 'h> {
+    values: Values<'h, 'b, 'static>,
+}
+
+impl<'h, 'b> Iterator for Routes<'h, 'b> {
+    type Item = Result<Route<'b>, ValueError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Result<Route<'b>, ValueError>> {
+        self.values.next().map(Route::parse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use headers::typed::ValueError;
+    use sip::{Request, EMPTY_HEADER};
+    use super::Routing;
+
+    #[test]
+    fn test_routes() {
+        let buf = b"BYE sip:bob@192.0.2.4 SIP/2.0\r\n\
+                    Route: \"Proxy, one\" <sip:p1.example.com;lr>,\r\n\t<sip:p2.example.com;lr>\r\n\
+                    Via: SIP/2.0/TLS\r\n client.atlanta.com;branch=z9hG4bK1\r\n\
+                    Route: <sip:p3.example.com;lr\r\n\r\n";
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse(buf).unwrap();
+        let routing = Routing::new(req.headers);
+
+        let mut routes = routing.routes();
+        let route = routes.next().unwrap().unwrap();
+        assert_eq!(route.addr.display_name, Some(&b"Proxy, one"[..]));
+        assert!(route.lr);
+        assert_eq!(routes.next().unwrap().unwrap().addr.uri, b"sip:p2.example.com;lr");
+        assert_eq!(routes.next(), Some(Err(ValueError::Addr)));
+        assert_eq!(routes.next(), None);
+        assert_eq!(routing.record_routes().next(), None);
+
+        let vias: Vec<_> = routing.vias().collect();
+        let via = vias[0].unwrap();
+        assert_eq!((via.transport, via.host), (&b"TLS"[..], &b"client.atlanta.com"[..]));
+        assert_eq!(vias.len(), 1);
+    }
+}
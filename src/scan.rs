@@ -0,0 +1,157 @@
+//! Scanning helpers for header values.
+
+use params;
+
+/// Splits the first element off a comma-separated header value.
+///
+/// Commas inside quoted strings and angle brackets do not separate
+/// elements. Returns the first element without surrounding whitespace, and
+/// everything after the separating comma, if any.
+pub fn split_first(value: &[u8]) -> (&[u8], Option<&[u8]>) {
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut angle = false;
+    for (idx, &b) in value.iter().enumerate() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match b {
+            b'\\' if quoted => escaped = true,
+            b'"' => quoted = !quoted,
+            b'<' if !quoted => angle = true,
+            b'>' if !quoted => angle = false,
+            b',' if !quoted && !angle => return (trim(&value[..idx]), Some(&value[idx + 1..])),
+            _ => {}
+        }
+    }
+    (trim(value), None)
+}
+
+/// Splits a name-addr or addr-spec header element into its URI and the
+/// header parameters following it, starting at their first `;`.
+pub fn split_addr(element: &[u8]) -> (&[u8], &[u8]) {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (idx, &b) in element.iter().enumerate() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match b {
+            b'\\' if quoted => escaped = true,
+            b'"' => quoted = !quoted,
+            b'<' if !quoted => {
+                let rest = &element[idx + 1..];
+                return match rest.iter().position(|&b| b == b'>') {
+                    Some(close) => (&rest[..close], &rest[close + 1..]),
+                    None => (rest, b""),
+                };
+            }
+            _ => {}
+        }
+    }
+    // without angle brackets, parameters belong to the header
+    match element.iter().position(|&b| b == b';') {
+        Some(semi) => (trim(&element[..semi]), &element[semi..]),
+        None => (trim(element), b""),
+    }
+}
+
+/// Value of the parameter `name` in `params`, such as `;tag=1;lr`.
+///
+/// Parameters without a value, like `lr`, have an empty one.
+pub fn param<'a>(params: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    params::parse(params)
+        .find(|&(param_name, _)| param_name.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.unwrap_or(b""))
+}
+
+/// Errors in the values of `Content-Length` headers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LengthError {
+    /// A value is not a number, or does not fit in a `usize`.
+    Invalid,
+    /// Values differ.
+    Conflicting,
+}
+
+/// Combines the values of all `Content-Length` headers, each possibly a
+/// comma-separated list, into the length of the body.
+///
+/// Equal duplicates are tolerated, differing ones are not: picking either
+/// would let a crafted message smuggle another one in its body.
+pub fn content_length<'a, I>(values: I) -> Result<Option<usize>, LengthError>
+    where I: IntoIterator<Item = &'a [u8]>
+{
+    let mut length = None;
+    for value in values.into_iter().flat_map(|value| value.split(|&b| b == b',')) {
+        let value = trim(value);
+        if value.is_empty() || !value.iter().all(u8::is_ascii_digit) {
+            return Err(LengthError::Invalid);
+        }
+        let value = value.iter()
+            .try_fold(0usize, |acc, &b| acc.checked_mul(10)?.checked_add((b - b'0') as usize))
+            .ok_or(LengthError::Invalid)?;
+        match length {
+            Some(length) if length != value => return Err(LengthError::Conflicting),
+            _ => length = Some(value),
+        }
+    }
+    Ok(length)
+}
+
+/// Strips leading and trailing linear whitespace.
+pub fn trim(mut bytes: &[u8]) -> &[u8] {
+    while let [b' ', rest @ ..] | [b'\t', rest @ ..] | [b'\r', rest @ ..] | [b'\n', rest @ ..] =
+        bytes {
+        bytes = rest;
+    }
+    while let [rest @ .., b' '] | [rest @ .., b'\t'] | [rest @ .., b'\r'] | [rest @ .., b'\n'] =
+        bytes {
+        bytes = rest;
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_length, param, split_addr, split_first, LengthError};
+
+    #[test]
+    fn test_split_first() {
+        assert_eq!(split_first(b"SIP/2.0/UDP a, SIP/2.0/UDP b"),
+                   (&b"SIP/2.0/UDP a"[..], Some(&b" SIP/2.0/UDP b"[..])));
+        assert_eq!(split_first(b"\"Doe, John\" <sip:j@x;a=1,2>, <sip:k@y>"),
+                   (&b"\"Doe, John\" <sip:j@x;a=1,2>"[..], Some(&b" <sip:k@y>"[..])));
+        assert_eq!(split_first(b"\"a\\\",b\"  "), (&b"\"a\\\",b\""[..], None));
+        assert_eq!(split_first(b""), (&b""[..], None));
+    }
+
+    #[test]
+    fn test_split_addr() {
+        assert_eq!(split_addr(b"\"A <b>\" <sip:a@x;lr>;tag=1"),
+                   (&b"sip:a@x;lr"[..], &b";tag=1"[..]));
+        assert_eq!(split_addr(b" sip:a@x ;tag=1"), (&b"sip:a@x"[..], &b";tag=1"[..]));
+        assert_eq!(split_addr(b"*"), (&b"*"[..], &b""[..]));
+    }
+
+    #[test]
+    fn test_param() {
+        assert_eq!(param(b";q=0.7; Expires = 60;lr", b"expires"), Some(&b"60"[..]));
+        assert_eq!(param(b";q=0.7;lr", b"lr"), Some(&b""[..]));
+        assert_eq!(param(b";q=0.7", b"expires"), None);
+        assert_eq!(param(b";a=\"x;b=1\"", b"b"), None);
+    }
+
+    #[test]
+    fn test_content_length() {
+        assert_eq!(content_length([&b" 12 "[..], b"12,12"]), Ok(Some(12)));
+        assert_eq!(content_length([&b"12"[..], b"13"]), Err(LengthError::Conflicting));
+        assert_eq!(content_length(Some(&b"1 2"[..])), Err(LengthError::Invalid));
+        assert_eq!(content_length(Some(&b","[..])), Err(LengthError::Invalid));
+        assert_eq!(content_length(Some(&b"99999999999999999999999"[..])),
+                   Err(LengthError::Invalid));
+        assert_eq!(content_length(None), Ok(None));
+    }
+}
@@ -0,0 +1,439 @@
+//! Zero-copy parsing of SDP session descriptions, as carried in the
+//! `application/sdp` bodies of INVITEs and their answers.
+//!
+//! The whole description is checked by `SdpSession::parse`; the media
+//! descriptions and attributes are then read with iterators, so no
+//! allocation is needed.
+//!
+//! From [RFC 4566](https://tools.ietf.org/html/rfc4566#section-5):
+//!
+//! > ```notrust
+//! > session-description = proto-version origin-field session-name-field
+//! >                       ... connection-field ... attribute-fields
+//! >                       media-descriptions
+//! > ```
+//!
+//! Lines may end with `CRLF` or a bare `LF`, and empty lines are skipped.
+//!
+//! # Example
+//!
+//! ```
+//! use parsip::sdp::SdpSession;
+//!
+//! let body = b"v=0\r\n\
+//!              o=alice 2890844526 2890844526 IN IP4 atlanta.com\r\n\
+//!              s=-\r\n\
+//!              c=IN IP4 192.0.2.101\r\n\
+//!              t=0 0\r\n\
+//!              m=audio 49172 RTP/AVP 0 8\r\n\
+//!              a=rtpmap:0 PCMU/8000\r\n\
+//!              a=sendrecv\r\n\
+//!              m=video 0 RTP/AVP 31\r\n";
+//! let session = SdpSession::parse(body).unwrap();
+//! assert_eq!(session.origin.username, b"alice");
+//! assert_eq!(session.connection.unwrap().address, b"192.0.2.101");
+//!
+//! let audio = session.media().next().unwrap();
+//! assert_eq!((audio.media, audio.port, audio.formats), (&b"audio"[..], 49172, &b"0 8"[..]));
+//! assert_eq!(audio.attribute("rtpmap"), Some(Some(&b"0 PCMU/8000"[..])));
+//! assert_eq!(audio.attribute("sendrecv"), Some(None));
+//! assert_eq!(session.media().count(), 2);
+//! ```
+
+/// Errors in parsing a session description.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SdpError {
+    /// A line is not of the form `<type>=<value>`.
+    Line,
+    /// The description does not start with `v=0`.
+    Version,
+    /// The `o=` line is missing or malformed.
+    Origin,
+    /// The `s=` line is missing.
+    SessionName,
+    /// A `c=` line is malformed.
+    Connection,
+    /// An `m=` line is malformed.
+    Media,
+}
+
+/// A parsed session description.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SdpSession<'a> {
+    /// The origin, from the `o=` line.
+    pub origin: Origin<'a>,
+    /// The session name, from the `s=` line.
+    pub name: &'a [u8],
+    /// The session-level connection data, from the `c=` line.
+    pub connection: Option<Connection<'a>>,
+    /// The session-level lines following the `s=` line.
+    pub lines: &'a [u8],
+    /// The media descriptions, from the first `m=` line on.
+    media: &'a [u8],
+}
+
+impl<'a> SdpSession<'a> {
+    /// Parses and checks a whole session description.
+    pub fn parse(body: &'a [u8]) -> Result<SdpSession<'a>, SdpError> {
+        let mut rest = body;
+        match next_line(&mut rest) {
+            Some(Ok((b'v', b"0"))) => {}
+            Some(Err(err)) => return Err(err),
+            _ => return Err(SdpError::Version),
+        }
+        let origin = match next_line(&mut rest) {
+            Some(Ok((b'o', value))) => Origin::parse(value)?,
+            Some(Err(err)) => return Err(err),
+            _ => return Err(SdpError::Origin),
+        };
+        let name = match next_line(&mut rest) {
+            Some(Ok((b's', value))) => value,
+            Some(Err(err)) => return Err(err),
+            _ => return Err(SdpError::SessionName),
+        };
+        let lines = rest;
+        let mut connection = None;
+        let mut media = None;
+        loop {
+            let line = rest;
+            match next_line(&mut rest) {
+                None => break,
+                Some(Err(err)) => return Err(err),
+                Some(Ok((b'm', value))) => {
+                    media_line(value)?;
+                    media.get_or_insert(line);
+                }
+                Some(Ok((b'c', value))) => {
+                    let parsed = Connection::parse(value)?;
+                    if media.is_none() {
+                        connection.get_or_insert(parsed);
+                    }
+                }
+                Some(Ok(_)) => {}
+            }
+        }
+        let media = media.unwrap_or(b"");
+        Ok(SdpSession {
+            origin,
+            name,
+            connection,
+            lines: &lines[..lines.len() - media.len()],
+            media,
+        })
+    }
+
+    /// The session-level attributes.
+    #[inline]
+    pub fn attributes(&self) -> Attributes<'a> {
+        Attributes { rest: self.lines }
+    }
+
+    /// The value of the first session-level attribute called `name`,
+    /// `Some(None)` for a flag such as `sendrecv`.
+    #[inline]
+    pub fn attribute(&self, name: &str) -> Option<Option<&'a [u8]>> {
+        find_attribute(self.lines, name)
+    }
+
+    /// The media descriptions.
+    #[inline]
+    pub fn media(&self) -> MediaDescriptions<'a> {
+        MediaDescriptions { rest: self.media }
+    }
+}
+
+/// The origin of a session.
+///
+/// > ```notrust
+/// > origin-field = %x6f "=" username SP sess-id SP sess-version SP
+/// >                nettype SP addrtype SP unicast-address CRLF
+/// > ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Origin<'a> {
+    /// The username, `-` when there is none.
+    pub username: &'a [u8],
+    /// The session identifier.
+    pub session_id: &'a [u8],
+    /// The version of the description, increased on every change.
+    pub session_version: &'a [u8],
+    /// The network type, such as `IN`.
+    pub net_type: &'a [u8],
+    /// The address type, such as `IP4`.
+    pub addr_type: &'a [u8],
+    /// The address of the originating host.
+    pub address: &'a [u8],
+}
+
+impl<'a> Origin<'a> {
+    fn parse(value: &'a [u8]) -> Result<Origin<'a>, SdpError> {
+        let mut fields = [&b""[..]; 6];
+        split_fields(value, &mut fields).ok_or(SdpError::Origin)?;
+        Ok(Origin {
+            username: fields[0],
+            session_id: fields[1],
+            session_version: fields[2],
+            net_type: fields[3],
+            addr_type: fields[4],
+            address: fields[5],
+        })
+    }
+}
+
+/// Connection data.
+///
+/// > ```notrust
+/// > connection-field = [%x63 "=" nettype SP addrtype SP
+/// >                    connection-address CRLF]
+/// > ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Connection<'a> {
+    /// The network type, such as `IN`.
+    pub net_type: &'a [u8],
+    /// The address type, such as `IP4`.
+    pub addr_type: &'a [u8],
+    /// The address, with the TTL and count suffixes of a multicast one.
+    pub address: &'a [u8],
+}
+
+impl<'a> Connection<'a> {
+    fn parse(value: &'a [u8]) -> Result<Connection<'a>, SdpError> {
+        let mut fields = [&b""[..]; 3];
+        split_fields(value, &mut fields).ok_or(SdpError::Connection)?;
+        Ok(Connection { net_type: fields[0], addr_type: fields[1], address: fields[2] })
+    }
+}
+
+/// A media description.
+///
+/// > ```notrust
+/// > media-field = %x6d "=" media SP port ["/" integer]
+/// >               SP proto 1*(SP fmt) CRLF
+/// > ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Media<'a> {
+    /// The media type, such as `audio`.
+    pub media: &'a [u8],
+    /// The transport port, `0` for a rejected stream.
+    pub port: u16,
+    /// The number of ports, if given.
+    pub port_count: Option<u16>,
+    /// The transport protocol, such as `RTP/AVP`.
+    pub proto: &'a [u8],
+    /// The space-separated media formats, such as RTP payload types.
+    pub formats: &'a [u8],
+    /// The media-level connection data.
+    pub connection: Option<Connection<'a>>,
+    /// The lines following the `m=` line.
+    pub lines: &'a [u8],
+}
+
+impl<'a> Media<'a> {
+    /// The media-level attributes.
+    #[inline]
+    pub fn attributes(&self) -> Attributes<'a> {
+        Attributes { rest: self.lines }
+    }
+
+    /// The value of the first media-level attribute called `name`,
+    /// `Some(None)` for a flag such as `sendrecv`.
+    #[inline]
+    pub fn attribute(&self, name: &str) -> Option<Option<&'a [u8]>> {
+        find_attribute(self.lines, name)
+    }
+}
+
+/// An iterator over the media descriptions of a session.
+#[derive(Clone, Debug)]
+pub struct MediaDescriptions<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for MediaDescriptions<'a> {
+    type Item = Media<'a>;
+
+    fn next(&mut self) -> Option<Media<'a>> {
+        let value = match next_line(&mut self.rest)? {
+            Ok((b'm', value)) => value,
+            // checked by `SdpSession::parse`
+            _ => return None,
+        };
+        let mut media = media_line(value).ok()?;
+        let lines = self.rest;
+        loop {
+            let line = self.rest;
+            match next_line(&mut self.rest) {
+                Some(Ok((b'm', _))) => {
+                    self.rest = line;
+                    break;
+                }
+                Some(Ok((b'c', value))) if media.connection.is_none() => {
+                    media.connection = Connection::parse(value).ok();
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+        media.lines = &lines[..lines.len() - self.rest.len()];
+        Some(media)
+    }
+}
+
+/// An attribute, from an `a=` line.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Attribute<'a> {
+    /// The name, such as `rtpmap`.
+    pub name: &'a [u8],
+    /// The value after the `:`, `None` for a flag such as `sendrecv`.
+    pub value: Option<&'a [u8]>,
+}
+
+/// An iterator over the attributes of a session or media description.
+#[derive(Clone, Debug)]
+pub struct Attributes<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for Attributes<'a> {
+    type Item = Attribute<'a>;
+
+    fn next(&mut self) -> Option<Attribute<'a>> {
+        loop {
+            if let Ok((b'a', value)) = next_line(&mut self.rest)? {
+                return Some(match value.iter().position(|&b| b == b':') {
+                    Some(colon) => {
+                        Attribute { name: &value[..colon], value: Some(&value[colon + 1..]) }
+                    }
+                    None => Attribute { name: value, value: None },
+                });
+            }
+        }
+    }
+}
+
+fn find_attribute<'a>(lines: &'a [u8], name: &str) -> Option<Option<&'a [u8]>> {
+    Attributes { rest: lines }
+        .find(|attribute| attribute.name == name.as_bytes())
+        .map(|attribute| attribute.value)
+}
+
+/// Splits the next non-empty line off `rest`, returning its type and
+/// value.
+fn next_line<'a>(rest: &mut &'a [u8]) -> Option<Result<(u8, &'a [u8]), SdpError>> {
+    loop {
+        if rest.is_empty() {
+            return None;
+        }
+        let (line, next) = match rest.iter().position(|&b| b == b'\n') {
+            Some(lf) => (&rest[..lf], &rest[lf + 1..]),
+            None => (&rest[..], &b""[..]),
+        };
+        *rest = next;
+        let line = match line {
+            [line @ .., b'\r'] => line,
+            line => line,
+        };
+        match *line {
+            [] => continue,
+            [kind, b'=', ..] if kind.is_ascii_lowercase() => return Some(Ok((kind, &line[2..]))),
+            _ => return Some(Err(SdpError::Line)),
+        }
+    }
+}
+
+/// Parses the value of an `m=` line, into a description without lines.
+fn media_line(value: &[u8]) -> Result<Media<'_>, SdpError> {
+    let mut fields = value.splitn(4, |&b| b == b' ');
+    let mut field = || fields.next().filter(|field| !field.is_empty()).ok_or(SdpError::Media);
+    let media = field()?;
+    let ports = field()?;
+    let proto = field()?;
+    let formats = field()?;
+    let (port, port_count) = match ports.iter().position(|&b| b == b'/') {
+        Some(slash) => (number(&ports[..slash]), Some(number(&ports[slash + 1..]))),
+        None => (number(ports), None),
+    };
+    let port = port.ok_or(SdpError::Media)?;
+    let port_count = match port_count {
+        Some(count) => Some(count.ok_or(SdpError::Media)?),
+        None => None,
+    };
+    Ok(Media { media, port, port_count, proto, formats, connection: None, lines: b"" })
+}
+
+/// Splits `value` into exactly `fields.len()` non-empty fields separated
+/// by single spaces.
+fn split_fields<'a>(value: &'a [u8], fields: &mut [&'a [u8]]) -> Option<()> {
+    let mut parts = value.split(|&b| b == b' ');
+    for field in fields.iter_mut() {
+        *field = parts.next().filter(|part| !part.is_empty())?;
+    }
+    match parts.next() {
+        Some(_) => None,
+        None => Some(()),
+    }
+}
+
+/// Parses a decimal number up to 65535.
+fn number(digits: &[u8]) -> Option<u16> {
+    if digits.is_empty() || digits.len() > 5 || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let value = digits.iter().fold(0u32, |acc, &b| acc * 10 + (b - b'0') as u32);
+    if value > 65535 {
+        return None;
+    }
+    Some(value as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Attribute, Connection, SdpError, SdpSession};
+
+    const OFFER: &[u8] = b"v=0\n\
+                           o=- 20518 0 IN IP6 2001:db8::1\n\
+                           s= \n\
+                           a=group:BUNDLE 0\n\
+                           m=audio 5004/2 RTP/AVP 96\r\n\
+                           c=IN IP4 224.2.17.12/127\r\n\
+                           \r\n\
+                           a=rtpmap:96 opus/48000/2\r\n";
+
+    #[test]
+    fn test_session() {
+        let session = SdpSession::parse(OFFER).unwrap();
+        assert_eq!(session.origin.address, b"2001:db8::1");
+        assert_eq!((session.name, session.connection), (&b" "[..], None));
+        assert_eq!(session.attributes().collect::<Vec<_>>(),
+                   [Attribute { name: b"group", value: Some(b"BUNDLE 0") }]);
+
+        let mut media = session.media();
+        let audio = media.next().unwrap();
+        assert_eq!((audio.port, audio.port_count), (5004, Some(2)));
+        assert_eq!(audio.connection,
+                   Some(Connection {
+                       net_type: b"IN",
+                       addr_type: b"IP4",
+                       address: b"224.2.17.12/127",
+                   }));
+        assert_eq!(audio.attribute("rtpmap"), Some(Some(&b"96 opus/48000/2"[..])));
+        assert_eq!(audio.attribute("group"), None);
+        assert_eq!(media.next(), None);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(SdpSession::parse(b""), Err(SdpError::Version));
+        assert_eq!(SdpSession::parse(b"v=1\r\n"), Err(SdpError::Version));
+        assert_eq!(SdpSession::parse(b"v=0\r\no=a b c IN IP4\r\n"), Err(SdpError::Origin));
+        assert_eq!(SdpSession::parse(b"v=0\r\no=a 1 1 IN IP4 h\r\n"), Err(SdpError::SessionName));
+        let head = "v=0\r\no=a 1 1 IN IP4 h\r\ns=x\r\n";
+        let parse = |rest: &str| SdpSession::parse([head, rest].concat().as_bytes()).map(|_| ());
+        assert_eq!(parse(""), Ok(()));
+        assert_eq!(parse("x\r\n"), Err(SdpError::Line));
+        assert_eq!(parse("A=b\r\n"), Err(SdpError::Line));
+        assert_eq!(parse("c=IN IP4\r\n"), Err(SdpError::Connection));
+        assert_eq!(parse("m=audio 70000 RTP/AVP 0\r\n"), Err(SdpError::Media));
+        assert_eq!(parse("m=audio 1/x RTP/AVP 0\r\n"), Err(SdpError::Media));
+        assert_eq!(parse("m=audio 1 RTP/AVP\r\n"), Err(SdpError::Media));
+    }
+}
@@ -0,0 +1,95 @@
+//! Serde support, behind the `serde` feature.
+//!
+//! Byte strings, such as header values and bodies, are serialized as
+//! strings when they are valid UTF-8, and as bytes otherwise, so they read
+//! naturally in formats like JSON. Deserializing accepts either form.
+
+#[cfg(feature = "std")]
+use std::fmt;
+use std::str;
+#[cfg(feature = "std")]
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+use sip::HeaderValue;
+
+/// Serializes bytes as a string when they are valid UTF-8.
+struct Text<'a>(&'a [u8]);
+
+impl<'a> Serialize for Text<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match str::from_utf8(self.0) {
+            Ok(text) => serializer.serialize_str(text),
+            Err(_) => serializer.serialize_bytes(self.0),
+        }
+    }
+}
+
+impl<'a> Serialize for HeaderValue<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Text(self.as_bytes()).serialize(serializer)
+    }
+}
+
+/// `serialize_with` function writing bytes as `Text`.
+pub(crate) fn text<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where T: AsRef<[u8]> + ?Sized,
+          S: Serializer
+{
+    Text(bytes.as_ref()).serialize(serializer)
+}
+
+/// `serialize_with` function writing optional bytes as `Text`.
+pub(crate) fn opt_text<T, S>(bytes: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where T: AsRef<[u8]>,
+          S: Serializer
+{
+    match *bytes {
+        Some(ref bytes) => serializer.serialize_some(&Text(bytes.as_ref())),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Bytes deserialized from a string, bytes or a sequence of numbers.
+#[cfg(feature = "std")]
+pub(crate) struct Buf(pub(crate) Vec<u8>);
+
+#[cfg(feature = "std")]
+impl<'de> Deserialize<'de> for Buf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Buf, D::Error> {
+        struct BufVisitor;
+
+        impl<'de> Visitor<'de> for BufVisitor {
+            type Value = Buf;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string or bytes")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Buf, E> {
+                Ok(Buf(value.as_bytes().to_vec()))
+            }
+
+            fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Buf, E> {
+                Ok(Buf(value.to_vec()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Buf, A::Error> {
+                let mut buf = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(b) = seq.next_element()? {
+                    buf.push(b);
+                }
+                Ok(Buf(buf))
+            }
+        }
+
+        deserializer.deserialize_any(BufVisitor)
+    }
+}
+
+/// `deserialize_with` function reading optional bytes written by
+/// `opt_text`.
+#[cfg(feature = "std")]
+pub(crate) fn opt_buf<'de, D: Deserializer<'de>>(deserializer: D)
+                                                 -> Result<Option<Vec<u8>>, D::Error> {
+    Ok(Option::<Buf>::deserialize(deserializer)?.map(|buf| buf.0))
+}
@@ -1,25 +1,80 @@
-use nom::{digit, is_space, line_ending, crlf, rest};
-use std::{str, slice};
+use std::{cmp, fmt, mem, result, str};
+use std::iter::FusedIterator;
+use std::ops::Deref;
+use std::ptr;
+use memchr::{memchr, memchr2};
 use lookup::{is_token, is_request_uri, is_reason_phrase, is_header_value};
+use headers::{canonical_name, expand_compact, groups, names_eq, GetAll, Groups, Headers, Values};
+use scan::{self, LengthError};
+use escape::unescape_into;
+use routing::Routing;
 
 /// A Result of any parsing action.
 ///
-/// If the input is invalid, an `IResult::Error` will be returned.
-/// Note that incomplete data is not considered invalid,
-/// and so will not return an error, but rather a `IResult::Incomplete(_)`.
-pub use nom::{IResult, Err, ErrorKind, Needed};
+/// If the input is invalid, an `Error` will be returned. Note that
+/// incomplete data is not considered invalid, and so will not return an
+/// error, but rather a `Ok(Status::Partial(_))`.
+pub type Result<T> = result::Result<Status<T>, Error>;
+
+/// The result of a successful parse pass.
+///
+/// `Complete` is used when the buffer contained the complete value.
+/// `Partial` is used when parsing did not reach the end of the expected
+/// value, but no invalid data was found.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Status<T> {
+    /// The completed result.
+    Complete(T),
+    /// A partial result; more bytes are needed. Holds the length of the
+    /// whole message when it is already known, as for a partial body.
+    Partial(Option<usize>),
+}
+
+impl<T> Status<T> {
+    /// Convenience method to check if status is complete.
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        matches!(*self, Status::Complete(..))
+    }
+
+    /// Convenience method to check if status is partial.
+    #[inline]
+    pub fn is_partial(&self) -> bool {
+        !self.is_complete()
+    }
+
+    /// Maps a `Complete` value with `f`, keeping a `Partial` status as is.
+    #[inline]
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Status<U> {
+        match self {
+            Status::Complete(t) => Status::Complete(f(t)),
+            Status::Partial(len) => Status::Partial(len),
+        }
+    }
+
+    /// Convenience method to unwrap a `Complete` value.
+    ///
+    /// # Panics
+    ///
+    /// If the status is `Partial`.
+    #[inline]
+    pub fn unwrap(self) -> T {
+        match self {
+            Status::Complete(t) => t,
+            Status::Partial(_) => panic!("Tried to unwrap Status::Partial"),
+        }
+    }
+}
 
 #[inline]
 fn shrink<T>(slice: &mut &mut [T], len: usize) {
-    debug_assert!(slice.len() >= len);
-    let ptr = slice.as_mut_ptr();
-    *slice = unsafe { slice::from_raw_parts_mut(ptr, len) };
+    let whole = mem::take(slice);
+    *slice = &mut whole[..len];
 }
 
-/// An error in parsing.
-/// TODO: for now this is unused; use this custom error type
+/// The kind of an error in parsing.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
-pub enum Error {
+pub enum ErrorKind {
     /// Invalid byte in header name.
     HeaderName,
     /// Invalid byte in header value.
@@ -34,8 +89,449 @@ pub enum Error {
     TooManyHeaders,
     /// Invalid byte in SIP version.
     Version,
+    /// A `Content-Length` value is malformed, or differs from another one.
+    ContentLength,
+    /// A line, header or header section exceeds a limit of the
+    /// `ParserConfig`.
+    TooLong,
+}
+
+impl ErrorKind {
+    /// A short description of the error.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            ErrorKind::HeaderName => "invalid header name",
+            ErrorKind::HeaderValue => "invalid header value",
+            ErrorKind::NewLine => "invalid new line",
+            ErrorKind::Status => "invalid response status",
+            ErrorKind::Token => "invalid token",
+            ErrorKind::TooManyHeaders => "too many headers",
+            ErrorKind::Version => "invalid SIP version",
+            ErrorKind::ContentLength => "invalid Content-Length",
+            ErrorKind::TooLong => "limit exceeded",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+/// An error in parsing: its kind, and the offset of the byte where parsing
+/// failed.
+///
+/// # Example
+///
+/// ```
+/// use parsip::{Error, ErrorKind};
+///
+/// let mut headers = [parsip::EMPTY_HEADER; 4];
+/// let mut res = parsip::Response::new(&mut headers);
+/// let err = res.parse(b"SIP/2.0 2OO OK\r\n\r\n").unwrap_err();
+/// assert_eq!(err, Error::new(ErrorKind::Status, 8));
+/// assert_eq!(err.to_string(), "invalid response status at byte 8");
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    offset: usize,
+}
+
+impl Error {
+    /// Creates an error of the given kind at byte `offset`.
+    #[inline]
+    pub const fn new(kind: ErrorKind, offset: usize) -> Error {
+        Error { kind, offset }
+    }
+
+    /// The kind of the error.
+    #[inline]
+    pub const fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The offset, in the parsed buffer, of the byte where parsing failed.
+    #[inline]
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.kind, self.offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for Error {}
+
+/// The outcome of an internal scanner run over a suffix of the buffer.
+#[derive(Debug, PartialEq)]
+enum Scan<'a, T> {
+    /// The scanned value, and the input following it.
+    Done(&'a [u8], T),
+    /// The input ends before the value does.
+    Incomplete,
+    /// The input is malformed, starting at the given suffix.
+    Error(ErrorKind, &'a [u8]),
+}
+
+impl<'a, T> Scan<'a, T> {
+    #[inline]
+    fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Scan<'a, U> {
+        match self {
+            Scan::Done(rest, value) => Scan::Done(rest, f(value)),
+            Scan::Incomplete => Scan::Incomplete,
+            Scan::Error(kind, at) => Scan::Error(kind, at),
+        }
+    }
+}
+
+/// Unwraps a `Scan::Done`, returning any other outcome from the enclosing
+/// scanner.
+macro_rules! scan {
+    ($scanned:expr) => (
+        match $scanned {
+            Scan::Done(rest, value) => (rest, value),
+            Scan::Incomplete => return Scan::Incomplete,
+            Scan::Error(kind, at) => return Scan::Error(kind, at),
+        }
+    );
+}
+
+/// Runs one scanner over a suffix of `$buf`.
+///
+/// Returns from the enclosing function when the scanner fails or needs
+/// more bytes, with errors located at their offset in `$buf`.
+macro_rules! step {
+    ($buf:expr, $scanned:expr) => (
+        match $scanned {
+            Scan::Done(rest, value) => (rest, value),
+            Scan::Error(kind, at) => return Err(Error::new(kind, $buf.len() - at.len())),
+            Scan::Incomplete => return Ok(Status::Partial(None)),
+        }
+    );
+}
+
+/// Parser configuration.
+///
+/// The default configuration follows RFC 3261 while being as permissive
+/// as the grammar allows.
+///
+/// # Example
+///
+/// ```
+/// let mut config = parsip::ParserConfig::default();
+/// config.strict_version(true);
+///
+/// let buf = b"SIP/3.0 200 OK\r\n\r\n";
+/// let mut headers = [parsip::EMPTY_HEADER; 4];
+/// let mut res = parsip::Response::new(&mut headers);
+/// assert!(res.parse_with(&config, buf).is_err());
+/// ```
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct ParserConfig {
+    strict_version: bool,
+    ascii_header_values: bool,
+    pub(crate) expand_compact_headers: bool,
+    pub(crate) allow_lf_line_endings: bool,
+    pub(crate) max_start_line_len: Option<usize>,
+    max_header_name_len: Option<usize>,
+    max_header_value_len: Option<usize>,
+    max_header_section_len: Option<usize>,
+    max_continuation_lines: Option<usize>,
+    metrics: MetricsHook,
+}
+
+impl ParserConfig {
+    /// Creates the default configuration, usable in const contexts.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// static CONFIG: parsip::ParserConfig = {
+    ///     let mut config = parsip::ParserConfig::new();
+    ///     config.strict_version(true);
+    ///     config
+    /// };
+    ///
+    /// let mut headers = [parsip::EMPTY_HEADER; 4];
+    /// let mut res = parsip::Response::new(&mut headers);
+    /// assert!(res.parse_with(&CONFIG, b"SIP/3.0 200 OK\r\n\r\n").is_err());
+    /// ```
+    #[inline]
+    pub const fn new() -> ParserConfig {
+        ParserConfig {
+            strict_version: false,
+            ascii_header_values: false,
+            expand_compact_headers: false,
+            allow_lf_line_endings: false,
+            max_start_line_len: None,
+            max_header_name_len: None,
+            max_header_value_len: None,
+            max_header_section_len: None,
+            max_continuation_lines: None,
+            metrics: MetricsHook(None),
+        }
+    }
+
+    /// Sets whether `SIP/2.0` is the only accepted SIP-Version.
+    #[inline]
+    pub const fn strict_version(&mut self, value: bool) -> &mut Self {
+        self.strict_version = value;
+        self
+    }
+
+    /// Sets whether header values must be pure US-ASCII.
+    ///
+    /// By default UTF-8 bytes are accepted as the grammar allows; enabling
+    /// this rejects them with `ErrorKind::HeaderValue`, for deployments that
+    /// want to exclude UTF-8 smuggling in sensitive headers.
+    #[inline]
+    pub const fn ascii_header_values(&mut self, value: bool) -> &mut Self {
+        self.ascii_header_values = value;
+        self
+    }
+
+    /// Sets whether compact header names are replaced by their long forms,
+    /// such as `Via` for `v`, see `headers::expand_compact`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut config = parsip::ParserConfig::new();
+    /// config.expand_compact_headers(true);
+    ///
+    /// let mut headers = [parsip::EMPTY_HEADER; 4];
+    /// let mut res = parsip::Response::new(&mut headers);
+    /// res.parse_with(&config, b"SIP/2.0 200 OK\r\ni: a84b4c76e66710\r\n\r\n").unwrap();
+    /// assert_eq!(res.headers[0].name, "Call-ID");
+    /// ```
+    #[inline]
+    pub const fn expand_compact_headers(&mut self, value: bool) -> &mut Self {
+        self.expand_compact_headers = value;
+        self
+    }
+
+    /// Sets whether lines may end with a bare `LF` instead of `CRLF`.
+    ///
+    /// This applies to the start line, the header lines and the empty line
+    /// closing the header section, for the user agents that get it wrong.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let buf = b"OPTIONS sip:carol@chicago.com SIP/2.0\nMax-Forwards: 70\n\n";
+    /// let mut headers = [parsip::EMPTY_HEADER; 4];
+    /// let mut req = parsip::Request::new(&mut headers);
+    /// assert!(req.parse(buf).is_err());
+    ///
+    /// let mut config = parsip::ParserConfig::new();
+    /// config.allow_lf_line_endings(true);
+    /// assert_eq!(req.parse_with(&config, buf), Ok(parsip::Status::Complete(buf.len())));
+    /// assert_eq!(req.headers[0].value, b"70");
+    /// ```
+    #[inline]
+    pub const fn allow_lf_line_endings(&mut self, value: bool) -> &mut Self {
+        self.allow_lf_line_endings = value;
+        self
+    }
+
+    /// Sets the maximum length of the start line, without its line end.
+    ///
+    /// Longer lines are rejected with `ErrorKind::TooLong` before the line
+    /// is parsed, so a flood without a line end is not scanned past the
+    /// limit. Unlimited by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use parsip::{Error, ErrorKind};
+    ///
+    /// let mut config = parsip::ParserConfig::new();
+    /// config.max_start_line_len(Some(32));
+    ///
+    /// let mut headers = [parsip::EMPTY_HEADER; 4];
+    /// let mut req = parsip::Request::new(&mut headers);
+    /// assert_eq!(req.parse_with(&config, b"INVITE sip:aaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"),
+    ///            Err(Error::new(ErrorKind::TooLong, 32)));
+    /// ```
+    #[inline]
+    pub const fn max_start_line_len(&mut self, max: Option<usize>) -> &mut Self {
+        self.max_start_line_len = max;
+        self
+    }
+
+    /// Sets the maximum length of a header name, rejecting longer ones with
+    /// `ErrorKind::TooLong`, even before the header line ends. Unlimited by
+    /// default.
+    #[inline]
+    pub const fn max_header_name_len(&mut self, max: Option<usize>) -> &mut Self {
+        self.max_header_name_len = max;
+        self
+    }
+
+    /// Sets the maximum length of a header value, including its folded
+    /// lines, rejecting longer ones with `ErrorKind::TooLong`, even before
+    /// the header line ends. Unlimited by default.
+    #[inline]
+    pub const fn max_header_value_len(&mut self, max: Option<usize>) -> &mut Self {
+        self.max_header_value_len = max;
+        self
+    }
+
+    /// Sets the maximum length of the header section, including the empty
+    /// line closing it.
+    ///
+    /// Longer sections are rejected with `ErrorKind::TooLong` without
+    /// scanning past the limit. Unlimited by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use parsip::{Error, ErrorKind};
+    ///
+    /// let mut config = parsip::ParserConfig::new();
+    /// config.max_header_section_len(Some(16));
+    ///
+    /// let buf = b"SIP/2.0 200 OK\r\nTo: <sip:b@biloxi.com>\r\n\r\n";
+    /// let mut headers = [parsip::EMPTY_HEADER; 4];
+    /// let mut res = parsip::Response::new(&mut headers);
+    /// assert_eq!(res.parse_with(&config, buf), Err(Error::new(ErrorKind::TooLong, 32)));
+    /// ```
+    #[inline]
+    pub const fn max_header_section_len(&mut self, max: Option<usize>) -> &mut Self {
+        self.max_header_section_len = max;
+        self
+    }
+
+    /// Sets the maximum number of continuation lines a header value may be
+    /// folded onto, rejecting more with `ErrorKind::TooLong`. Unlimited by
+    /// default.
+    #[inline]
+    pub const fn max_continuation_lines(&mut self, max: Option<usize>) -> &mut Self {
+        self.max_continuation_lines = max;
+        self
+    }
+
+    /// Returns `true` if a limit applies to the header section.
+    #[inline]
+    pub(crate) fn limits_headers(&self) -> bool {
+        self.max_header_name_len.is_some() || self.max_header_value_len.is_some() ||
+        self.max_header_section_len.is_some() || self.max_continuation_lines.is_some()
+    }
+
+    /// Sets the `Metrics` that `parse_with` reports every parse into.
+    #[inline]
+    pub const fn metrics(&mut self, metrics: &'static dyn Metrics) -> &mut Self {
+        self.metrics = MetricsHook(Some(metrics));
+        self
+    }
+}
+
+/// Receives counts from the parser, for monitoring.
+///
+/// Every call to `Request::parse_with` or `Response::parse_with` with a
+/// `ParserConfig` holding a `Metrics` reports its outcome with exactly one
+/// call. All methods do nothing by default.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// struct Counters {
+///     messages: AtomicUsize,
+///     errors: AtomicUsize,
+/// }
+///
+/// impl parsip::Metrics for Counters {
+///     fn parsed(&self, _len: usize, _headers: usize) {
+///         self.messages.fetch_add(1, Ordering::Relaxed);
+///     }
+///
+///     fn error(&self, _error: &parsip::Error, _scanned: usize) {
+///         self.errors.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// static COUNTERS: Counters = Counters {
+///     messages: AtomicUsize::new(0),
+///     errors: AtomicUsize::new(0),
+/// };
+///
+/// let mut config = parsip::ParserConfig::new();
+/// config.metrics(&COUNTERS);
+///
+/// let mut headers = [parsip::EMPTY_HEADER; 4];
+/// let mut res = parsip::Response::new(&mut headers);
+/// res.parse_with(&config, b"SIP/2.0 200 OK\r\n\r\n").unwrap();
+/// assert!(res.parse_with(&config, b"SIP/2.0 2OO OK\r\n\r\n").is_err());
+/// assert_eq!(COUNTERS.messages.load(Ordering::Relaxed), 1);
+/// assert_eq!(COUNTERS.errors.load(Ordering::Relaxed), 1);
+/// ```
+pub trait Metrics: Sync {
+    /// A message head of `len` bytes with `headers` headers was parsed.
+    fn parsed(&self, len: usize, headers: usize) {
+        let _ = (len, headers);
+    }
+
+    /// Parsing failed with `error` after being handed `scanned` bytes.
+    fn error(&self, error: &Error, scanned: usize) {
+        let _ = (error, scanned);
+    }
+
+    /// The `scanned` bytes handed to the parser were not a complete message
+    /// head.
+    fn incomplete(&self, scanned: usize) {
+        let _ = scanned;
+    }
+}
+
+/// The optional `Metrics` of a `ParserConfig`.
+#[derive(Copy, Clone, Default)]
+struct MetricsHook(Option<&'static dyn Metrics>);
+
+impl MetricsHook {
+    /// Reports the outcome of parsing `buf` into a message with `headers`
+    /// headers.
+    #[inline]
+    fn report(&self, buf: &[u8], result: &Result<usize>, headers: usize) {
+        let metrics = match self.0 {
+            Some(metrics) => metrics,
+            None => return,
+        };
+        match *result {
+            Ok(Status::Complete(len)) => metrics.parsed(len, headers),
+            Ok(Status::Partial(_)) => metrics.incomplete(buf.len()),
+            Err(ref error) => metrics.error(error, buf.len()),
+        }
+    }
+}
+
+impl fmt::Debug for MetricsHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(_) => f.write_str("Some(Metrics)"),
+            None => f.write_str("None"),
+        }
+    }
+}
+
+impl PartialEq for MetricsHook {
+    fn eq(&self, other: &MetricsHook) -> bool {
+        match (self.0, other.0) {
+            (Some(a), Some(b)) => ptr::addr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
 }
 
+impl Eq for MetricsHook {}
+
 /// A parsed Request.
 ///
 /// The optional values will be `None` if a parse was not complete, and did not
@@ -49,7 +545,7 @@ pub enum Error {
 /// let mut headers = [parsip::EMPTY_HEADER; 16];
 /// let mut req = parsip::Request::new(&mut headers);
 /// let res = req.parse(buf);
-/// if let parsip::IResult::Incomplete(_) = res {
+/// if let Ok(parsip::Status::Partial(_)) = res {
 ///     match req.path {
 ///         Some(ref path) => {
 ///             // check router for path.
@@ -62,6 +558,7 @@ pub enum Error {
 /// }
 /// ```
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct Request<'headers, 'buf: 'headers> {
     /// The request method, such as `INVITE`.
     pub method: Option<&'buf str>,
@@ -71,6 +568,9 @@ pub struct Request<'headers, 'buf: 'headers> {
     pub version: Option<SipVersion>,
     /// The request headers.
     pub headers: &'headers mut [Header<'buf>],
+    /// The request body, set by `parse_message`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub body: Option<&'buf [u8]>,
 }
 
 impl<'h, 'b> Request<'h, 'b> {
@@ -81,44 +581,166 @@ impl<'h, 'b> Request<'h, 'b> {
             method: None,
             path: None,
             version: None,
-            headers: headers,
+            headers,
+            body: None,
         }
     }
 
+    /// Try to parse a buffer of bytes into this `Request`,
+    /// using the default `ParserConfig`.
+    ///
     /// > ```notrust
     /// > Request-Line  =  Method SP Request-URI SP SIP-Version CRLF
     /// > ```
-    // TODO: extract parse_request_line method when figure out how
-    pub fn parse(&mut self, buf: &'b [u8]) -> IResult<&'b [u8], usize> {
-        do_parse!(buf,
-            begin: rest_len >>
-            skip_empty_lines >>
-            map!(parse_method, |method| self.method = Some(method)) >> char!(' ') >>
-            map!(parse_request_uri, |path| self.path = Some(path)) >> char!(' ') >>
-            map!(parse_version, |version| self.version = Some(version)) >> crlf >>
-            headers_len: map!(call!(parse_headers, self.headers), |headers| headers.len()) >>
-            crlf >>
-            end: rest_len >>
-            ({
-                shrink(&mut self.headers, headers_len);
-                begin - end
-            })
-        )
+    #[inline]
+    pub fn parse(&mut self, buf: &'b [u8]) -> Result<usize> {
+        self.parse_with(&ParserConfig::default(), buf)
+    }
+
+    /// Try to parse a buffer of bytes into this `Request`,
+    /// using the given `ParserConfig`.
+    ///
+    /// Returns the length of the message head, up to and including the
+    /// empty line closing the header section.
+    pub fn parse_with(&mut self, config: &ParserConfig, buf: &'b [u8]) -> Result<usize> {
+        let result = self.parse_head(config, buf);
+        config.metrics.report(buf, &result, self.headers.len());
+        result
+    }
+
+    fn parse_head(&mut self, config: &ParserConfig, buf: &'b [u8]) -> Result<usize> {
+        let line = match self.parse_request_line(config, buf)? {
+            Status::Complete(line) => line,
+            Status::Partial(len) => return Ok(Status::Partial(len)),
+        };
+        let (len, headers) = match header_section(buf, &buf[line..], self.headers, config)? {
+            Status::Complete(section) => section,
+            Status::Partial(len) => return Ok(Status::Partial(len)),
+        };
+        shrink(&mut self.headers, headers);
+        Ok(Status::Complete(len))
+    }
+
+    /// Parses the empty lines and the Request-Line at the start of `buf`,
+    /// returning their length.
+    pub(crate) fn parse_request_line(&mut self, config: &ParserConfig, buf: &'b [u8])
+                                     -> Result<usize> {
+        let input = skip_empty_lines(buf);
+        check_line_len(buf, input, config.max_start_line_len)?;
+        let (input, method) = step!(buf, parse_method(input));
+        self.method = Some(method);
+        let (input, _) = step!(buf, space(input, ErrorKind::Token));
+        let (input, path) = step!(buf, parse_request_uri(input));
+        self.path = Some(path);
+        let (input, _) = step!(buf, space(input, ErrorKind::Token));
+        let (input, version) = step!(buf, parse_version(input, config));
+        self.version = Some(version);
+        let (input, _) = step!(buf, line_end(input, config));
+        Ok(Status::Complete(buf.len() - input.len()))
+    }
+
+    /// Try to parse a buffer of bytes into this `Request` along with its
+    /// body, using the default `ParserConfig`.
+    ///
+    /// See `parse_message_with`.
+    #[inline]
+    pub fn parse_message(&mut self, buf: &'b [u8]) -> Result<usize> {
+        self.parse_message_with(&ParserConfig::default(), buf)
+    }
+
+    /// Try to parse a buffer of bytes into this `Request` along with its
+    /// body, using the given `ParserConfig`.
+    ///
+    /// The body is as long as the `Content-Length` header says, and
+    /// `Status::Partial` gives the length of the whole message until it has
+    /// been received. Without a `Content-Length`, the body extends to the
+    /// end of `buf`, as for a datagram. Returns the length of the message.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use parsip::Status;
+    ///
+    /// let buf = b"MESSAGE sip:bob@biloxi.com SIP/2.0\r\nl: 5\r\n\r\nhello";
+    /// let mut headers = [parsip::EMPTY_HEADER; 4];
+    /// let mut req = parsip::Request::new(&mut headers);
+    ///
+    /// assert_eq!(req.parse_message(&buf[..46]), Ok(Status::Partial(Some(49))));
+    /// assert_eq!(req.parse_message(buf), Ok(Status::Complete(49)));
+    /// assert_eq!(req.body, Some(&b"hello"[..]));
+    /// ```
+    pub fn parse_message_with(&mut self, config: &ParserConfig, buf: &'b [u8]) -> Result<usize> {
+        let result = self.parse_with(config, buf);
+        with_body(buf, result, self.headers, &mut self.body)
+    }
+}
+
+/// Splits the body, as long as the `Content-Length` headers among
+/// `headers` say, off the bytes of `buf` following a parsed message head.
+fn with_body<'b>(buf: &'b [u8],
+                 result: Result<usize>,
+                 headers: &[Header<'b>],
+                 body: &mut Option<&'b [u8]>)
+                 -> Result<usize> {
+    let head = match result {
+        Ok(Status::Complete(head)) => head,
+        result => return result,
+    };
+    let rest = &buf[head..];
+    let values = headers.iter()
+        .filter(|header| names_eq(header.name, "Content-Length"))
+        .map(|header| header.value.as_bytes());
+    let len = match scan::content_length(values) {
+        Ok(Some(len)) => len,
+        Ok(None) => rest.len(),
+        Err(LengthError::Invalid) | Err(LengthError::Conflicting) => {
+            return Err(Error::new(ErrorKind::ContentLength, head));
+        }
+    };
+    if len > rest.len() {
+        return Ok(Status::Partial(Some(head.saturating_add(len))));
     }
+    *body = Some(&rest[..len]);
+    Ok(Status::Complete(head + len))
 }
 
-/// Helper that results in number of remaining bytes
-named!(#[inline], rest_len<usize>, map!(peek!(rest), |buf| buf.len()));
+/// Skips all `\r\n` or `\n` bytes preceding a start line.
+#[inline]
+fn skip_empty_lines(mut buf: &[u8]) -> &[u8] {
+    loop {
+        match buf {
+            [b'\r', b'\n', rest @ ..] | [b'\n', rest @ ..] => buf = rest,
+            _ => return buf,
+        }
+    }
+}
 
-/// Helper that skips all `\r\n` or `\n` bytes
-named!(#[inline], skip_empty_lines<()>,
-    fold_many0!(line_ending, (), |_, _| ())
-);
+/// Checks that the line starting at `input`, a suffix of `buf`, is no
+/// longer than `max`, looking no further than the limit.
+#[inline]
+pub(crate) fn check_line_len(buf: &[u8], input: &[u8], max: Option<usize>)
+                             -> result::Result<(), Error> {
+    let max = match max {
+        Some(max) => max,
+        None => return Ok(()),
+    };
+    let window = &input[..cmp::min(input.len(), max.saturating_add(2))];
+    let line = match memchr(b'\n', window) {
+        Some(lf) => &window[..lf],
+        None => window,
+    };
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    if line.len() > max {
+        return Err(Error::new(ErrorKind::TooLong, buf.len() - input.len() + max));
+    }
+    Ok(())
+}
 
 /// A parsed Response.
 ///
 /// See `Request` docs for explanation of optional values.
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct Response<'headers, 'buf: 'headers> {
     /// The response version, such as `SIP/2.0`.
     pub version: Option<SipVersion>,
@@ -128,6 +750,9 @@ pub struct Response<'headers, 'buf: 'headers> {
     pub reason: Option<&'buf str>,
     /// The response headers.
     pub headers: &'headers mut [Header<'buf>],
+    /// The response body, set by `parse_message`.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::ser::opt_text"))]
+    pub body: Option<&'buf [u8]>,
 }
 
 impl<'h, 'b> Response<'h, 'b> {
@@ -138,79 +763,674 @@ impl<'h, 'b> Response<'h, 'b> {
             version: None,
             code: None,
             reason: None,
-            headers: headers,
+            headers,
+            body: None,
         }
     }
 
-    /// Try to parse a buffer of bytes into this `Response`.
+    /// Decodes the escaped sequences of the reason-phrase into `buf`.
+    ///
+    /// Returns `None` if there is no reason-phrase, it contains a malformed
+    /// escape, `buf` is too small or the decoded bytes are not UTF-8.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut headers = [parsip::EMPTY_HEADER; 4];
+    /// let mut res = parsip::Response::new(&mut headers);
+    /// res.parse(b"SIP/2.0 404 Not%20Found\r\n\r\n").unwrap();
+    ///
+    /// let mut buf = [0u8; 32];
+    /// assert_eq!(res.decode_reason(&mut buf), Some("Not Found"));
+    /// ```
+    pub fn decode_reason<'d>(&self, buf: &'d mut [u8]) -> Option<&'d str> {
+        unescape_into(self.reason?.as_bytes(), buf).ok()
+    }
+
+    /// Try to parse a buffer of bytes into this `Response`,
+    /// using the default `ParserConfig`.
     ///
     /// > ```notrust
     /// > Status-Line     =  SIP-Version SP Status-Code SP Reason-Phrase CRLF
     /// > ```
-    // TODO: extract parse_status_line method when figure out how
-    pub fn parse(&mut self, buf: &'b [u8]) -> IResult<&'b [u8], usize> {
-        do_parse!(buf,
-            begin: rest_len >>
-            skip_empty_lines >>
-            map!(parse_version, |version| self.version = Some(version)) >> char!(' ') >>
-            map!(parse_code, |code| self.code = Some(code)) >> char!(' ') >>
-            map!(parse_reason, |reason| self.reason = Some(reason)) >> crlf >>
-            headers_len: map!(call!(parse_headers, self.headers), |headers| headers.len()) >>
-            crlf >>
-            end: rest_len >>
-            ({
-                shrink(&mut self.headers, headers_len);
-                begin - end
-            })
-        )
+    #[inline]
+    pub fn parse(&mut self, buf: &'b [u8]) -> Result<usize> {
+        self.parse_with(&ParserConfig::default(), buf)
     }
-}
 
-/// Represents a parsed header.
-#[derive(Copy, Clone, PartialEq, Debug)]
-pub struct Header<'a> {
-    /// The name portion of a header.
+    /// Try to parse a buffer of bytes into this `Response`,
+    /// using the given `ParserConfig`.
     ///
-    /// A header name must be valid US-ASCII, so it's safe to store as a `&str`.
-    pub name: &'a str,
-    /// The value portion of a header.
+    /// See `Request::parse_with`.
+    pub fn parse_with(&mut self, config: &ParserConfig, buf: &'b [u8]) -> Result<usize> {
+        let result = self.parse_head(config, buf);
+        config.metrics.report(buf, &result, self.headers.len());
+        result
+    }
+
+    fn parse_head(&mut self, config: &ParserConfig, buf: &'b [u8]) -> Result<usize> {
+        let line = match self.parse_status_line(config, buf)? {
+            Status::Complete(line) => line,
+            Status::Partial(len) => return Ok(Status::Partial(len)),
+        };
+        let (len, headers) = match header_section(buf, &buf[line..], self.headers, config)? {
+            Status::Complete(section) => section,
+            Status::Partial(len) => return Ok(Status::Partial(len)),
+        };
+        shrink(&mut self.headers, headers);
+        Ok(Status::Complete(len))
+    }
+
+    /// Parses the empty lines and the Status-Line at the start of `buf`,
+    /// returning their length.
+    pub(crate) fn parse_status_line(&mut self, config: &ParserConfig, buf: &'b [u8])
+                                    -> Result<usize> {
+        let input = skip_empty_lines(buf);
+        check_line_len(buf, input, config.max_start_line_len)?;
+        let (input, version) = step!(buf, parse_version(input, config));
+        self.version = Some(version);
+        let (input, _) = step!(buf, space(input, ErrorKind::Version));
+        let (input, code) = step!(buf, parse_code(input));
+        self.code = Some(code);
+        let (input, _) = step!(buf, space(input, ErrorKind::Status));
+        let (input, reason) = step!(buf, parse_reason(input));
+        self.reason = Some(reason);
+        let (input, _) = step!(buf, line_end(input, config));
+        Ok(Status::Complete(buf.len() - input.len()))
+    }
+
+    /// Try to parse a buffer of bytes into this `Response` along with its
+    /// body, using the default `ParserConfig`.
     ///
-    /// While headers **should** be US-ASCII, the specification allows for
-    /// values that may not be, and so the value is stored as bytes.
-    pub value: &'a [u8],
+    /// See `Request::parse_message_with`.
+    #[inline]
+    pub fn parse_message(&mut self, buf: &'b [u8]) -> Result<usize> {
+        self.parse_message_with(&ParserConfig::default(), buf)
+    }
+
+    /// Try to parse a buffer of bytes into this `Response` along with its
+    /// body, using the given `ParserConfig`.
+    ///
+    /// See `Request::parse_message_with`.
+    pub fn parse_message_with(&mut self, config: &ParserConfig, buf: &'b [u8]) -> Result<usize> {
+        let result = self.parse_with(config, buf);
+        with_body(buf, result, self.headers, &mut self.body)
+    }
 }
 
-/// An empty header, useful for constructing a `Header` array to pass in for
-/// parsing.
+/// A parsed Request or Response, for transports carrying both, such as
+/// UDP.
 ///
 /// # Example
 ///
 /// ```
-///# #![allow(unused_variables)]
-/// let headers = [parsip::EMPTY_HEADER; 64];
+/// use parsip::{Message, Status};
+///
+/// let mut headers = [parsip::EMPTY_HEADER; 4];
+/// match Message::parse(&mut headers, b"SIP/2.0 180 Ringing\r\n\r\n") {
+///     Ok(Status::Complete((Message::Response(res), _))) => assert_eq!(res.code, Some(180)),
+///     _ => unreachable!(),
+/// }
 /// ```
-pub const EMPTY_HEADER: Header<'static> = Header {
-    name: "",
-    value: b"",
-};
-
-/// SIP-Version
-/// ex. `SIP/2.0 -> SipVersion(2, 0)`
-#[derive(Copy, Clone, PartialEq, Debug)]
-pub struct SipVersion(pub u8, pub u8);
+#[derive(Debug, PartialEq)]
+pub enum Message<'headers, 'buf: 'headers> {
+    /// A request.
+    Request(Request<'headers, 'buf>),
+    /// A response.
+    Response(Response<'headers, 'buf>),
+}
 
-/// Get one digit from input and return it as `u8` (ie. `b'7'` becomes `7`)
-named!(#[inline], single_digit<&[u8], u8>,
-    map!(
-        flat_map!(take!(1), digit),
-        |a| a[0] - b'0'
-    )
-);
+impl<'h, 'b> Message<'h, 'b> {
+    /// Try to parse a buffer of bytes into a `Request` or `Response`,
+    /// using the default `ParserConfig`.
+    #[inline]
+    pub fn parse(headers: &'h mut [Header<'b>], buf: &'b [u8])
+                 -> Result<(Message<'h, 'b>, usize)> {
+        Message::parse_with(&ParserConfig::default(), headers, buf)
+    }
 
-/// Eats token bytes
-named!(#[inline], parse_token<&[u8], &str>,
-    map_res!(take_while1!(is_token), str::from_utf8)
-);
+    /// Try to parse a buffer of bytes into a `Request` or `Response`,
+    /// using the given `ParserConfig`.
+    ///
+    /// Status lines start with `SIP/`, which no method does. Until `buf`
+    /// holds enough of the start line to tell, the result is `Partial`
+    /// and nothing has been parsed.
+    pub fn parse_with(config: &ParserConfig, headers: &'h mut [Header<'b>], buf: &'b [u8])
+                      -> Result<(Message<'h, 'b>, usize)> {
+        Message::dispatch(config, headers, buf, false)
+    }
+
+    /// Try to parse a buffer of bytes into a `Request` or `Response` along
+    /// with its body, using the default `ParserConfig`.
+    #[inline]
+    pub fn parse_message(headers: &'h mut [Header<'b>], buf: &'b [u8])
+                         -> Result<(Message<'h, 'b>, usize)> {
+        Message::parse_message_with(&ParserConfig::default(), headers, buf)
+    }
+
+    /// Try to parse a buffer of bytes into a `Request` or `Response` along
+    /// with its body, using the given `ParserConfig`.
+    ///
+    /// See `parse_with` and `Request::parse_message_with`.
+    pub fn parse_message_with(config: &ParserConfig,
+                              headers: &'h mut [Header<'b>],
+                              buf: &'b [u8])
+                              -> Result<(Message<'h, 'b>, usize)> {
+        Message::dispatch(config, headers, buf, true)
+    }
+
+    fn dispatch(config: &ParserConfig, headers: &'h mut [Header<'b>], buf: &'b [u8], body: bool)
+                -> Result<(Message<'h, 'b>, usize)> {
+        let start = buf.iter().position(|&b| b != b'\r' && b != b'\n').unwrap_or(buf.len());
+        let prefix = &buf[start..cmp::min(buf.len(), start + 4)];
+        if !prefix.eq_ignore_ascii_case(&b"SIP/"[..prefix.len()]) {
+            let mut req = Request::new(headers);
+            let result = if body {
+                req.parse_message_with(config, buf)
+            } else {
+                req.parse_with(config, buf)
+            };
+            result.map(|status| status.map(move |len| (Message::Request(req), len)))
+        } else if prefix.len() == 4 {
+            let mut res = Response::new(headers);
+            let result = if body {
+                res.parse_message_with(config, buf)
+            } else {
+                res.parse_with(config, buf)
+            };
+            result.map(|status| status.map(move |len| (Message::Response(res), len)))
+        } else {
+            config.metrics.report(buf, &Ok(Status::Partial(None)), 0);
+            Ok(Status::Partial(None))
+        }
+    }
+}
+
+/// Uniform access to the parts shared by `Request` and `Response`.
+///
+/// Allows middleware such as loggers, authenticators or header filters to be
+/// written once for both kinds of messages.
+///
+/// # Example
+///
+/// ```
+/// use parsip::SipMessage;
+///
+/// fn call_id<'b, M: SipMessage<'b>>(msg: &M) -> Option<&'b [u8]> {
+///     msg.header("Call-ID")
+/// }
+///
+/// let buf = b"SIP/2.0 200 OK\r\nCall-ID: a84b4c76e66710\r\n\r\n";
+/// let mut headers = [parsip::EMPTY_HEADER; 4];
+/// let mut res = parsip::Response::new(&mut headers);
+/// res.parse(buf).unwrap();
+/// assert_eq!(call_id(&res), Some(&b"a84b4c76e66710"[..]));
+/// ```
+pub trait SipMessage<'b> {
+    /// The message version, such as `SIP/2.0`.
+    fn version(&self) -> Option<SipVersion>;
+
+    /// The message headers.
+    fn headers(&self) -> &[Header<'b>];
+
+    /// The message headers, mutably.
+    fn headers_mut(&mut self) -> &mut [Header<'b>];
+
+    /// Returns `true` if the message is a `Request`.
+    fn is_request(&self) -> bool;
+
+    /// Returns `true` if the message is a `Response`.
+    #[inline]
+    fn is_response(&self) -> bool {
+        !self.is_request()
+    }
+
+    /// Returns the value of the first header with a matching name.
+    ///
+    /// Header names are compared case-insensitively, and compact forms
+    /// match their long forms, see `headers::names_eq`.
+    fn header(&self, name: &str) -> Option<&'b [u8]> {
+        Headers::new(self.headers()).get(name)
+    }
+
+    /// Returns the values of all the headers with a matching name, see
+    /// `headers::Headers::get_all`.
+    #[inline]
+    fn header_all<'n>(&self, name: &'n str) -> GetAll<'_, 'b, 'n> {
+        Headers::new(self.headers()).get_all(name)
+    }
+
+    /// Returns the comma-separated elements of all the headers with a
+    /// matching name, see `headers::Headers::values`.
+    #[inline]
+    fn header_values<'n>(&self, name: &'n str) -> Values<'_, 'b, 'n> {
+        Headers::new(self.headers()).values(name)
+    }
+
+    /// Groups the message headers by name, see `headers::groups`.
+    #[inline]
+    fn header_groups(&self) -> Groups<'_, 'b> {
+        groups(self.headers())
+    }
+
+    /// Returns the Via hops and the Route and Record-Route entries of the
+    /// message, see `routing::Routing`.
+    #[inline]
+    fn routing(&self) -> Routing<'_, 'b> {
+        Routing::new(self.headers())
+    }
+}
+
+impl<'h, 'b> SipMessage<'b> for Request<'h, 'b> {
+    #[inline]
+    fn version(&self) -> Option<SipVersion> {
+        self.version
+    }
+
+    #[inline]
+    fn headers(&self) -> &[Header<'b>] {
+        self.headers
+    }
+
+    #[inline]
+    fn headers_mut(&mut self) -> &mut [Header<'b>] {
+        self.headers
+    }
+
+    #[inline]
+    fn is_request(&self) -> bool {
+        true
+    }
+}
+
+impl<'h, 'b> SipMessage<'b> for Response<'h, 'b> {
+    #[inline]
+    fn version(&self) -> Option<SipVersion> {
+        self.version
+    }
+
+    #[inline]
+    fn headers(&self) -> &[Header<'b>] {
+        self.headers
+    }
+
+    #[inline]
+    fn headers_mut(&mut self) -> &mut [Header<'b>] {
+        self.headers
+    }
+
+    #[inline]
+    fn is_request(&self) -> bool {
+        false
+    }
+}
+
+impl<'h, 'b> SipMessage<'b> for Message<'h, 'b> {
+    #[inline]
+    fn version(&self) -> Option<SipVersion> {
+        match *self {
+            Message::Request(ref req) => req.version,
+            Message::Response(ref res) => res.version,
+        }
+    }
+
+    #[inline]
+    fn headers(&self) -> &[Header<'b>] {
+        match *self {
+            Message::Request(ref req) => req.headers,
+            Message::Response(ref res) => res.headers,
+        }
+    }
+
+    #[inline]
+    fn headers_mut(&mut self) -> &mut [Header<'b>] {
+        match *self {
+            Message::Request(ref mut req) => req.headers,
+            Message::Response(ref mut res) => res.headers,
+        }
+    }
+
+    #[inline]
+    fn is_request(&self) -> bool {
+        matches!(*self, Message::Request(_))
+    }
+}
+
+/// Represents a parsed header.
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Header<'a> {
+    /// The name portion of a header.
+    ///
+    /// A header name must be valid US-ASCII, so it's safe to store as a `&str`.
+    pub name: &'a str,
+    /// The value portion of a header.
+    ///
+    /// While headers **should** be US-ASCII, the specification allows for
+    /// values that may not be, and so the value is stored as bytes.
+    pub value: HeaderValue<'a>,
+}
+
+impl<'a> Header<'a> {
+    /// Creates a header, usable in const contexts.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use parsip::{Header, HeaderValue};
+    ///
+    /// const SERVER: Header<'static> =
+    ///     Header::new("Server", HeaderValue::from_static(b"parsip"));
+    /// assert_eq!(SERVER.value, b"parsip");
+    /// ```
+    #[inline]
+    pub const fn new(name: &'a str, value: HeaderValue<'a>) -> Header<'a> {
+        Header { name, value }
+    }
+
+    /// The name in its canonical registered casing, with compact forms
+    /// expanded, see `headers::canonical_name`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use parsip::{Header, HeaderValue};
+    ///
+    /// let header = Header::new("v", HeaderValue::from_static(b"SIP/2.0/UDP a.example.com"));
+    /// assert_eq!(header.canonical_name(), "Via");
+    /// ```
+    #[inline]
+    pub fn canonical_name(&self) -> &'a str {
+        canonical_name(self.name)
+    }
+}
+
+/// An empty header, useful for constructing a `Header` array to pass in for
+/// parsing.
+///
+/// # Example
+///
+/// ```
+///# #![allow(unused_variables)]
+/// let headers = [parsip::EMPTY_HEADER; 64];
+/// ```
+pub const EMPTY_HEADER: Header<'static> = Header::new("", HeaderValue(b""));
+
+/// A header value, guaranteed to be legal header-value content.
+///
+/// It never contains a `CR` or `LF` other than as part of line folding, nor
+/// a `NUL` other than as part of a quoted-pair (`\` followed by `NUL`), so
+/// it can be written into a message as-is without allowing header injection.
+/// Values produced by the parser are always legal; values built from user
+/// data are checked by `HeaderValue::new`.
+///
+/// > ```notrust
+/// > header-value      =  *(TEXT-UTF8char / UTF8-CONT / LWS)
+/// > ```
+///
+/// # Example
+///
+/// ```
+/// use parsip::{ErrorKind, HeaderValue};
+///
+/// assert_eq!(HeaderValue::new(b"Alice <sip:alice@atlanta.com>").unwrap(),
+///            b"Alice <sip:alice@atlanta.com>");
+/// assert_eq!(HeaderValue::new(b"x\r\nVia: SIP/2.0/UDP evil.com"),
+///            Err(ErrorKind::HeaderValue));
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct HeaderValue<'a>(&'a [u8]);
+
+impl<'a> HeaderValue<'a> {
+    /// Checks that `bytes` are legal header-value content.
+    ///
+    /// Returns `ErrorKind::HeaderValue` otherwise.
+    pub const fn new(bytes: &'a [u8]) -> result::Result<HeaderValue<'a>, ErrorKind> {
+        if is_valid_header_value(bytes) {
+            Ok(HeaderValue(bytes))
+        } else {
+            Err(ErrorKind::HeaderValue)
+        }
+    }
+
+    /// Creates a `HeaderValue` from a static byte string.
+    ///
+    /// # Panics
+    ///
+    /// If `bytes` are not legal header-value content; in a const context
+    /// this fails the build instead.
+    pub const fn from_static(bytes: &'static [u8]) -> HeaderValue<'static> {
+        match HeaderValue::new(bytes) {
+            Ok(value) => value,
+            Err(_) => panic!("invalid header value"),
+        }
+    }
+
+    /// Wraps bytes already known to be legal header-value content.
+    #[inline]
+    pub(crate) fn from_parsed(bytes: &'a [u8]) -> HeaderValue<'a> {
+        debug_assert!(is_valid_header_value(bytes));
+        HeaderValue(bytes)
+    }
+
+    /// The raw bytes of this value.
+    #[inline]
+    pub const fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Iterates over the logical segments of the value: its lines, without
+    /// the whitespace around line folds, skipping blank ones.
+    ///
+    /// From [RFC 3261](https://tools.ietf.org/html/rfc3261#section-7.3.1),
+    /// a fold is equivalent to a single `SP`, so the unfolded value is the
+    /// segments separated by spaces.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use parsip::HeaderValue;
+    ///
+    /// let value = HeaderValue::new(b"first \r\n\tsecond\r\n \r\n third").unwrap();
+    /// let segments: Vec<&[u8]> = value.segments().collect();
+    /// assert_eq!(segments, [&b"first"[..], b"second", b"third"]);
+    /// ```
+    #[inline]
+    pub fn segments(&self) -> Segments<'a> {
+        Segments { rest: Some(self.0) }
+    }
+
+    /// Writes the value into `dst` with every fold replaced by a single
+    /// `SP`, returning the length written, or `None` if `dst` is too small.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use parsip::HeaderValue;
+    ///
+    /// let value = HeaderValue::new(b"newfangled value\r\n continued").unwrap();
+    /// let mut buf = [0u8; 32];
+    /// let len = value.unfold_into(&mut buf).unwrap();
+    /// assert_eq!(&buf[..len], b"newfangled value continued");
+    /// ```
+    pub fn unfold_into(&self, dst: &mut [u8]) -> Option<usize> {
+        let mut len = 0;
+        for byte in unfolded(self.0) {
+            *dst.get_mut(len)? = byte;
+            len += 1;
+        }
+        Some(len)
+    }
+
+    /// Compares the value to `other` once both are unfolded, without
+    /// allocating.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use parsip::HeaderValue;
+    ///
+    /// let value = HeaderValue::new(b"lunch\r\n  at noon").unwrap();
+    /// assert!(value.eq_normalized(b"lunch at noon"));
+    /// assert!(value.eq_normalized(b"lunch \r\n\tat noon"));
+    /// assert!(!value.eq_normalized(b"lunch  at noon"));
+    /// ```
+    pub fn eq_normalized(&self, other: &[u8]) -> bool {
+        unfolded(self.0).eq(unfolded(other))
+    }
+}
+
+/// The bytes of `value` with every fold replaced by a single `SP`.
+fn unfolded(value: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    let mut first = true;
+    Segments { rest: Some(value) }.flat_map(move |segment| {
+        let space = if first { None } else { Some(b' ') };
+        first = false;
+        space.into_iter().chain(segment.iter().cloned())
+    })
+}
+
+/// An iterator over the logical segments of a `HeaderValue`, see
+/// `HeaderValue::segments`.
+#[derive(Clone, Debug)]
+pub struct Segments<'a> {
+    rest: Option<&'a [u8]>,
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        loop {
+            let rest = self.rest?;
+            let line = match memchr(b'\n', rest) {
+                Some(lf) => {
+                    self.rest = Some(&rest[lf + 1..]);
+                    &rest[..lf]
+                }
+                None => {
+                    self.rest = None;
+                    rest
+                }
+            };
+            let line = scan::trim(line);
+            if !line.is_empty() {
+                return Some(line);
+            }
+        }
+    }
+}
+
+/// Checks every byte is a header value char, that `CR` and `LF` only
+/// appear as part of line folding and `NUL` only as part of a quoted-pair.
+const fn is_valid_header_value(bytes: &[u8]) -> bool {
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let next = if idx + 1 < bytes.len() { Some(bytes[idx + 1]) } else { None };
+        let valid = match bytes[idx] {
+            b'\r' => matches!(next, Some(b'\n')),
+            b'\n' => matches!(next, Some(b' ') | Some(b'\t')),
+            0 => idx > 0 && bytes[idx - 1] == b'\\',
+            b => is_header_value(b),
+        };
+        if !valid {
+            return false;
+        }
+        idx += 1;
+    }
+    true
+}
+
+impl<'a> fmt::Debug for HeaderValue<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "b\"{}\"", self.0.escape_ascii())
+    }
+}
+
+impl<'a> Deref for HeaderValue<'a> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl<'a> AsRef<[u8]> for HeaderValue<'a> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl<'a> From<HeaderValue<'a>> for &'a [u8] {
+    #[inline]
+    fn from(value: HeaderValue<'a>) -> &'a [u8] {
+        value.0
+    }
+}
+
+impl<'a> PartialEq<[u8]> for HeaderValue<'a> {
+    #[inline]
+    fn eq(&self, other: &[u8]) -> bool {
+        self.0 == other
+    }
+}
+
+impl<'a, 'b> PartialEq<&'b [u8]> for HeaderValue<'a> {
+    #[inline]
+    fn eq(&self, other: &&'b [u8]) -> bool {
+        self.0 == *other
+    }
+}
+
+impl<'a, const N: usize> PartialEq<[u8; N]> for HeaderValue<'a> {
+    #[inline]
+    fn eq(&self, other: &[u8; N]) -> bool {
+        self.0 == other
+    }
+}
+
+impl<'a, 'b, const N: usize> PartialEq<&'b [u8; N]> for HeaderValue<'a> {
+    #[inline]
+    fn eq(&self, other: &&'b [u8; N]) -> bool {
+        self.0 == *other
+    }
+}
+
+/// SIP-Version
+/// ex. `SIP/2.0 -> SipVersion(2, 0)`
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SipVersion(pub u8, pub u8);
+
+impl SipVersion {
+    /// `SIP/2.0`, the version defined by RFC 3261.
+    pub const SIP_2_0: SipVersion = SipVersion(2, 0);
+}
+
+/// Eats the bytes matching `pred`, at least one, reporting `kind` if
+/// there are none.
+#[inline]
+fn take_while1<F>(buf: &[u8], pred: F, kind: ErrorKind) -> Scan<'_, &[u8]>
+    where F: Fn(u8) -> bool
+{
+    match buf.iter().position(|&b| !pred(b)) {
+        Some(0) => Scan::Error(kind, buf),
+        Some(len) => Scan::Done(&buf[len..], &buf[..len]),
+        None => Scan::Incomplete,
+    }
+}
+
+/// Eats the `SP` separating the parts of a start line.
+#[inline]
+fn space(buf: &[u8], kind: ErrorKind) -> Scan<'_, ()> {
+    match buf {
+        [b' ', rest @ ..] => Scan::Done(rest, ()),
+        [] => Scan::Incomplete,
+        _ => Scan::Error(kind, buf),
+    }
+}
 
 /// > ```notrust
 /// > Method            =  INVITEm / ACKm / OPTIONSm / BYEm
@@ -218,12 +1438,16 @@ named!(#[inline], parse_token<&[u8], &str>,
 /// >                      / extension-method
 /// > extension-method  =  token
 /// > ```
-named!(#[inline], parse_method<&[u8], &str>,
-    call!(parse_token)
-);
+#[inline]
+fn parse_method(buf: &[u8]) -> Scan<'_, &str> {
+    // token chars are all ASCII
+    take_while1(buf, is_token, ErrorKind::Token).map(|method| unsafe {
+        str::from_utf8_unchecked(method)
+    })
+}
 
-/// As parsing uri is a bit complicated, it should be properly
-/// parsed in higher layers of parsing.
+/// Only the bytes of the Request-URI are checked here; `uri::SipUri`
+/// splits `sip:` and `sips:` URIs into their parts.
 ///
 /// > ```notrust
 /// > Request-URI    =  SIP-URI / SIPS-URI / absoluteURI
@@ -247,9 +1471,13 @@ named!(#[inline], parse_method<&[u8], &str>,
 /// >                     / alphanum *( alphanum / "-" ) alphanum
 /// > toplabel         =  ALPHA / ALPHA *( alphanum / "-" ) alphanum
 /// > ```
-named!(#[inline], parse_request_uri<&[u8], &str>,
-    map_res!(take_while1!(is_request_uri), str::from_utf8)
-);
+#[inline]
+fn parse_request_uri(buf: &[u8]) -> Scan<'_, &str> {
+    // Request-URI chars are all ASCII
+    take_while1(buf, is_request_uri, ErrorKind::Token).map(|uri| unsafe {
+        str::from_utf8_unchecked(uri)
+    })
+}
 
 /// From [RFC 3261](https://tools.ietf.org/html/rfc3261#section-7.1):
 ///
@@ -259,168 +1487,583 @@ named!(#[inline], parse_request_uri<&[u8], &str>,
 /// > ```notrust
 /// > SIP-Version    =  "SIP" "/" 1*DIGIT "." 1*DIGIT
 /// > ```
-named!(#[inline], parse_version<SipVersion>,
-    do_parse!(
-        tag_no_case!("SIP/") >>
-        x: single_digit >>
-        char!('.') >>
-        y: single_digit >>
-        ( SipVersion(x, y) )
-    )
-);
+///
+/// Version numbers which don't fit into `u8` are rejected with
+/// `ErrorKind::Version`, as is anything but `SIP/2.0` when
+/// `ParserConfig::strict_version` is set.
+fn parse_version<'a>(buf: &'a [u8], config: &ParserConfig) -> Scan<'a, SipVersion> {
+    let tag = cmp::min(buf.len(), 4);
+    if !buf[..tag].eq_ignore_ascii_case(&b"SIP/"[..tag]) {
+        return Scan::Error(ErrorKind::Version, buf);
+    }
+    if tag < 4 {
+        return Scan::Incomplete;
+    }
+    let (rest, x) = scan!(take_while1(&buf[4..], |b| b.is_ascii_digit(), ErrorKind::Version));
+    let rest = match rest {
+        [b'.', rest @ ..] => rest,
+        [] => return Scan::Incomplete,
+        _ => return Scan::Error(ErrorKind::Version, rest),
+    };
+    let (rest, y) = scan!(take_while1(rest, |b| b.is_ascii_digit(), ErrorKind::Version));
+    match (version_number(x), version_number(y)) {
+        (Some(x), Some(y)) if !config.strict_version || SipVersion(x, y) == SipVersion::SIP_2_0 => {
+            Scan::Done(rest, SipVersion(x, y))
+        }
+        _ => Scan::Error(ErrorKind::Version, buf),
+    }
+}
+
+/// Converts a string of ASCII digits into a number, checking for overflow.
+#[inline]
+fn version_number(digits: &[u8]) -> Option<u8> {
+    digits.iter().try_fold(0u8, |acc, &b| acc.checked_mul(10)?.checked_add(b - b'0'))
+}
+
+/// From [RFC 3261](https://tools.ietf.org/html/rfc3261):
+///
+/// > ```notrust
+/// > Reason-Phrase   =  *(reserved / unreserved / escaped
+/// >                    / UTF8-NONASCII / UTF8-CONT / SP / HTAB)
+/// > ```
+#[inline]
+fn parse_reason(buf: &[u8]) -> Scan<'_, &str> {
+    let len = match buf.iter().position(|&b| !is_reason_phrase(b)) {
+        Some(len) => len,
+        None => return Scan::Incomplete,
+    };
+    match str::from_utf8(&buf[..len]) {
+        Ok(reason) => Scan::Done(&buf[len..], reason),
+        Err(_) => Scan::Error(ErrorKind::Status, buf),
+    }
+}
+
+/// From [RFC 3261](https://tools.ietf.org/html/rfc3261):
+///
+/// > ```notrust
+/// > Status-Code     =  Informational
+/// >                /   Redirection
+/// >                /   Success
+/// >                /   Client-Error
+/// >                /   Server-Error
+/// >                /   Global-Failure
+/// >                /   extension-code
+/// > extension-code  =  3DIGIT
+/// > ```
+///
+/// Codes outside of `100..=699` are rejected with `ErrorKind::Status`.
+fn parse_code(buf: &[u8]) -> Scan<'_, u16> {
+    if buf.len() < 3 {
+        return Scan::Incomplete;
+    }
+    if !buf[..3].iter().all(u8::is_ascii_digit) {
+        return Scan::Error(ErrorKind::Status, buf);
+    }
+    let code = (buf[0] - b'0') as u16 * 100 + (buf[1] - b'0') as u16 * 10 +
+               (buf[2] - b'0') as u16;
+    if !(100..=699).contains(&code) {
+        return Scan::Error(ErrorKind::Status, buf);
+    }
+    Scan::Done(&buf[3..], code)
+}
+
+/// Eats the `CRLF` ending a start line, or a bare `LF` if the config
+/// allows it.
+#[inline]
+fn line_end<'a>(buf: &'a [u8], config: &ParserConfig) -> Scan<'a, ()> {
+    match buf {
+        [b'\r', b'\n', rest @ ..] => Scan::Done(rest, ()),
+        [b'\n', rest @ ..] if config.allow_lf_line_endings => Scan::Done(rest, ()),
+        [] | [b'\r'] => Scan::Incomplete,
+        _ => Scan::Error(ErrorKind::NewLine, buf),
+    }
+}
+
+/// From [RFC 3261](https://tools.ietf.org/html/rfc3261#section-7.3.1):
+///
+/// Header fields can be extended over multiple lines by preceding each
+/// extra line with at least one SP or horizontal tab (HT).
+///
+/// Header value may be empty!
+///
+fn header_value<'a>(buf: &'a [u8], config: &ParserConfig) -> Scan<'a, &'a [u8]> {
+    let mut end_pos = 0;
+    let mut line = 0;
+    let mut folds = 0;
+    loop {
+        let eol = memchr2(b'\r', b'\n', &buf[line..]).map_or(buf.len(), |pos| line + pos);
+        match scan_line(&buf[line..eol], config) {
+            Some(Some(len)) => end_pos = line + len,
+            Some(None) => {}
+            None => return header_error(ErrorKind::HeaderValue, buf),
+        }
+        if eol == buf.len() {
+            return Scan::Done(&b""[..], buf);
+        }
+        let lf = if buf[eol] == b'\r' {
+            match buf.get(eol + 1) {
+                Some(&b'\n') => eol + 1,
+                Some(_) => {
+                    return header_error(ErrorKind::HeaderValue, buf);
+                }
+                None => return Scan::Incomplete,
+            }
+        } else {
+            eol
+        };
+        match buf.get(lf + 1) {
+            // folded onto the next line
+            Some(&b' ') | Some(&b'\t') => {
+                folds += 1;
+                if config.max_continuation_lines.is_some_and(|max| folds > max) {
+                    return header_error(ErrorKind::TooLong, &buf[lf + 1..]);
+                }
+                line = lf + 2;
+            }
+            // leave the line terminator for the caller
+            Some(_) => return Scan::Done(&buf[eol..], &buf[..end_pos]),
+            None => return Scan::Incomplete,
+        }
+    }
+}
+
+/// Reports a malformed header section, kept out of the scanning loops.
+#[cold]
+#[inline(never)]
+fn header_error<T>(kind: ErrorKind, buf: &[u8]) -> Scan<'_, T> {
+    Scan::Error(kind, buf)
+}
+
+/// Validates a single line of a header value, which holds no `CR` nor `LF`.
+///
+/// Returns the length of the line without trailing whitespace (`None` for a
+/// blank line), or `None` if the line holds an illegal byte.
+#[inline]
+fn scan_line(line: &[u8], config: &ParserConfig) -> Option<Option<usize>> {
+    if config.ascii_header_values && !line.is_ascii() {
+        return None;
+    }
+    if !line.iter().all(|&b| is_header_value(b)) {
+        // a NUL is only allowed as part of a quoted-pair
+        let valid = line.iter().enumerate().all(|(idx, &b)| {
+            is_header_value(b) || (b == 0 && idx > 0 && line[idx - 1] == b'\\')
+        });
+        if !valid {
+            return None;
+        }
+    }
+    Some(line.iter().rposition(|&b| b != b' ' && b != b'\t').map(|pos| pos + 1))
+}
+
+/// Returns `true` for the headers present in essentially every message:
+/// Via, To, From, Call-ID, CSeq and Content-Length (including compact forms).
+#[inline]
+fn is_hot_header(name: &str) -> bool {
+    match name.len() {
+        1 => matches!(name.as_bytes()[0] | 0x20, b'v' | b't' | b'f' | b'i' | b'l'),
+        2 => name.eq_ignore_ascii_case("To"),
+        3 => name.eq_ignore_ascii_case("Via"),
+        4 => name.eq_ignore_ascii_case("From") || name.eq_ignore_ascii_case("CSeq"),
+        7 => name.eq_ignore_ascii_case("Call-ID"),
+        14 => name.eq_ignore_ascii_case("Content-Length"),
+        _ => false,
+    }
+}
+
+/// Fast scanner for the common shape of a header value: a single line
+/// terminated by `CRLF`, with no folding.
+///
+/// Returns `None` when the value does not have that shape, in which case
+/// it has to be scanned by `header_value`.
+#[inline]
+fn single_line_header_value<'a>(buf: &'a [u8],
+                               config: &ParserConfig)
+                               -> Option<(&'a [u8], &'a [u8])> {
+    let eol = memchr2(b'\r', b'\n', buf)?;
+    if buf[eol] != b'\r' {
+        return None;
+    }
+    match buf.get(eol + 1..eol + 3) {
+        Some(&[b'\n', next]) if next != b' ' && next != b'\t' => {}
+        _ => return None,
+    }
+    let len = scan_line(&buf[..eol], config)?;
+    Some((&buf[eol..], &buf[..len.unwrap_or(0)]))
+}
+
+/// Scans the value of the header called `name`, taking the fast path for
+/// the hottest headers.
+#[inline]
+fn message_header_value<'a>(buf: &'a [u8],
+                            name: &str,
+                            config: &ParserConfig)
+                            -> Scan<'a, &'a [u8]> {
+    if is_hot_header(name) {
+        if let Some((rest, value)) = single_line_header_value(buf, config) {
+            return Scan::Done(rest, value);
+        }
+    }
+    header_value(buf, config)
+}
+
+/// Scans a single header line, finding the name, colon, value and `CRLF`
+/// boundaries in one pass.
+///
+/// > ```notrust
+/// > header       =  "header-name" HCOLON header-value *(COMMA header-value)
+/// > header-name  =  token
+/// > HCOLON       =  *( SP / HTAB ) ":" SWS
+/// > ```
+#[inline]
+fn message_header<'a>(buf: &'a [u8], config: &ParserConfig) -> Scan<'a, Header<'a>> {
+    let name_end = buf.iter().position(|&b| !is_token(b)).unwrap_or(buf.len());
+    let mut idx = name_end;
+    while idx < buf.len() && matches!(buf[idx], b' ' | b'\t') {
+        idx += 1;
+    }
+    match buf.get(idx) {
+        Some(&b':') => idx += 1,
+        Some(_) => return header_error(ErrorKind::HeaderName, &buf[idx..]),
+        None => return Scan::Incomplete,
+    }
+    while idx < buf.len() && matches!(buf[idx], b' ' | b'\t') {
+        idx += 1;
+    }
+    // token chars are all ASCII
+    let name = unsafe { str::from_utf8_unchecked(&buf[..name_end]) };
+    let (rest, value) = scan!(message_header_value(&buf[idx..], name, config));
+    match rest {
+        [b'\r', b'\n', rest @ ..] => {
+            Scan::Done(rest, Header { name, value: HeaderValue::from_parsed(value) })
+        }
+        [b'\n', rest @ ..] if config.allow_lf_line_endings => {
+            Scan::Done(rest, Header { name, value: HeaderValue::from_parsed(value) })
+        }
+        [] | [b'\r'] => Scan::Incomplete,
+        _ => header_error(ErrorKind::NewLine, rest),
+    }
+}
+
+/// Parse a buffer of bytes as headers.
+///
+/// The return value, if complete and successful, includes the length of
+/// the header section, up to and including the empty line closing it, and
+/// a sliced reference to the parsed headers. The length of the slice will
+/// be equal to the number of properly parsed headers.
+///
+/// # Example
+///
+/// ```
+/// let buf = b"Host: foo.bar\r\nAccept: */*\r\n\r\nbody";
+/// let mut headers = [parsip::EMPTY_HEADER; 4];
+/// assert_eq!(parsip::parse_headers(buf, &mut headers),
+///            Ok(parsip::Status::Complete((30, &[
+///                parsip::Header { name: "Host",
+///                                 value: parsip::HeaderValue::from_static(b"foo.bar") },
+///                parsip::Header { name: "Accept",
+///                                 value: parsip::HeaderValue::from_static(b"*/*") }
+///            ][..]))));
+/// ```
+pub fn parse_headers<'b: 'h, 'h>(input: &'b [u8],
+                                 headers: &'h mut [Header<'b>])
+                                 -> Result<(usize, &'h [Header<'b>])> {
+    parse_headers_with(input, headers, &ParserConfig::default())
+}
+
+/// Parse a buffer of bytes as headers, using the given `ParserConfig`.
+///
+/// # Example
+///
+/// ```
+/// let buf = b"Subject: caf\xc3\xa9\r\n\r\n";
+/// let mut headers = [parsip::EMPTY_HEADER; 4];
+/// let mut config = parsip::ParserConfig::default();
+/// config.ascii_header_values(true);
+/// assert!(parsip::parse_headers_with(buf, &mut headers, &config).is_err());
+/// ```
+pub fn parse_headers_with<'b: 'h, 'h>(input: &'b [u8],
+                                      mut headers: &'h mut [Header<'b>],
+                                      config: &ParserConfig)
+                                      -> Result<(usize, &'h [Header<'b>])> {
+    let (len, count) = match header_section(input, input, headers, config)? {
+        Status::Complete(section) => section,
+        Status::Partial(len) => return Ok(Status::Partial(len)),
+    };
+    shrink(&mut headers, count);
+    Ok(Status::Complete((len, headers)))
+}
+
+/// Parses the header section starting at `input`, a suffix of `buf`, into
+/// `headers`.
+///
+/// Returns the offset in `buf` of the end of the empty line closing the
+/// section, and the number of headers.
+fn header_section<'b>(buf: &'b [u8],
+                      input: &'b [u8],
+                      headers: &mut [Header<'b>],
+                      config: &ParserConfig)
+                      -> Result<(usize, usize)> {
+    let mut iter = HeaderIter::new(buf, buf.len() - input.len(), config);
+    let mut count = 0;
+    loop {
+        match iter.section_end()? {
+            Status::Complete(true) => return Ok(Status::Complete((iter.pos, count))),
+            Status::Complete(false) => {}
+            Status::Partial(len) => return Ok(Status::Partial(len)),
+        }
+        if count == headers.len() {
+            return Err(Error::new(ErrorKind::TooManyHeaders, iter.pos));
+        }
+        headers[count] = match iter.header()? {
+            Status::Complete(header) => header,
+            Status::Partial(len) => return Ok(Status::Partial(len)),
+        };
+        count += 1;
+    }
+}
+
+/// Parse a buffer of bytes as headers, one at a time, without storage.
+///
+/// The iterator yields every header up to the empty line closing the
+/// header section, then `status` gives the length of the section. It
+/// stops early, with `status` still `Partial`, when the input ends first,
+/// and after yielding an error.
+///
+/// # Example
+///
+/// ```
+/// use parsip::Status;
+///
+/// let buf = b"Via: SIP/2.0/UDP a\r\nVia: SIP/2.0/UDP b\r\nCSeq: 1 BYE\r\n\r\n";
+///
+/// // size the storage before parsing into it
+/// let count = parsip::parse_headers_iter(buf).count();
+/// let mut headers = vec![parsip::EMPTY_HEADER; count];
+/// assert!(parsip::parse_headers(buf, &mut headers).unwrap().is_complete());
+///
+/// let mut iter = parsip::parse_headers_iter(&buf[..46]);
+/// assert_eq!(iter.next().unwrap().unwrap().value, b"SIP/2.0/UDP a");
+/// assert_eq!(iter.next().unwrap().unwrap().value, b"SIP/2.0/UDP b");
+/// assert!(iter.next().is_none());
+/// assert_eq!(iter.status(), Status::Partial(None));
+/// ```
+#[inline]
+pub fn parse_headers_iter(input: &[u8]) -> HeaderIter<'_> {
+    parse_headers_iter_with(input, &ParserConfig::default())
+}
+
+/// Parse a buffer of bytes as headers, one at a time, using the given
+/// `ParserConfig`.
+///
+/// See `parse_headers_iter`.
+#[inline]
+pub fn parse_headers_iter_with<'b>(input: &'b [u8], config: &ParserConfig) -> HeaderIter<'b> {
+    HeaderIter::new(input, 0, config)
+}
+
+/// An iterator over the headers of a buffer, created by
+/// `parse_headers_iter`.
+#[derive(Clone, Debug)]
+pub struct HeaderIter<'b> {
+    /// The input, up to the end of the header section limit.
+    buf: &'b [u8],
+    /// The offset of the next line.
+    pos: usize,
+    /// Whether `buf` stops at the header section limit.
+    truncated: bool,
+    /// The length of the section, once its closing line is scanned.
+    len: Option<usize>,
+    done: bool,
+    config: ParserConfig,
+}
+
+impl<'b> HeaderIter<'b> {
+    /// Creates an iterator over the header section starting at offset `pos`
+    /// of `buf`, reporting lengths and errors at their offset in `buf`.
+    #[inline]
+    fn new(buf: &'b [u8], pos: usize, config: &ParserConfig) -> HeaderIter<'b> {
+        HeaderIter::resume(buf, pos, pos, config)
+    }
+
+    /// Creates an iterator over the header section starting at offset
+    /// `start` of `buf`, resuming at the line starting at offset `pos`.
+    pub(crate) fn resume(buf: &'b [u8], start: usize, pos: usize, config: &ParserConfig)
+                         -> HeaderIter<'b> {
+        let (buf, truncated) = match config.max_header_section_len {
+            // scan no further than the limit
+            Some(max) if buf.len() - start > max => (&buf[..start + max], true),
+            _ => (buf, false),
+        };
+        HeaderIter { buf, pos, truncated, len: None, done: false, config: *config }
+    }
+
+    /// The offset of the next line.
+    #[inline]
+    pub(crate) fn offset(&self) -> usize {
+        self.pos
+    }
 
-/// From [RFC 3261](https://tools.ietf.org/html/rfc3261):
-///
-/// > ```notrust
-/// > Reason-Phrase   =  *(reserved / unreserved / escaped
-/// >                    / UTF8-NONASCII / UTF8-CONT / SP / HTAB)
-/// > ```
-named!(#[inline], parse_reason<&[u8], &str>,
-    map_res!(take_while!(is_reason_phrase), str::from_utf8)
-);
+    /// The length of the header section, up to and including the empty
+    /// line closing it, once the iterator has reached it.
+    #[inline]
+    pub fn status(&self) -> Status<usize> {
+        match self.len {
+            Some(len) => Status::Complete(len),
+            None => Status::Partial(None),
+        }
+    }
 
-// From [RFC 3261](https://tools.ietf.org/html/rfc3261):
-///
-/// > ```notrust
-/// > Status-Code     =  Informational
-/// >                /   Redirection
-/// >                /   Success
-/// >                /   Client-Error
-/// >                /   Server-Error
-/// >                /   Global-Failure
-/// >                /   extension-code
-/// > ```
-named!(#[inline], parse_code<&[u8], u16>,
-    map!(
-        flat_map!(take!(3), digit),
-        |arr| (arr[0] - b'0') as u16 * 100 + (arr[1] - b'0') as u16 * 10 +
-              (arr[2] - b'0') as u16
-    )
-);
+    /// Eats the empty line closing the section, if the next line is one.
+    pub(crate) fn section_end(&mut self) -> Result<bool> {
+        let len = match self.buf[self.pos..] {
+            [b'\r', b'\n', ..] => 2,
+            [b'\n', ..] if self.config.allow_lf_line_endings => 1,
+            [] | [b'\r'] => return self.partial(),
+            _ => return Ok(Status::Complete(false)),
+        };
+        self.pos += len;
+        self.len = Some(self.pos);
+        Ok(Status::Complete(true))
+    }
 
-/// > ```notrust
-/// > header-name       =  token
-/// > ```
-named!(#[inline], header_name<&[u8], &str>,
-    map_res!(
-        take_while!(is_token),
-        str::from_utf8
-    )
-);
+    /// Parses the header on the next line.
+    fn header(&mut self) -> Result<Header<'b>> {
+        let mut header = match self.raw_header()? {
+            Status::Complete(header) => header,
+            Status::Partial(len) => return Ok(Status::Partial(len)),
+        };
+        if self.config.expand_compact_headers {
+            header.name = expand_compact(header.name);
+        }
+        Ok(Status::Complete(header))
+    }
 
-/// From [RFC 3261](https://tools.ietf.org/html/rfc3261#section-7.3.1):
-///
-/// Header fields can be extended over multiple lines by preceding each
-/// extra line with at least one SP or horizontal tab (HT).
-///
-/// Header value may be empty!
-///
-fn header_value(buf: &[u8]) -> IResult<&[u8], &[u8]> {
-    use self::IResult::*;
+    /// Parses the header on the next line, with its name as written.
+    pub(crate) fn raw_header(&mut self) -> Result<Header<'b>> {
+        let (end, header) = match parse_header(self.buf, self.pos, &self.config)? {
+            Status::Complete(header) => header,
+            Status::Partial(_) => return self.partial(),
+        };
+        self.pos = end;
+        Ok(Status::Complete(header))
+    }
 
-    let mut end_pos = 0;
-    let mut idx = 0;
-    while idx < buf.len() {
-        match buf[idx] {
-            b'\n' => {
-                idx += 1;
-                if idx >= buf.len() {
-                    return Incomplete(Needed::Size(1));
-                }
-                match buf[idx] {
-                    b' ' | b'\t' => {
-                        idx += 1;
-                        continue;
-                    }
-                    _ => {
-                        return Done(&buf[end_pos..], &buf[..end_pos]);
-                    }
-                }
+    /// Reports that the input ends within the section.
+    #[inline]
+    fn partial<T>(&self) -> Result<T> {
+        if self.truncated {
+            Err(Error::new(ErrorKind::TooLong, self.buf.len()))
+        } else {
+            Ok(Status::Partial(None))
+        }
+    }
+}
+
+impl<'b> Iterator for HeaderIter<'b> {
+    type Item = result::Result<Header<'b>, Error>;
+
+    fn next(&mut self) -> Option<result::Result<Header<'b>, Error>> {
+        if self.done {
+            return None;
+        }
+        let result = match self.section_end() {
+            Ok(Status::Complete(false)) => self.header(),
+            // the closing line, or the end of the input
+            Ok(_) => Ok(Status::Partial(None)),
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(Status::Complete(header)) => Some(Ok(header)),
+            Ok(Status::Partial(_)) => {
+                self.done = true;
+                None
             }
-            b' ' | b'\t' | b'\r' => {}
-            b => {
-                if !is_header_value(b) {
-                    return Error(error_position!(ErrorKind::Custom(b as u32), buf));
-                }
-                end_pos = idx + 1;
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
             }
         }
-        idx += 1;
     }
-    Done(&b""[..], buf)
 }
 
-/// > ```notrust
-/// > HCOLON  =  *( SP / HTAB ) ":" SWS
-/// > ```
-named!(hcolon<char>, delimited!(
-    take_while!(is_space),
-    char!(':'),
-    take_while!(is_space)
-));
+impl<'b> FusedIterator for HeaderIter<'b> {}
 
-/// > ```notrust
-/// > header  =  "header-name" HCOLON header-value *(COMMA header-value)
-/// > ```
-named!(message_header<Header>, do_parse!(
-    n: header_name  >>
-    hcolon >>
-    v: header_value >>
-    crlf >>
-    (Header{ name: n, value: v })
-));
+/// Parses the header line starting at offset `pos` of `buf`, returning the
+/// offset of its end and the header, with its name as written.
+fn parse_header<'b>(buf: &'b [u8], pos: usize, config: &ParserConfig)
+                    -> Result<(usize, Header<'b>)> {
+    let (rest, header) = match message_header(&buf[pos..], config) {
+        Scan::Done(rest, header) => (rest, header),
+        Scan::Error(kind, at) => return Err(Error::new(kind, buf.len() - at.len())),
+        Scan::Incomplete => {
+            // the limits hold however the buffer ends
+            let (name, value) = partial_header(&buf[pos..]);
+            check_header_len(buf, pos, name, value, config)?;
+            return Ok(Status::Partial(None));
+        }
+    };
+    check_header_len(buf, pos, header.name.as_bytes(), header.value.as_bytes(), config)?;
+    Ok(Status::Complete((buf.len() - rest.len(), header)))
+}
 
-/// Parse a buffer of bytes as headers.
-///
-/// The return value, if complete and successful, includes the index of the
-/// buffer that parsing stopped at, and a sliced reference to the parsed
-/// headers. The length of the slice will be equal to the number of properly
-/// parsed headers.
-///
-/// # Example
-///
-/// ```
-/// let buf = b"Host: foo.bar\r\nAccept: */*\r\n\r\n";
-/// let mut headers = [parsip::EMPTY_HEADER; 4];
-/// assert_eq!(parsip::parse_headers(buf, &mut headers),
-///            parsip::IResult::Done(&buf[28..], &[
-///                parsip::Header { name: "Host", value: b"foo.bar" },
-///                parsip::Header { name: "Accept", value: b"*/*" }
-///            ][..]));
-/// ```
-pub fn parse_headers<'b: 'h, 'h>(mut input: &'b [u8],
-                                 mut headers: &'h mut [Header<'b>])
-                                 -> IResult<&'b [u8], &'h [Header<'b>]> {
-    use self::IResult::*;
-    let mut i = 0;
-    while i < headers.len() {
-        match crlf(input) {
-            Done(_, _) => break,
-            Error(_) => {}
-            Incomplete(e) => return Incomplete(e),
-        };
-        let (rest, header) = try_parse!(input, message_header);
-        headers[i] = header;
-        input = rest;
-        i += 1;
+/// Checks the name and value of the header at offset `pos` of `buf`
+/// against the limits of `config`.
+#[inline]
+fn check_header_len(buf: &[u8],
+                    pos: usize,
+                    name: &[u8],
+                    value: &[u8],
+                    config: &ParserConfig)
+                    -> result::Result<(), Error> {
+    if let Some(max) = config.max_header_name_len {
+        if name.len() > max {
+            return Err(Error::new(ErrorKind::TooLong, pos + max));
+        }
+    }
+    if let Some(max) = config.max_header_value_len {
+        if value.len() > max {
+            let start = value.as_ptr() as usize - buf.as_ptr() as usize;
+            return Err(Error::new(ErrorKind::TooLong, start + max));
+        }
     }
+    Ok(())
+}
 
-    shrink(&mut headers, i);
-    Done(input, headers)
+/// Splits the start of an incomplete header line into the part of its name
+/// and of its value received so far, the value without trailing whitespace.
+#[cold]
+fn partial_header(line: &[u8]) -> (&[u8], &[u8]) {
+    let name_end = line.iter().position(|&b| !is_token(b)).unwrap_or(line.len());
+    let rest = &line[name_end..];
+    let colon = rest.iter().position(|&b| b != b' ' && b != b'\t');
+    let value = match colon {
+        Some(colon) if rest[colon] == b':' => {
+            let value = &rest[colon + 1..];
+            let start = value.iter().position(|&b| b != b' ' && b != b'\t').unwrap_or(value.len());
+            let value = &value[start..];
+            let end = value.iter()
+                .rposition(|&b| !matches!(b, b' ' | b'\t' | b'\r' | b'\n'))
+                .map_or(0, |end| end + 1);
+            &value[..end]
+        }
+        _ => &rest[..0],
+    };
+    (&line[..name_end], value)
 }
 
 
 #[cfg(test)]
 mod tests {
-    use super::{IResult, ErrorKind, Needed};
-    use super::{Request, Response, EMPTY_HEADER, SipVersion};
+    use super::{Error, ErrorKind, Result, Scan, Status};
+    use super::{Request, Response, EMPTY_HEADER, SipVersion, SipMessage, ParserConfig};
+    use super::{Header, HeaderValue, Message, Metrics};
 
     const NUM_OF_HEADERS: usize = 4;
 
     macro_rules! req {
         ($name:ident, $buf:expr, |$arg:ident| $body:expr) => (
             req! {$name, $buf,
-                 |buf| IResult::Done(&buf[buf.len()..], buf.len()),
+                 |buf| Ok(Status::Complete(buf.len())),
                  |$arg| $body }
         );
         ($name:ident, $buf:expr,
@@ -434,7 +2077,7 @@ mod tests {
             assert_eq!(result, res_closure($buf));
             closure(req);
 
-            fn res_closure($res_arg: &[u8]) -> IResult<&[u8], usize> {
+            fn res_closure($res_arg: &[u8]) -> Result<usize> {
                 $res_body
             }
 
@@ -445,11 +2088,18 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_header_value_leading_lf() {
+        let buf = b"\nX: y\r\n\r\n";
+        assert_eq!(super::header_value(buf, &ParserConfig::default()),
+                   Scan::Done(&buf[0..], &buf[..0]));
+    }
+
     #[test]
     fn test_header_value_empty() {
         let buf = b"\r\nAccept: */*\r\n\r\n";
-        assert_eq!(super::header_value(buf),
-                   IResult::Done(&buf[0..], &buf[..0]));
+        assert_eq!(super::header_value(buf, &ParserConfig::default()),
+                   Scan::Done(&buf[0..], &buf[..0]));
     }
 
     req! {
@@ -494,6 +2144,110 @@ mod tests {
         }
     }
 
+    req! {
+        test_request_too_many_headers,
+        b"INVITE sip:callee@domain.com SIP/2.0\r\n\
+          A: A\r\n\
+          B: B\r\n\
+          C: C\r\n\
+          D: D\r\n\
+          E: E\r\n\
+          \r\n",
+        |_buf| Err(Error::new(ErrorKind::TooManyHeaders, 62)),
+        |_req| {}
+    }
+
+    #[test]
+    fn test_parse_headers() {
+        let buf = b"Host: foo.bar\r\nX: \x01\r\n\r\n";
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        assert_eq!(super::parse_headers(&buf[..15], &mut headers), Ok(Status::Partial(None)));
+        let err = super::parse_headers(buf, &mut headers).unwrap_err();
+        assert_eq!((err.kind(), err.offset()), (ErrorKind::HeaderValue, 18));
+        assert_eq!(err.to_string(), "invalid header value at byte 18");
+    }
+
+    #[test]
+    fn test_lf_line_endings() {
+        let mut config = ParserConfig::new();
+        config.allow_lf_line_endings(true);
+
+        let buf = b"SIP/2.0 403 Forbidden\nServer: foo.bar\r\nSubject: a\n b\n\nbody";
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        let mut res = Response::new(&mut headers);
+        assert_eq!(res.parse_with(&config, buf), Ok(Status::Complete(buf.len() - 4)));
+        assert_eq!(res.reason, Some("Forbidden"));
+        assert_eq!(res.headers.len(), 2);
+        assert_eq!(res.headers[0].value, b"foo.bar");
+        assert_eq!(res.headers[1].value, b"a\n b");
+
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        assert_eq!(super::parse_headers_with(b"To: a\n", &mut headers, &config),
+                   Ok(Status::Partial(None)));
+        let err = super::parse_headers_with(b"To: a\rb\n\n", &mut headers, &config);
+        assert_eq!(err.unwrap_err().kind(), ErrorKind::HeaderValue);
+    }
+
+    #[test]
+    fn test_limits() {
+        let buf = b"\r\nBYE sip:b SIP/2.0\r\nCall-ID: 1\r\nSubject: a\r\n b\r\n c\r\n\r\n";
+        let parse = |config: &ParserConfig| {
+            let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+            Request::new(&mut headers).parse_with(config, buf)
+        };
+        let mut config = ParserConfig::new();
+        config.max_start_line_len(Some(17))
+            .max_header_name_len(Some(7))
+            .max_header_value_len(Some(9))
+            .max_header_section_len(Some(34))
+            .max_continuation_lines(Some(2));
+        assert_eq!(parse(&config), Ok(Status::Complete(buf.len())));
+
+        let limited = |limit: fn(&mut ParserConfig)| {
+            let mut config = config;
+            limit(&mut config);
+            parse(&config).map_err(|err| (err.kind(), err.offset()))
+        };
+        let too_long = |offset| Err((ErrorKind::TooLong, offset));
+        assert_eq!(limited(|c| { c.max_start_line_len(Some(16)); }), too_long(18));
+        assert_eq!(limited(|c| { c.max_header_name_len(Some(6)); }), too_long(27));
+        assert_eq!(limited(|c| { c.max_header_value_len(Some(8)); }), too_long(50));
+        assert_eq!(limited(|c| { c.max_header_section_len(Some(33)); }), too_long(54));
+        assert_eq!(limited(|c| { c.max_continuation_lines(Some(1)); }), too_long(49));
+
+        // the limits hold however the buffer ends
+        config.max_start_line_len(Some(4));
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        let mut res = Response::new(&mut headers);
+        assert_eq!(res.parse_with(&config, b"SIP/2"), Err(Error::new(ErrorKind::TooLong, 4)));
+        assert_eq!(res.parse_with(&config, b"SIP/"), Ok(Status::Partial(None)));
+    }
+
+    #[test]
+    fn test_headers_iter() {
+        let buf = b"v: SIP/2.0/UDP a\r\nRoute: <sip:b>\r\nX-Bad\r\n\r\n";
+        let mut config = ParserConfig::new();
+        config.expand_compact_headers(true);
+        let mut iter = super::parse_headers_iter_with(buf, &config);
+        assert_eq!(iter.next().map(|header| header.unwrap().name), Some("Via"));
+        assert_eq!(iter.next().map(|header| header.unwrap().name), Some("Route"));
+        assert_eq!(iter.next(), Some(Err(Error::new(ErrorKind::HeaderName, 39))));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.status(), Status::Partial(None));
+
+        let mut iter = super::parse_headers_iter(&buf[..35]);
+        assert_eq!(iter.by_ref().count(), 2);
+        assert_eq!(iter.status(), Status::Partial(None));
+        let mut iter = super::parse_headers_iter(b"To: b\r\n\r\nbody");
+        assert_eq!(iter.by_ref().count(), 1);
+        assert_eq!(iter.status(), Status::Complete(9));
+
+        config.max_header_section_len(Some(20));
+        let mut iter = super::parse_headers_iter_with(buf, &config);
+        assert!(iter.next().unwrap().is_ok());
+        assert_eq!(iter.next(), Some(Err(Error::new(ErrorKind::TooLong, 20))));
+    }
+
     req! {
         test_request_multibyte,
         b"INVITE sip:callee@domain.com SIP/2.0\r\nHost: foo.com\r\n\
@@ -509,17 +2263,248 @@ mod tests {
         }
     }
 
+    req! {
+        test_request_header_value_folded_blank_line,
+        b"MESSAGE sip:bob@biloxi.com SIP/2.0\r\nSubject: first  \r\n second \t\r\n \r\nTo: b\r\n\r\n",
+        |req| {
+            assert_eq!(req.headers.len(), 2);
+            assert_eq!(req.headers[0].value, b"first  \r\n second");
+            assert!(req.headers[0].value.eq_normalized(b"first second"));
+            assert_eq!(req.headers[1].value, b"b");
+        }
+    }
+
+    req! {
+        test_request_header_value_nul,
+        b"INVITE sip:callee@domain.com SIP/2.0\r\nSubject: a\x00b\r\n\r\n",
+        |_buf| Err(Error::new(ErrorKind::HeaderValue, 47)),
+        |_req| {}
+    }
+
+    req! {
+        test_request_header_value_bare_cr,
+        b"INVITE sip:callee@domain.com SIP/2.0\r\nSubject: a\rb\r\n\r\n",
+        |_buf| Err(Error::new(ErrorKind::HeaderValue, 47)),
+        |_req| {}
+    }
+
+    const STATIC_HEADERS: [Header<'static>; 2] = [
+        Header::new("Max-Forwards", HeaderValue::from_static(b"70")),
+        Header::new("User-Agent", HeaderValue::from_static(b"parsip")),
+    ];
+
+    #[test]
+    fn test_const_headers() {
+        assert_eq!(STATIC_HEADERS[1].name, "User-Agent");
+        assert_eq!(STATIC_HEADERS[1].value, b"parsip");
+        assert_eq!(Header::new("", HeaderValue::from_static(b"")), EMPTY_HEADER);
+        const _: () = assert!(HeaderValue::new(b"a\rb").is_err());
+    }
+
+    #[test]
+    fn test_header_value_new() {
+        assert!(HeaderValue::new(b"").is_ok());
+        assert!(HeaderValue::new(b"folded\r\n value").is_ok());
+        assert!(HeaderValue::new(b"folded\n\tvalue").is_ok());
+        assert_eq!(HeaderValue::new(b"a\rb"), Err(ErrorKind::HeaderValue));
+        assert_eq!(HeaderValue::new(b"a\r\nb"), Err(ErrorKind::HeaderValue));
+        assert_eq!(HeaderValue::new(b"a\r\n"), Err(ErrorKind::HeaderValue));
+        assert_eq!(HeaderValue::new(b"a\x00b"), Err(ErrorKind::HeaderValue));
+        assert!(HeaderValue::new(b"\"NUL:\\\x00\"").is_ok());
+        assert_eq!(format!("{:?}", HeaderValue::from_static(b"a\r\n b")), "b\"a\\r\\n b\"");
+    }
+
+    #[test]
+    fn test_header_value_unfold() {
+        let value = HeaderValue::from_static(b"a \t\n b\r\n\t\r\n  c d");
+        assert_eq!(value.segments().collect::<Vec<_>>(), [&b"a"[..], b"b", b"c d"]);
+        let mut buf = [0u8; 7];
+        assert_eq!(value.unfold_into(&mut buf), Some(7));
+        assert_eq!(&buf, b"a b c d");
+        assert_eq!(value.unfold_into(&mut buf[..6]), None);
+        assert_eq!(HeaderValue::from_static(b"").segments().next(), None);
+        assert!(HeaderValue::from_static(b"").eq_normalized(b""));
+        assert!(!value.eq_normalized(b"a b c"));
+    }
+
     req! {
         test_request_partial,
         b"INVITE sip:callee@domain.com SIP/2.0\r\n\r",
-        |_buf| IResult::Incomplete(Needed::Size(40)),
+        |_buf| Ok(Status::Partial(None)),
+        |_req| {}
+    }
+
+    req! {
+        test_request_partial_version_mismatch,
+        b"INVITE sip:callee@domain.com H",
+        |_buf| Err(Error::new(ErrorKind::Version, 29)),
+        |req| {
+            assert_eq!(req.path, Some("sip:callee@domain.com"));
+            assert_eq!(req.version, None);
+        }
+    }
+
+    req! {
+        test_request_header_space_before_colon,
+        b"OPTIONS sip:carol@chicago.com SIP/2.0\r\nCSeq \t: 63104 OPTIONS\r\n\r\n",
+        |req| {
+            assert_eq!(req.headers.len(), 1);
+            assert_eq!(req.headers[0].name, "CSeq");
+            assert_eq!(req.headers[0].value, b"63104 OPTIONS");
+        }
+    }
+
+    req! {
+        test_request_header_missing_colon,
+        b"OPTIONS sip:carol@chicago.com SIP/2.0\r\nCSeq 63104 OPTIONS\r\n\r\n",
+        |_buf| Err(Error::new(ErrorKind::HeaderName, 44)),
+        |_req| {}
+    }
+
+    req! {
+        test_request_header_partial_name,
+        b"OPTIONS sip:carol@chicago.com SIP/2.0\r\nCSe",
+        |_buf| Ok(Status::Partial(None)),
         |_req| {}
     }
 
+    #[test]
+    fn test_request_multibyte_ascii_header_values() {
+        let buf = b"INVITE sip:callee@domain.com SIP/2.0\r\nHost: foo.com\r\n\
+                    User-Agent: \xe3\x81\xb2\xe3/1.0\r\n\r\n";
+        let mut config = ParserConfig::default();
+        config.ascii_header_values(true);
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        let mut req = Request::new(&mut headers);
+        assert_eq!(req.parse_with(&config, buf), Err(Error::new(ErrorKind::HeaderValue, 65)));
+    }
+
+    #[test]
+    fn test_request_hot_header_ascii_header_values() {
+        let buf = b"INVITE sip:callee@domain.com SIP/2.0\r\nTo: \xc3\xa9\r\n\r\n";
+        let mut config = ParserConfig::default();
+        config.ascii_header_values(true);
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        let mut req = Request::new(&mut headers);
+        assert!(req.parse_with(&config, buf).is_err());
+    }
+
+    #[test]
+    fn test_request_metrics() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Recorder(AtomicUsize, AtomicUsize, AtomicUsize);
+
+        impl Metrics for Recorder {
+            fn parsed(&self, len: usize, headers: usize) {
+                self.0.fetch_add(len * 10 + headers, Ordering::Relaxed);
+            }
+
+            fn incomplete(&self, scanned: usize) {
+                self.1.fetch_add(scanned, Ordering::Relaxed);
+            }
+
+            fn error(&self, _error: &Error, _scanned: usize) {
+                self.2.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        static RECORDER: Recorder = Recorder(AtomicUsize::new(0),
+                                             AtomicUsize::new(0),
+                                             AtomicUsize::new(0));
+        let mut config = ParserConfig::default();
+        config.metrics(&RECORDER);
+        assert_ne!(config, ParserConfig::default());
+
+        let buf = b"INVITE sip:callee@domain.com SIP/2.0\r\nHost: foo.com\r\n\r\nbody";
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        let mut req = Request::new(&mut headers);
+        assert!(req.parse_with(&config, buf).is_ok());
+        assert!(req.parse_with(&config, &buf[..20]).is_ok());
+        assert!(req.parse_with(&config, b"INVITE sip:callee@domain.com SIP/2.0\n\n").is_err());
+        assert_eq!(RECORDER.0.load(Ordering::Relaxed), 55 * 10 + 1);
+        assert_eq!(RECORDER.1.load(Ordering::Relaxed), 20);
+        assert_eq!(RECORDER.2.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_request_body() {
+        let buf = b"MESSAGE sip:a SIP/2.0\r\nContent-Length: 5\r\nl: 5\r\n\r\nhelloINFO";
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        let mut req = Request::new(&mut headers);
+        assert_eq!(req.parse_message(&buf[..50]), Ok(Status::Partial(Some(55))));
+        assert_eq!(req.body, None);
+        assert_eq!(req.parse_message(buf), Ok(Status::Complete(55)));
+        assert_eq!(req.body, Some(&b"hello"[..]));
+
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        let mut req = Request::new(&mut headers);
+        assert_eq!(req.parse_message(b"MESSAGE sip:a SIP/2.0\r\n\r\nhi"),
+                   Ok(Status::Complete(27)));
+        assert_eq!(req.body, Some(&b"hi"[..]));
+    }
+
+    #[test]
+    fn test_response_body_conflicting_length() {
+        let buf = b"SIP/2.0 200 OK\r\nContent-Length: 2, 3\r\n\r\nabc";
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        let mut res = Response::new(&mut headers);
+        assert_eq!(res.parse_message(buf), Err(Error::new(ErrorKind::ContentLength, 40)));
+        assert_eq!(res.body, None);
+    }
+
+    #[test]
+    fn test_expand_compact_headers() {
+        let buf = b"SIP/2.0 200 OK\r\nv: SIP/2.0/UDP a\r\nq: 1\r\nl: 0\r\n\r\n";
+        let mut config = ParserConfig::new();
+        config.expand_compact_headers(true);
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        let mut res = Response::new(&mut headers);
+        assert_eq!(res.parse_with(&config, buf), Ok(Status::Complete(buf.len())));
+        let names: Vec<&str> = res.headers.iter().map(|header| header.name).collect();
+        assert_eq!(names, ["Via", "q", "Content-Length"]);
+
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        let mut res = Response::new(&mut headers);
+        assert_eq!(res.parse(buf), Ok(Status::Complete(buf.len())));
+        assert_eq!(res.headers[0].name, "v");
+        assert_eq!(res.headers[0].canonical_name(), "Via");
+        assert_eq!(res.header("Content-Length"), Some(&b"0"[..]));
+    }
+
+    #[test]
+    fn test_message_dispatch() {
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        match Message::parse(&mut headers, b"\r\nSIP/2.0 200 OK\r\nCSeq: 1 BYE\r\n\r\n") {
+            Ok(Status::Complete((msg, 33))) => {
+                assert!(msg.is_response());
+                assert_eq!(msg.header("cseq"), Some(&b"1 BYE"[..]));
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        match Message::parse(&mut headers, b"SIPPING sip:a@b SIP/2.0\r\n\r\nbody") {
+            Ok(Status::Complete((Message::Request(req), 27))) => {
+                assert_eq!(req.method, Some("SIPPING"));
+                assert_eq!(req.body, None);
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        assert_eq!(Message::parse(&mut headers, b"\r\nSI"), Ok(Status::Partial(None)));
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        match Message::parse(&mut headers, b"SIP/2.0 ") {
+            Ok(Status::Partial(None)) => {}
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
     req! {
         test_request_newlines,
         b"INVITE sip:callee@domain.com SIP/2.0\nHost: foo.bar\n\n",
-        |_buf| IResult::Error(error_position!(ErrorKind::CrLf, &_buf[36..])),
+        |_buf| Err(Error::new(ErrorKind::NewLine, 36)),
         |_req| {}
     }
 
@@ -547,7 +2532,7 @@ mod tests {
     req! {
         test_request_with_invalid_token_delimiter,
         b"GET\n/ SIP/2.0\r\nHost: foo.bar\r\n\r\n",
-        |_buf| IResult::Error(error_position!(ErrorKind::Char, &_buf[3..])),
+        |_buf| Err(Error::new(ErrorKind::Token, 3)),
         |_req| {}
     }
 
@@ -564,10 +2549,41 @@ mod tests {
         }
     }
 
+    req! {
+        test_request_headers_trailing_whitespace,
+        b"INVITE sip:callee@domain.com SIP/2.0\r\n\
+          Call-ID: a84b4c76e66710 \t\r\n\
+          Subject: lunch \r\n\
+          \r\n",
+        |req| {
+            assert_eq!(req.headers.len(), 2);
+            assert_eq!(req.headers[0].value, b"a84b4c76e66710");
+            assert_eq!(req.headers[1].value, b"lunch");
+        }
+    }
+
+    req! {
+        test_request_hot_headers,
+        b"INVITE sip:callee@domain.com SIP/2.0\r\n\
+          v: SIP/2.0/UDP pc33.atlanta.com\r\n\
+          Via: SIP/2.0/UDP bigbox3.site3.atlanta.com\r\n ;branch=z9hG4bK77ef4c2312983.1\r\n\
+          CSeq: 314159 INVITE\r\n\
+          l: 0\r\n\
+          \r\n",
+        |req| {
+            assert_eq!(req.headers.len(), 4);
+            assert_eq!(req.headers[0].value, b"SIP/2.0/UDP pc33.atlanta.com");
+            assert_eq!(req.headers[1].value,
+                &b"SIP/2.0/UDP bigbox3.site3.atlanta.com\r\n ;branch=z9hG4bK77ef4c2312983.1"[..]);
+            assert_eq!(req.headers[2].value, b"314159 INVITE");
+            assert_eq!(req.headers[3].value, b"0");
+        }
+    }
+
     macro_rules! res {
         ($name:ident, $buf:expr, |$arg:ident| $body:expr) => (
             res! {$name, $buf,
-                 |buf| IResult::Done(&buf[buf.len()..], buf.len()),
+                 |buf| Ok(Status::Complete(buf.len())),
                  |$arg| $body }
         );
         ($name:ident, $buf:expr,
@@ -581,7 +2597,7 @@ mod tests {
             assert_eq!(result, res_closure($buf));
             closure(res);
 
-            fn res_closure($res_arg: &[u8]) -> IResult<&[u8], usize> {
+            fn res_closure($res_arg: &[u8]) -> Result<usize> {
                 $res_body
             }
 
@@ -605,7 +2621,7 @@ mod tests {
     res! {
         test_response_newlines,
         b"SIP/2.0 403 Forbidden\nServer: foo.bar\n\n",
-        |_buf| IResult::Error(error_position!(ErrorKind::CrLf, &_buf[21..])),
+        |_buf| Err(Error::new(ErrorKind::NewLine, 21)),
         |_res| {}
     }
 
@@ -622,7 +2638,7 @@ mod tests {
     res! {
         test_response_reason_missing_no_space,
         b"SIP/2.0 200\r\n\r\n",
-        |_buf| IResult::Error(error_position!(ErrorKind::Char, &_buf[11..])),
+        |_buf| Err(Error::new(ErrorKind::Status, 11)),
         |res| {
             assert_eq!(res.version.unwrap(), SipVersion(2,0));
             assert_eq!(res.code.unwrap(), 200);
@@ -639,39 +2655,148 @@ mod tests {
         }
     }
 
-    static RESPONSE_REASON_WITH_OBS_TEXT_BYTE: &'static [u8] = b"SIP/2.0 200 X\xFFZ\r\n\r\n";
+    res! {
+        test_response_code_out_of_range,
+        b"SIP/2.0 700 Whatever\r\n\r\n",
+        |_buf| Err(Error::new(ErrorKind::Status, 8)),
+        |res| {
+            assert_eq!(res.code, None);
+        }
+    }
+
+    res! {
+        test_response_code_too_low,
+        b"SIP/2.0 099 Whatever\r\n\r\n",
+        |_buf| Err(Error::new(ErrorKind::Status, 8)),
+        |_res| {}
+    }
+
+    res! {
+        test_response_code_highest,
+        b"SIP/2.0 699 Whatever\r\n\r\n",
+        |res| {
+            assert_eq!(res.code.unwrap(), 699);
+        }
+    }
+
+    res! {
+        test_response_code_short,
+        b"SIP/2.0 2 0 OK\r\n\r\n",
+        |_buf| Err(Error::new(ErrorKind::Status, 8)),
+        |_res| {}
+    }
+
+    res! {
+        test_response_version_multi_digit,
+        b"SIP/12.034 200 OK\r\n\r\n",
+        |res| {
+            assert_eq!(res.version.unwrap(), SipVersion(12, 34));
+        }
+    }
+
+    res! {
+        test_response_version_overflow,
+        b"SIP/256.0 200 OK\r\n\r\n",
+        |_buf| Err(Error::new(ErrorKind::Version, 0)),
+        |res| {
+            assert_eq!(res.version, None);
+        }
+    }
+
+    #[test]
+    fn test_response_version_strict() {
+        let mut config = ParserConfig::default();
+        config.strict_version(true);
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+
+        let buf = b"SIP/2.1 200 OK\r\n\r\n";
+        let mut res = Response::new(&mut headers);
+        assert_eq!(res.parse_with(&config, buf), Err(Error::new(ErrorKind::Version, 0)));
+
+        let buf = b"sip/2.0 200 OK\r\n\r\n";
+        let mut res = Response::new(&mut headers);
+        assert_eq!(res.parse_with(&config, buf), Ok(Status::Complete(buf.len())));
+    }
+
+    res! {
+        test_response_reason_escaped,
+        b"SIP/2.0 488 Not%20Acceptable%20%48ere\r\n\r\n",
+        |res| {
+            assert_eq!(res.reason.unwrap(), "Not%20Acceptable%20%48ere");
+            let mut buf = [0u8; 32];
+            assert_eq!(res.decode_reason(&mut buf), Some("Not Acceptable Here"));
+            assert_eq!(res.decode_reason(&mut buf[..4]), None);
+        }
+    }
+
+    res! {
+        test_response_reason_malformed_escape,
+        b"SIP/2.0 200 100%\r\n\r\n",
+        |res| {
+            assert_eq!(res.reason.unwrap(), "100%");
+            assert_eq!(res.decode_reason(&mut [0u8; 32]), None);
+        }
+    }
+
+    static RESPONSE_REASON_WITH_OBS_TEXT_BYTE: &[u8] = b"SIP/2.0 200 X\xFFZ\r\n\r\n";
     res! {
         test_response_reason_with_obsolete_text_byte,
         RESPONSE_REASON_WITH_OBS_TEXT_BYTE,
-        |_buf| IResult::Error(error_position!(ErrorKind::MapRes, &_buf[12..])),
+        |_buf| Err(Error::new(ErrorKind::Status, 12)),
         |_res| {}
     }
 
     res! {
         test_response_reason_with_nul_byte,
         b"SIP/2.0 200 \x00\r\n\r\n",
-        |_buf| IResult::Error(error_position!(ErrorKind::CrLf, &_buf[12..])),
+        |_buf| Err(Error::new(ErrorKind::NewLine, 12)),
         |_res| {}
     }
 
     res! {
         test_response_version_missing_space,
         b"SIP/2.0",
-        |_buf| IResult::Incomplete(Needed::Size(8)),
+        |_buf| Ok(Status::Partial(None)),
         |_res| {}
     }
 
     res! {
         test_response_code_missing_space,
         b"SIP/2.0 200",
-        |_buf| IResult::Incomplete(Needed::Size(12)),
+        |_buf| Ok(Status::Partial(None)),
         |_res| {}
     }
 
     res! {
         test_response_empty_lines_prefix_lf_only,
         b"\n\nSIP/2.0 200 OK\n\n",
-        |_buf| IResult::Error(error_position!(ErrorKind::CrLf, &_buf[16..])),
+        |_buf| Err(Error::new(ErrorKind::NewLine, 16)),
         |_res| {}
     }
+
+    fn describe<'b, M: SipMessage<'b>>(msg: &M) -> (bool, usize, Option<&'b [u8]>) {
+        (msg.is_request(), msg.headers().len(), msg.header("call-id"))
+    }
+
+    #[test]
+    fn test_sip_message_request() {
+        let buf = b"BYE sip:callee@domain.com SIP/2.0\r\nCall-ID: 1234\r\n\r\n";
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        let mut req = Request::new(&mut headers);
+        req.parse(buf).unwrap();
+        assert_eq!(req.version(), Some(SipVersion(2, 0)));
+        assert_eq!(describe(&req), (true, 1, Some(&b"1234"[..])));
+    }
+
+    #[test]
+    fn test_sip_message_response() {
+        let buf = b"SIP/2.0 180 Ringing\r\nTo: <sip:bob@biloxi.com>\r\n\r\n";
+        let mut headers = [EMPTY_HEADER; NUM_OF_HEADERS];
+        let mut res = Response::new(&mut headers);
+        res.parse(buf).unwrap();
+        assert!(res.is_response());
+        assert_eq!(describe(&res), (false, 1, None));
+        res.headers_mut()[0].value = HeaderValue::new(b"<sip:alice@atlanta.com>").unwrap();
+        assert_eq!(res.header("TO"), Some(&b"<sip:alice@atlanta.com>"[..]));
+    }
 }
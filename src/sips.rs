@@ -0,0 +1,169 @@
+//! Checking the SIPS consistency rules of requests.
+//!
+//! A request targeting a SIPS URI must be secured over every hop, see
+//! [RFC 5630](https://tools.ietf.org/html/rfc5630). TLS-only deployments
+//! can reject requests breaking these rules before processing them.
+
+use headers::names_eq;
+use scan;
+use sip::Request;
+use transport::resolve_via;
+
+/// A broken SIPS rule.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// A Contact URI is not a SIPS URI.
+    Contact,
+    /// A Record-Route URI is not a SIPS URI.
+    RecordRoute,
+    /// The top Via does not use a secure transport.
+    Transport,
+}
+
+const ALL: [Violation; 3] = [Violation::Contact, Violation::RecordRoute, Violation::Transport];
+
+/// The set of rules a request breaks.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Violations(u8);
+
+impl Violations {
+    /// Returns `true` if no rule is broken.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if `violation` is in the set.
+    #[inline]
+    pub fn contains(&self, violation: Violation) -> bool {
+        self.0 & (1 << violation as u8) != 0
+    }
+
+    /// The violations in the set.
+    pub fn iter(&self) -> impl Iterator<Item = Violation> {
+        let set = *self;
+        ALL.iter().cloned().filter(move |&violation| set.contains(violation))
+    }
+
+    fn insert(&mut self, violation: Violation) {
+        self.0 |= 1 << violation as u8;
+    }
+}
+
+/// Checks the SIPS rules of `req`.
+///
+/// When the Request-URI or the top Route is a SIPS URI, every Contact
+/// and Record-Route URI must be one as well, and the top Via must use
+/// TLS, TLS over SCTP or WebSockets over TLS. Other requests break no
+/// rule.
+///
+/// # Example
+///
+/// ```
+/// use parsip::sips::{check, Violation};
+///
+/// let buf = b"INVITE sips:bob@biloxi.com SIP/2.0\r\n\
+///             Via: SIP/2.0/TLS pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+///             Contact: <sip:alice@pc33.atlanta.com;transport=tls>\r\n\r\n";
+/// let mut headers = [parsip::EMPTY_HEADER; 8];
+/// let mut req = parsip::Request::new(&mut headers);
+/// req.parse(buf).unwrap();
+///
+/// let violations = check(&req);
+/// assert_eq!(violations.iter().collect::<Vec<_>>(), [Violation::Contact]);
+/// ```
+pub fn check(req: &Request) -> Violations {
+    let mut violations = Violations::default();
+    let top_route = req.headers
+        .iter()
+        .find(|header| names_eq(header.name, "Route"))
+        .map(|header| scan::split_addr(scan::split_first(&header.value).0).0);
+    if !req.path.is_some_and(|path| is_sips(path.as_bytes())) &&
+       !top_route.is_some_and(is_sips) {
+        return violations;
+    }
+
+    for header in req.headers.iter() {
+        let violation = if names_eq(header.name, "Contact") {
+            Violation::Contact
+        } else if names_eq(header.name, "Record-Route") {
+            Violation::RecordRoute
+        } else {
+            continue;
+        };
+        let mut rest = Some(header.value.as_bytes());
+        while let Some(value) = rest {
+            let (element, next) = scan::split_first(value);
+            rest = next;
+            let (uri, _) = scan::split_addr(element);
+            if uri != b"*" && !is_sips(uri) {
+                violations.insert(violation);
+            }
+        }
+    }
+
+    let secure = req.headers
+        .iter()
+        .find(|header| names_eq(header.name, "Via"))
+        .is_some_and(|via| is_secure_transport(scan::split_first(&via.value).0));
+    if !secure {
+        violations.insert(Violation::Transport);
+    }
+    violations
+}
+
+/// Returns `true` if `uri` has the `sips` scheme.
+fn is_sips(uri: &[u8]) -> bool {
+    uri.len() > 5 && uri[..5].eq_ignore_ascii_case(b"sips:")
+}
+
+/// Returns `true` if a Via element uses a secure transport.
+fn is_secure_transport(via: &[u8]) -> bool {
+    resolve_via(via).is_some_and(|target| target.transport.is_secure())
+}
+
+#[cfg(test)]
+mod tests {
+    use sip::{Request, EMPTY_HEADER};
+    use super::{check, Violation, Violations};
+
+    fn violations(buf: &[u8]) -> Violations {
+        let mut headers = [EMPTY_HEADER; 8];
+        let mut req = Request::new(&mut headers);
+        req.parse(buf).unwrap();
+        check(&req)
+    }
+
+    #[test]
+    fn test_sips_request() {
+        let set = violations(b"INVITE sips:bob@biloxi.com SIP/2.0\r\n\
+                               Via: SIP / 2.0 / tls p1.example.com;branch=z9hG4bK1\r\n\
+                               Via: SIP/2.0/UDP 10.0.0.1;branch=z9hG4bK2\r\n\
+                               Record-Route: <sips:p1.example.com;lr>\r\n\
+                               Contact: <SIPS:alice@10.0.0.1>\r\n\r\n");
+        assert!(set.is_empty());
+
+        let set = violations(b"INVITE sips:bob@biloxi.com SIP/2.0\r\n\
+                               Via: SIP/2.0/UDP 10.0.0.1;branch=z9hG4bK2\r\n\
+                               Record-Route: <sips:p2.example.com;lr>, <sip:p1.example.com;lr>\r\n\
+                               Contact: <sips:alice@10.0.0.1>\r\n\r\n");
+        assert_eq!(set.iter().collect::<Vec<_>>(),
+                   [Violation::RecordRoute, Violation::Transport]);
+    }
+
+    #[test]
+    fn test_sips_route() {
+        let set = violations(b"BYE sip:alice@10.0.0.1 SIP/2.0\r\n\
+                               Route: <sips:p1.example.com;lr>\r\n\
+                               Contact: sip:bob@192.0.2.4\r\n\r\n");
+        assert!(set.contains(Violation::Contact) && set.contains(Violation::Transport));
+    }
+
+    #[test]
+    fn test_sip_request() {
+        let set = violations(b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+                               Via: SIP/2.0/UDP 10.0.0.1;branch=z9hG4bK2\r\n\
+                               Contact: <sip:alice@10.0.0.1>\r\n\r\n");
+        assert!(set.is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+//! Response status codes.
+
+use std::fmt;
+use sip::Response;
+
+/// The class of a status code, given by its first digit.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StatusClass {
+    /// `1xx`, the request is being processed.
+    Provisional,
+    /// `2xx`, the request succeeded.
+    Success,
+    /// `3xx`, the request should be retried elsewhere.
+    Redirection,
+    /// `4xx`, the request failed at this server.
+    ClientError,
+    /// `5xx`, the server failed to process a valid request.
+    ServerError,
+    /// `6xx`, the request fails at any server.
+    GlobalFailure,
+}
+
+/// A status code in `100..=699`.
+///
+/// # Example
+///
+/// ```
+/// use parsip::{StatusClass, StatusCode};
+///
+/// let mut headers = [parsip::EMPTY_HEADER; 4];
+/// let mut res = parsip::Response::new(&mut headers);
+/// res.parse(b"SIP/2.0 486 Busy Here\r\n\r\n").unwrap();
+/// let code = res.status_code().unwrap();
+/// assert_eq!(code.class(), StatusClass::ClientError);
+/// assert!(code.is_final());
+/// assert_eq!(StatusCode::new(180).unwrap().canonical_reason(), Some("Ringing"));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StatusCode(u16);
+
+impl StatusCode {
+    /// Checks that `code` is in `100..=699`.
+    #[inline]
+    pub const fn new(code: u16) -> Option<StatusCode> {
+        if code >= 100 && code <= 699 {
+            Some(StatusCode(code))
+        } else {
+            None
+        }
+    }
+
+    /// The code as a number.
+    #[inline]
+    pub const fn as_u16(&self) -> u16 {
+        self.0
+    }
+
+    /// The class of the code.
+    pub fn class(&self) -> StatusClass {
+        match self.0 / 100 {
+            1 => StatusClass::Provisional,
+            2 => StatusClass::Success,
+            3 => StatusClass::Redirection,
+            4 => StatusClass::ClientError,
+            5 => StatusClass::ServerError,
+            _ => StatusClass::GlobalFailure,
+        }
+    }
+
+    /// Returns `true` for `1xx` codes.
+    #[inline]
+    pub fn is_provisional(&self) -> bool {
+        self.class() == StatusClass::Provisional
+    }
+
+    /// Returns `true` for final codes, `2xx` to `6xx`, which complete a
+    /// transaction.
+    #[inline]
+    pub fn is_final(&self) -> bool {
+        !self.is_provisional()
+    }
+
+    /// Returns `true` for `2xx` codes.
+    #[inline]
+    pub fn is_success(&self) -> bool {
+        self.class() == StatusClass::Success
+    }
+
+    /// Returns `true` for `3xx` codes.
+    #[inline]
+    pub fn is_redirect(&self) -> bool {
+        self.class() == StatusClass::Redirection
+    }
+
+    /// Returns `true` for `4xx` codes.
+    #[inline]
+    pub fn is_client_error(&self) -> bool {
+        self.class() == StatusClass::ClientError
+    }
+
+    /// Returns `true` for `5xx` codes.
+    #[inline]
+    pub fn is_server_error(&self) -> bool {
+        self.class() == StatusClass::ServerError
+    }
+
+    /// Returns `true` for `6xx` codes.
+    #[inline]
+    pub fn is_global_failure(&self) -> bool {
+        self.class() == StatusClass::GlobalFailure
+    }
+
+    /// The reason-phrase registered for the code in the
+    /// [IANA registry](https://www.iana.org/assignments/sip-parameters),
+    /// if any.
+    pub fn canonical_reason(&self) -> Option<&'static str> {
+        Some(match self.0 {
+            100 => "Trying",
+            180 => "Ringing",
+            181 => "Call Is Being Forwarded",
+            182 => "Queued",
+            183 => "Session Progress",
+            199 => "Early Dialog Terminated",
+            200 => "OK",
+            202 => "Accepted",
+            204 => "No Notification",
+            300 => "Multiple Choices",
+            301 => "Moved Permanently",
+            302 => "Moved Temporarily",
+            305 => "Use Proxy",
+            380 => "Alternative Service",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            402 => "Payment Required",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            406 => "Not Acceptable",
+            407 => "Proxy Authentication Required",
+            408 => "Request Timeout",
+            410 => "Gone",
+            412 => "Conditional Request Failed",
+            413 => "Request Entity Too Large",
+            414 => "Request-URI Too Long",
+            415 => "Unsupported Media Type",
+            416 => "Unsupported URI Scheme",
+            417 => "Unknown Resource-Priority",
+            420 => "Bad Extension",
+            421 => "Extension Required",
+            422 => "Session Interval Too Small",
+            423 => "Interval Too Brief",
+            424 => "Bad Location Information",
+            428 => "Use Identity Header",
+            429 => "Provide Referrer Identity",
+            430 => "Flow Failed",
+            433 => "Anonymity Disallowed",
+            436 => "Bad Identity-Info",
+            437 => "Unsupported Certificate",
+            438 => "Invalid Identity Header",
+            439 => "First Hop Lacks Outbound Support",
+            440 => "Max-Breadth Exceeded",
+            469 => "Bad Info Package",
+            470 => "Consent Needed",
+            480 => "Temporarily Unavailable",
+            481 => "Call/Transaction Does Not Exist",
+            482 => "Loop Detected",
+            483 => "Too Many Hops",
+            484 => "Address Incomplete",
+            485 => "Ambiguous",
+            486 => "Busy Here",
+            487 => "Request Terminated",
+            488 => "Not Acceptable Here",
+            489 => "Bad Event",
+            491 => "Request Pending",
+            493 => "Undecipherable",
+            494 => "Security Agreement Required",
+            500 => "Server Internal Error",
+            501 => "Not Implemented",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            504 => "Server Time-out",
+            505 => "Version Not Supported",
+            513 => "Message Too Large",
+            555 => "Push Notification Service Not Supported",
+            580 => "Precondition Failure",
+            600 => "Busy Everywhere",
+            603 => "Decline",
+            604 => "Does Not Exist Anywhere",
+            606 => "Not Acceptable",
+            607 => "Unwanted",
+            608 => "Rejected",
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl<'h, 'b> Response<'h, 'b> {
+    /// The status code of the response, once parsed.
+    #[inline]
+    pub fn status_code(&self) -> Option<StatusCode> {
+        self.code.and_then(StatusCode::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StatusClass, StatusCode};
+
+    #[test]
+    fn test_classes() {
+        assert_eq!(StatusCode::new(99), None);
+        assert_eq!(StatusCode::new(700), None);
+        let code = StatusCode::new(199).unwrap();
+        assert!(code.is_provisional() && !code.is_final());
+        assert!(StatusCode::new(202).unwrap().is_success());
+        assert!(StatusCode::new(380).unwrap().is_redirect());
+        assert!(StatusCode::new(499).unwrap().is_client_error());
+        assert!(StatusCode::new(503).unwrap().is_server_error());
+        assert_eq!(StatusCode::new(699).unwrap().class(), StatusClass::GlobalFailure);
+    }
+
+    #[test]
+    fn test_canonical_reason() {
+        assert_eq!(StatusCode::new(491).unwrap().canonical_reason(), Some("Request Pending"));
+        assert_eq!(StatusCode::new(299).unwrap().canonical_reason(), None);
+        assert_eq!(StatusCode::new(603).unwrap().to_string(), "603");
+    }
+}
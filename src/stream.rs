@@ -0,0 +1,261 @@
+//! Receiving messages from an `AsyncRead` source.
+//!
+//! `MessageStream` turns any `futures_io::AsyncRead`, such as a TCP or TLS
+//! connection, into a `futures_core::Stream` of `OwnedMessage`s. Framing,
+//! keepalives and partial reads are handled internally.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use futures_core::Stream;
+use futures_io::AsyncRead;
+use memchr::memchr_iter;
+use framing::{self, Frame};
+use owned::OwnedMessage;
+use sip::{Message, Status, EMPTY_HEADER};
+
+pub use framing::ReadError;
+
+/// Size of the receive buffer a `MessageStream` starts with.
+const INITIAL_CAPACITY: usize = 4096;
+
+/// A stream of the messages read from an `AsyncRead` source.
+///
+/// The stream ends when the source does between two messages; parse errors
+/// skip the offending message, while other errors end the stream.
+///
+/// # Example
+///
+/// ```
+/// # extern crate futures_core;
+/// # extern crate parsip;
+/// use std::pin::Pin;
+/// use std::task::{Context, Poll, Waker};
+/// use futures_core::Stream;
+/// use parsip::stream::MessageStream;
+///
+/// # fn main() {
+/// let source: &[u8] = b"SIP/2.0 200 OK\r\nContent-Length: 2\r\n\r\nhi\r\n\r\n";
+/// let mut stream = MessageStream::new(source);
+/// let mut cx = Context::from_waker(Waker::noop());
+///
+/// match Pin::new(&mut stream).poll_next(&mut cx) {
+///     Poll::Ready(Some(Ok(msg))) => {
+///         assert_eq!(msg.code(), Some(200));
+///         assert_eq!(msg.body(), b"hi");
+///     }
+///     _ => unreachable!(),
+/// }
+/// assert!(matches!(Pin::new(&mut stream).poll_next(&mut cx), Poll::Ready(None)));
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MessageStream<R> {
+    reader: R,
+    buf: Vec<u8>,
+    start: usize,
+    end: usize,
+    max_len: usize,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> MessageStream<R> {
+    /// Creates a stream accepting messages of up to 64 KiB.
+    #[inline]
+    pub fn new(reader: R) -> MessageStream<R> {
+        MessageStream::with_max_len(reader, 64 * 1024)
+    }
+
+    /// Creates a stream accepting messages of up to `max_len` bytes.
+    pub fn with_max_len(reader: R, max_len: usize) -> MessageStream<R> {
+        MessageStream {
+            reader,
+            buf: vec![0; INITIAL_CAPACITY.min(max_len)],
+            start: 0,
+            end: 0,
+            max_len,
+            done: false,
+        }
+    }
+
+    /// Returns the underlying source.
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn poll_message(&mut self,
+                    cx: &mut Context)
+                    -> Poll<Option<Result<OwnedMessage, ReadError<io::Error>>>> {
+        loop {
+            self.start += framing::keepalive_len(&self.buf[self.start..self.end]);
+            match framing::frame(&self.buf[self.start..self.end]) {
+                Frame::Complete(len) => {
+                    let msg = &self.buf[self.start..self.start + len];
+                    self.start += len;
+                    return Poll::Ready(Some(parse(msg)));
+                }
+                Frame::Invalid => return Poll::Ready(Some(Err(ReadError::ContentLength))),
+                Frame::Conflicting => {
+                    return Poll::Ready(Some(Err(ReadError::ConflictingContentLength)));
+                }
+                Frame::Partial => {}
+            }
+            if self.start > 0 {
+                self.buf.copy_within(self.start..self.end, 0);
+                self.end -= self.start;
+                self.start = 0;
+            }
+            if self.end == self.buf.len() {
+                if self.buf.len() >= self.max_len {
+                    return Poll::Ready(Some(Err(ReadError::BufferFull)));
+                }
+                let len = (self.buf.len() * 2).min(self.max_len);
+                self.buf.resize(len, 0);
+            }
+            let read = Pin::new(&mut self.reader).poll_read(cx, &mut self.buf[self.end..]);
+            match read {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(0)) if self.start == self.end => return Poll::Ready(None),
+                Poll::Ready(Ok(0)) => return Poll::Ready(Some(Err(ReadError::Closed))),
+                Poll::Ready(Ok(n)) => self.end += n,
+                Poll::Ready(Err(ref e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(ReadError::Io(e)))),
+            }
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for MessageStream<R> {
+    type Item = Result<OwnedMessage, ReadError<io::Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let next = this.poll_message(cx);
+        if let Poll::Ready(Some(Err(ref e))) = next {
+            match *e {
+                ReadError::Parse(_) => {}
+                _ => this.done = true,
+            }
+        }
+        next
+    }
+}
+
+/// Parses a complete message, head and body.
+fn parse(msg: &[u8]) -> Result<OwnedMessage, ReadError<io::Error>> {
+    // every header takes at least one line
+    let mut headers = vec![EMPTY_HEADER; memchr_iter(b'\n', msg).count()];
+    match Message::parse(&mut headers, msg) {
+        Ok(Status::Complete((Message::Request(req), head))) => {
+            Ok(OwnedMessage::from(&req).with_body(&msg[head..]))
+        }
+        Ok(Status::Complete((Message::Response(res), head))) => {
+            Ok(OwnedMessage::from(&res).with_body(&msg[head..]))
+        }
+        Ok(Status::Partial(_)) => Err(ReadError::Parse(framing::truncated(msg))),
+        Err(e) => Err(ReadError::Parse(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+    use futures_core::Stream;
+    use futures_io::AsyncRead;
+    use super::{MessageStream, ReadError};
+
+    /// Hands out its bytes one at a time, with a pending read in between.
+    struct Trickle<'a> {
+        buf: &'a [u8],
+        ready: bool,
+    }
+
+    impl<'a> AsyncRead for Trickle<'a> {
+        fn poll_read(mut self: Pin<&mut Self>,
+                     cx: &mut Context,
+                     buf: &mut [u8])
+                     -> Poll<io::Result<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            match self.buf.split_first() {
+                Some((&b, rest)) => {
+                    buf[0] = b;
+                    self.buf = rest;
+                    Poll::Ready(Ok(1))
+                }
+                None => Poll::Ready(Ok(0)),
+            }
+        }
+    }
+
+    fn next<S: Stream + Unpin>(stream: &mut S) -> Option<S::Item> {
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(item) = Pin::new(&mut *stream).poll_next(&mut cx) {
+                return item;
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream_in_pieces() {
+        let source = b"\r\n\r\nOPTIONS sip:carol@chicago.com SIP/2.0\r\nCSeq: 1 OPTIONS\r\n\r\n\
+                       \r\n\
+                       SIP/2.0 200 OK\r\nl: 5\r\n\r\nhello";
+        let mut stream = MessageStream::new(Trickle { buf: source, ready: false });
+
+        let msg = next(&mut stream).unwrap().unwrap();
+        assert_eq!(msg.method(), Some("OPTIONS"));
+        assert_eq!(msg.header("cseq"), Some(&b"1 OPTIONS"[..]));
+        assert_eq!(msg.body(), b"");
+
+        let msg = next(&mut stream).unwrap().unwrap();
+        assert_eq!(msg.code(), Some(200));
+        assert_eq!(msg.body(), b"hello");
+
+        assert!(next(&mut stream).is_none());
+    }
+
+    #[test]
+    fn test_stream_skips_unparsable_message() {
+        let source: &[u8] = b"SIP/2.0 99 Low\r\n\r\nSIP/2.0 180 Ringing\r\n\r\n";
+        let mut stream = MessageStream::new(source);
+        match next(&mut stream) {
+            Some(Err(ReadError::Parse(_))) => {}
+            item => panic!("unexpected {:?}", item),
+        }
+        assert_eq!(next(&mut stream).unwrap().unwrap().code(), Some(180));
+        assert!(next(&mut stream).is_none());
+    }
+
+    #[test]
+    fn test_stream_closed_mid_message() {
+        let source: &[u8] = b"SIP/2.0 200 OK\r\nContent-Length: 10\r\n\r\nhello";
+        let mut stream = MessageStream::new(source);
+        match next(&mut stream) {
+            Some(Err(ReadError::Closed)) => {}
+            item => panic!("unexpected {:?}", item),
+        }
+        assert!(next(&mut stream).is_none());
+    }
+
+    #[test]
+    fn test_stream_message_too_large() {
+        let mut source = b"SIP/2.0 200 OK\r\nContent-Length: 100\r\n\r\n".to_vec();
+        source.resize(source.len() + 100, b'x');
+        let mut stream = MessageStream::with_max_len(&source[..], 64);
+        match next(&mut stream) {
+            Some(Err(ReadError::BufferFull)) => {}
+            item => panic!("unexpected {:?}", item),
+        }
+    }
+}
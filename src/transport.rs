@@ -0,0 +1,218 @@
+//! Resolving the transport and port to reach a URI or answer a Via.
+//!
+//! [RFC 3261](https://tools.ietf.org/html/rfc3261#section-19.1.2) and
+//! [RFC 3263](https://tools.ietf.org/html/rfc3263#section-4.1) define which
+//! transport and port apply when a URI or Via leaves them out. These
+//! helpers apply those rules without any DNS lookup, as done for numeric
+//! hosts, or hosts with an explicit port.
+
+use headers::typed::Via;
+use scan;
+
+/// A transport protocol carrying SIP messages.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Transport {
+    /// UDP.
+    Udp,
+    /// TCP.
+    Tcp,
+    /// TLS over TCP.
+    Tls,
+    /// SCTP.
+    Sctp,
+    /// TLS over SCTP.
+    TlsSctp,
+    /// WebSocket, see [RFC 7118](https://tools.ietf.org/html/rfc7118).
+    Ws,
+    /// Secure WebSocket.
+    Wss,
+}
+
+impl Transport {
+    /// Looks up a transport by its name in a Via or `transport` parameter,
+    /// ignoring case.
+    pub fn from_name(name: &[u8]) -> Option<Transport> {
+        let transports = [Transport::Udp,
+                          Transport::Tcp,
+                          Transport::Tls,
+                          Transport::Sctp,
+                          Transport::TlsSctp,
+                          Transport::Ws,
+                          Transport::Wss];
+        transports.iter().cloned().find(|transport| {
+            name.eq_ignore_ascii_case(transport.as_str().as_bytes())
+        })
+    }
+
+    /// The name of the transport, as written in a Via.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Transport::Udp => "UDP",
+            Transport::Tcp => "TCP",
+            Transport::Tls => "TLS",
+            Transport::Sctp => "SCTP",
+            Transport::TlsSctp => "TLS-SCTP",
+            Transport::Ws => "WS",
+            Transport::Wss => "WSS",
+        }
+    }
+
+    /// Returns `true` if the transport is secured with TLS.
+    #[inline]
+    pub fn is_secure(&self) -> bool {
+        matches!(*self, Transport::Tls | Transport::TlsSctp | Transport::Wss)
+    }
+
+    /// Returns `true` if the transport is a stream, framing messages by
+    /// their `Content-Length`.
+    #[inline]
+    pub fn is_stream(&self) -> bool {
+        !matches!(*self, Transport::Udp)
+    }
+
+    /// The port used when none is given: 5061 for secure transports, 5060
+    /// otherwise.
+    #[inline]
+    pub fn default_port(&self) -> u16 {
+        if self.is_secure() { 5061 } else { 5060 }
+    }
+}
+
+/// Where to send a message: a transport and a port.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Target {
+    /// The transport.
+    pub transport: Transport,
+    /// The port, explicit or the default of the transport.
+    pub port: u16,
+}
+
+/// Resolves the transport and port to reach a `sip` or `sips` URI.
+///
+/// The `transport` parameter wins, otherwise `sips` URIs use TLS and
+/// `sip` URIs UDP. Returns `None` for other schemes, unknown transports,
+/// a `transport` parameter that is not secure on a `sips` URI, or a
+/// malformed port.
+///
+/// # Example
+///
+/// ```
+/// use parsip::transport::{resolve_uri, Target, Transport};
+///
+/// assert_eq!(resolve_uri(b"sips:bob@biloxi.com"),
+///            Some(Target { transport: Transport::Tls, port: 5061 }));
+/// assert_eq!(resolve_uri(b"sip:bob@[2001:db8::1]:5080;transport=tcp"),
+///            Some(Target { transport: Transport::Tcp, port: 5080 }));
+/// ```
+pub fn resolve_uri(uri: &[u8]) -> Option<Target> {
+    let colon = uri.iter().position(|&b| b == b':')?;
+    let (scheme, rest) = (&uri[..colon], &uri[colon + 1..]);
+    let secure = if scheme.eq_ignore_ascii_case(b"sips") {
+        true
+    } else if scheme.eq_ignore_ascii_case(b"sip") {
+        false
+    } else {
+        return None;
+    };
+    // the user may contain `;` and `?`, but nothing after it may hold an `@`
+    let rest = match rest.iter().position(|&b| b == b'@') {
+        Some(at) => &rest[at + 1..],
+        None => rest,
+    };
+    // URI headers come after parameters
+    let rest = rest.split(|&b| b == b'?').next().unwrap_or(rest);
+    let semi = rest.iter().position(|&b| b == b';').unwrap_or(rest.len());
+    let (hostport, params) = rest.split_at(semi);
+
+    let transport = match scan::param(params, b"transport") {
+        Some(name) => Transport::from_name(name)?,
+        None if secure => Transport::Tls,
+        None => Transport::Udp,
+    };
+    if secure && !transport.is_secure() && transport != Transport::Tcp {
+        return None;
+    }
+    // `sips:` with `transport=tcp` means TLS over TCP
+    let transport = if secure && transport == Transport::Tcp { Transport::Tls } else { transport };
+    let port = match port(hostport)? {
+        Some(port) => port,
+        None => transport.default_port(),
+    };
+    Some(Target { transport, port })
+}
+
+/// Resolves the transport and port of the sent-by of a Via element, where
+/// responses go when it carries no `received` or `rport` parameter.
+///
+/// # Example
+///
+/// ```
+/// use parsip::transport::{resolve_via, Target, Transport};
+///
+/// assert_eq!(resolve_via(b"SIP/2.0/TLS pc33.atlanta.com;branch=z9hG4bK776asdhds"),
+///            Some(Target { transport: Transport::Tls, port: 5061 }));
+/// ```
+pub fn resolve_via(via: &[u8]) -> Option<Target> {
+    let via = Via::parse(via).ok()?;
+    let transport = Transport::from_name(via.transport)?;
+    let port = via.port.unwrap_or_else(|| transport.default_port());
+    Some(Target { transport, port })
+}
+
+/// The port of a hostport, `Some(None)` if there is none, and `None` if it
+/// is malformed.
+fn port(hostport: &[u8]) -> Option<Option<u16>> {
+    // skip the colons of an IPv6 reference
+    let start = hostport.iter().position(|&b| b == b']').unwrap_or(0);
+    let port = match hostport[start..].iter().position(|&b| b == b':') {
+        Some(colon) => scan::trim(&hostport[start + colon + 1..]),
+        None => return Some(None),
+    };
+    if port.is_empty() || port.len() > 5 || !port.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let port = port.iter().fold(0u32, |acc, &b| acc * 10 + (b - b'0') as u32);
+    if port > u16::MAX as u32 {
+        return None;
+    }
+    Some(Some(port as u16))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_uri, resolve_via, Target, Transport};
+
+    #[test]
+    fn test_resolve_uri() {
+        let target = |transport, port| Some(Target { transport, port });
+        assert_eq!(resolve_uri(b"sip:bob@biloxi.com"), target(Transport::Udp, 5060));
+        assert_eq!(resolve_uri(b"SIP:biloxi.com:5070"), target(Transport::Udp, 5070));
+        assert_eq!(resolve_uri(b"sip:bob:p:w@biloxi.com;transport=TLS"),
+                   target(Transport::Tls, 5061));
+        assert_eq!(resolve_uri(b"sips:bob@biloxi.com;transport=tcp"), target(Transport::Tls, 5061));
+        assert_eq!(resolve_uri(b"sip:[2001:db8::1];transport=ws?x=1:2"),
+                   target(Transport::Ws, 5060));
+        assert_eq!(resolve_uri(b"SIP:+1-212-555-1212;npdi:p%40ss@192.0.2.4:5080;\
+                                 maddr=[2001:db8::2];user=phone?to=alice%40atlanta.com&x"),
+                   target(Transport::Udp, 5080));
+        assert_eq!(resolve_uri(b"sip:alice;transport=tcp@192.0.2.4"),
+                   target(Transport::Udp, 5060));
+        assert_eq!(resolve_uri(b"sips:bob@biloxi.com;transport=udp"), None);
+        assert_eq!(resolve_uri(b"sip:bob@biloxi.com;transport=carrier-pigeon"), None);
+        assert_eq!(resolve_uri(b"sip:biloxi.com:65536"), None);
+        assert_eq!(resolve_uri(b"tel:+15555550100"), None);
+    }
+
+    #[test]
+    fn test_resolve_via() {
+        let target = |transport, port| Some(Target { transport, port });
+        assert_eq!(resolve_via(b"SIP/2.0/UDP 192.0.2.1;rport"), target(Transport::Udp, 5060));
+        assert_eq!(resolve_via(b"SIP/2.0/tcp [2001:db8::9]:5080 ;branch=z9hG4bK1"),
+                   target(Transport::Tcp, 5080));
+        assert_eq!(resolve_via(b"SIP/2.0/WSS df7jal23ls0d.invalid;branch=z9hG4bK56sdasks"),
+                   target(Transport::Wss, 5061));
+        assert_eq!(resolve_via(b"SIP / 2.0 / TLS pc33.atlanta.com:5062"),
+                   target(Transport::Tls, 5062));
+        assert_eq!(resolve_via(b"SIP/2.0/UDP"), None);
+    }
+}
@@ -0,0 +1,651 @@
+//! Zero-copy parsing of SIP, SIPS and telephone URIs.
+//!
+//! `Uri::parse` tells the schemes apart; `SipUri` and `TelUri` parse the
+//! URIs of a known scheme.
+//!
+//! > ```notrust
+//! > SIP-URI          =  "sip:" [ userinfo ] hostport
+//! >                     uri-parameters [ headers ]
+//! > SIPS-URI         =  "sips:" [ userinfo ] hostport
+//! >                     uri-parameters [ headers ]
+//! > userinfo         =  ( user / telephone-subscriber ) [ ":" password ] "@"
+//! > hostport         =  host [ ":" port ]
+//! > host             =  hostname / IPv4address / IPv6reference
+//! > uri-parameters   =  *( ";" uri-parameter)
+//! > headers          =  "?" header *( "&" header )
+//! > ```
+//!
+//! # Example
+//!
+//! ```
+//! use parsip::uri::Uri;
+//!
+//! match Uri::parse("tel:+1-201-555-0123").unwrap() {
+//!     Uri::Sip(uri) | Uri::Sips(uri) => println!("route to {:?}", uri.host),
+//!     Uri::Tel(tel) => assert!(tel.global),
+//!     Uri::Other(uri) => println!("unsupported {}", uri),
+//! }
+//! ```
+
+use std::str;
+use escape::{unescape_bytes_into, unescape_into, EscapeError};
+
+/// Errors in parsing a URI.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UriError {
+    /// The scheme is missing, malformed, or not the one expected.
+    Scheme,
+    /// Invalid byte in the user or password.
+    UserInfo,
+    /// The host is missing or malformed.
+    Host,
+    /// The port is not a number up to 65535.
+    Port,
+    /// Invalid byte in the parameters.
+    Params,
+    /// Invalid byte in the headers.
+    Headers,
+    /// A `%` is not followed by two hexadecimal digits.
+    Escape,
+    /// The number of a `tel` URI, or a number in its parameters, is
+    /// malformed.
+    Number,
+    /// A local `tel` number has no valid `phone-context` parameter.
+    PhoneContext,
+}
+
+/// A URI of any scheme, such as a Request-URI or the address of a From
+/// header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Uri<'a> {
+    /// A `sip:` URI.
+    Sip(SipUri<'a>),
+    /// A `sips:` URI.
+    Sips(SipUri<'a>),
+    /// A `tel:` URI.
+    Tel(TelUri<'a>),
+    /// A URI of another scheme, such as `urn:` or `mailto:`, unchecked
+    /// beyond its scheme.
+    Other(&'a str),
+}
+
+impl<'a> Uri<'a> {
+    /// Parses a URI, checking it as far as its scheme is known.
+    pub fn parse(uri: &'a str) -> Result<Uri<'a>, UriError> {
+        let colon = uri.find(':').ok_or(UriError::Scheme)?;
+        match &uri[..colon] {
+            scheme if scheme.eq_ignore_ascii_case("sip") => SipUri::parse(uri).map(Uri::Sip),
+            scheme if scheme.eq_ignore_ascii_case("sips") => SipUri::parse(uri).map(Uri::Sips),
+            scheme if scheme.eq_ignore_ascii_case("tel") => TelUri::parse(uri).map(Uri::Tel),
+            // scheme  =  ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )
+            scheme if scheme.starts_with(|c: char| c.is_ascii_alphabetic()) &&
+                      scheme.bytes().all(|b| b.is_ascii_alphanumeric() || b"+-.".contains(&b)) => {
+                Ok(Uri::Other(uri))
+            }
+            _ => Err(UriError::Scheme),
+        }
+    }
+}
+
+/// The scheme of a `SipUri`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Scheme {
+    /// `sip`.
+    Sip,
+    /// `sips`.
+    Sips,
+}
+
+/// The host of a `SipUri`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Host<'a> {
+    /// A domain name.
+    Domain(&'a str),
+    /// An IPv4 address.
+    Ipv4(&'a str),
+    /// An IPv6 address, without the brackets of the reference.
+    Ipv6(&'a str),
+}
+
+/// A SIP or SIPS URI, as slices of the parsed string.
+///
+/// The user, password, parameters and headers are kept escaped; the
+/// parser only checks that escaped sequences are well-formed.
+///
+/// # Example
+///
+/// ```
+/// use parsip::uri::{Host, Scheme, SipUri};
+///
+/// let uri = SipUri::parse("sips:alice:secret@[2001:db8::1]:5061;transport=tcp;lr?subject=x")
+///     .unwrap();
+/// assert_eq!(uri.scheme, Scheme::Sips);
+/// assert_eq!(uri.user, Some("alice"));
+/// assert_eq!(uri.password, Some("secret"));
+/// assert_eq!(uri.host, Host::Ipv6("2001:db8::1"));
+/// assert_eq!(uri.port, Some(5061));
+/// assert_eq!(uri.param("transport"), Some("tcp"));
+/// assert_eq!(uri.param("lr"), Some(""));
+/// assert_eq!(uri.headers().collect::<Vec<_>>(), [("subject", "x")]);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SipUri<'a> {
+    /// The scheme.
+    pub scheme: Scheme,
+    /// The user, such as `alice`.
+    pub user: Option<&'a str>,
+    /// The password following the user.
+    pub password: Option<&'a str>,
+    /// The host.
+    pub host: Host<'a>,
+    /// The port, if explicit.
+    pub port: Option<u16>,
+    /// The parameters with their leading `;`, such as `;transport=tcp;lr`.
+    pub params: &'a str,
+    /// The headers without the leading `?`, such as `subject=x&priority=urgent`.
+    pub headers: &'a str,
+}
+
+impl<'a> SipUri<'a> {
+    /// Parses a `sip:` or `sips:` URI, such as a `Request.path`.
+    pub fn parse(uri: &'a str) -> Result<SipUri<'a>, UriError> {
+        let colon = uri.find(':').ok_or(UriError::Scheme)?;
+        let scheme = match &uri[..colon] {
+            scheme if scheme.eq_ignore_ascii_case("sip") => Scheme::Sip,
+            scheme if scheme.eq_ignore_ascii_case("sips") => Scheme::Sips,
+            _ => return Err(UriError::Scheme),
+        };
+        let mut rest = &uri[colon + 1..];
+
+        // the user may contain `;` and `?`, but nothing after it may hold an `@`
+        let (mut user, mut password) = (None, None);
+        if let Some(at) = rest.find('@') {
+            let userinfo = &rest[..at];
+            let (name, secret) = match userinfo.find(':') {
+                Some(colon) => (&userinfo[..colon], Some(&userinfo[colon + 1..])),
+                None => (userinfo, None),
+            };
+            if name.is_empty() {
+                return Err(UriError::UserInfo);
+            }
+            check(name, UriError::UserInfo, |b| is_unreserved(b) || b"&=+$,;?/".contains(&b))?;
+            if let Some(secret) = secret {
+                check(secret, UriError::UserInfo, |b| is_unreserved(b) || b"&=+$,".contains(&b))?;
+            }
+            user = Some(name);
+            password = secret;
+            rest = &rest[at + 1..];
+        }
+
+        let (host, after_host) = if let Some(reference) = rest.strip_prefix('[') {
+            let close = reference.find(']').ok_or(UriError::Host)?;
+            let address = &reference[..close];
+            if address.is_empty() ||
+               !address.bytes().all(|b| b.is_ascii_hexdigit() || b == b':' || b == b'.') {
+                return Err(UriError::Host);
+            }
+            (Host::Ipv6(address), &reference[close + 1..])
+        } else {
+            let end = rest.find([':', ';', '?']).unwrap_or(rest.len());
+            (host(&rest[..end])?, &rest[end..])
+        };
+
+        let (port, after_port) = match after_host.strip_prefix(':') {
+            Some(port) => {
+                let end = port.find([';', '?']).unwrap_or(port.len());
+                let digits = &port[..end];
+                if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(UriError::Port);
+                }
+                (Some(digits.parse().map_err(|_| UriError::Port)?), &port[end..])
+            }
+            None => (None, after_host),
+        };
+
+        let (params, headers) = match after_port.find('?') {
+            Some(question) => (&after_port[..question], &after_port[question + 1..]),
+            None => (after_port, ""),
+        };
+        if !params.is_empty() && !params.starts_with(';') {
+            return Err(UriError::Host);
+        }
+        check(params, UriError::Params, |b| is_param_char(b) || b == b';' || b == b'=')?;
+        check(headers, UriError::Headers, |b| {
+            is_unreserved(b) || b"[]/?:+$=&".contains(&b)
+        })?;
+
+        Ok(SipUri {
+            scheme,
+            user,
+            password,
+            host,
+            port,
+            params,
+            headers,
+        })
+    }
+
+    /// The parameters, as names and values, empty for parameters without
+    /// one such as `lr`.
+    pub fn params(&self) -> Params<'a> {
+        Params { rest: self.params.split(';') }
+    }
+
+    /// The value of the first parameter called `name`, compared
+    /// case-insensitively.
+    pub fn param(&self, name: &str) -> Option<&'a str> {
+        self.params().find(|&(param, _)| param.eq_ignore_ascii_case(name)).map(|(_, value)| value)
+    }
+
+    /// The headers, as names and values.
+    pub fn headers(&self) -> Headers<'a> {
+        Headers { rest: self.headers.split('&') }
+    }
+
+    /// Decodes the escaped sequences of the user into `dst`.
+    ///
+    /// Returns `None` if there is no user, or `dst` is too small.
+    pub fn user_into<'d>(&self, dst: &'d mut [u8]) -> Option<&'d [u8]> {
+        unescape_bytes_into(self.user?.as_bytes(), dst).ok()
+    }
+
+    /// Decodes the escaped sequences of the user into `dst`, as text.
+    ///
+    /// Returns `Ok(None)` if there is no user; see `escape::unescape_into`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use parsip::uri::SipUri;
+    ///
+    /// let uri = SipUri::parse("sip:%61lice@atlanta.com").unwrap();
+    /// let mut buf = [0u8; 16];
+    /// assert_eq!(uri.user_decoded(&mut buf), Ok(Some("alice")));
+    /// ```
+    pub fn user_decoded<'d>(&self, dst: &'d mut [u8]) -> Result<Option<&'d str>, EscapeError> {
+        self.user.map(move |user| unescape_into(user.as_bytes(), dst)).transpose()
+    }
+
+    /// Decodes the escaped sequences of the password into `dst`, as text.
+    ///
+    /// Returns `Ok(None)` if there is no password; see
+    /// `escape::unescape_into`.
+    pub fn password_decoded<'d>(&self, dst: &'d mut [u8])
+                                -> Result<Option<&'d str>, EscapeError> {
+        self.password.map(move |password| unescape_into(password.as_bytes(), dst)).transpose()
+    }
+
+    /// The port, or the default one of the scheme when not explicit.
+    #[inline]
+    pub fn port_or_default(&self) -> u16 {
+        match (self.port, self.scheme) {
+            (Some(port), _) => port,
+            (None, Scheme::Sip) => 5060,
+            (None, Scheme::Sips) => 5061,
+        }
+    }
+}
+
+/// A telephone number URI, as defined by
+/// [RFC 3966](https://tools.ietf.org/html/rfc3966).
+///
+/// > ```notrust
+/// > telephone-uri         =  "tel:" telephone-subscriber
+/// > telephone-subscriber  =  global-number / local-number
+/// > global-number         =  global-number-digits *par
+/// > local-number          =  local-number-digits *par context *par
+/// > par                   =  parameter / extension / isdn-subaddress
+/// > context               =  ";phone-context=" descriptor
+/// > descriptor            =  domainname / global-number-digits
+/// > ```
+///
+/// # Example
+///
+/// ```
+/// use parsip::uri::TelUri;
+///
+/// let uri = TelUri::parse("tel:7042;phone-context=example.com;ext=22").unwrap();
+/// assert_eq!((uri.number, uri.global), ("7042", false));
+/// assert_eq!(uri.phone_context(), Some("example.com"));
+/// assert_eq!(uri.ext(), Some("22"));
+///
+/// let uri = TelUri::parse("tel:+1-201-555-0123").unwrap();
+/// let mut buf = [0u8; 16];
+/// assert_eq!(uri.digits_into(&mut buf), Some(&b"+12015550123"[..]));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TelUri<'a> {
+    /// The number as written, with its visual separators, such as
+    /// `+1-201-555-0123`.
+    pub number: &'a str,
+    /// Whether the number is global, starting with `+`, rather than local
+    /// to its phone context.
+    pub global: bool,
+    /// The parameters with their leading `;`, such as `;ext=22`.
+    pub params: &'a str,
+}
+
+impl<'a> TelUri<'a> {
+    /// Parses a `tel:` URI.
+    pub fn parse(uri: &'a str) -> Result<TelUri<'a>, UriError> {
+        let colon = uri.find(':').ok_or(UriError::Scheme)?;
+        if !uri[..colon].eq_ignore_ascii_case("tel") {
+            return Err(UriError::Scheme);
+        }
+        let rest = &uri[colon + 1..];
+        let end = rest.find(';').unwrap_or(rest.len());
+        let (number, params) = rest.split_at(end);
+        let global = number.starts_with('+');
+        let valid = if global {
+            is_global_number(number)
+        } else {
+            number.bytes().any(|b| b.is_ascii_hexdigit() || b == b'*' || b == b'#') &&
+            number.bytes().all(|b| b.is_ascii_hexdigit() || b"*#-.()".contains(&b))
+        };
+        if !valid {
+            return Err(UriError::Number);
+        }
+        check(params, UriError::Params, |b| is_param_char(b) || b == b';' || b == b'=')?;
+
+        let uri = TelUri { number, global, params };
+        match uri.phone_context() {
+            Some(context) if is_global_number(context) || host(context).is_ok() => {}
+            Some(_) => return Err(UriError::PhoneContext),
+            None if !global => return Err(UriError::PhoneContext),
+            None => {}
+        }
+        if let Some(ext) = uri.ext() {
+            if !is_phone_digits(ext) {
+                return Err(UriError::Number);
+            }
+        }
+        if uri.isub() == Some("") {
+            return Err(UriError::Params);
+        }
+        Ok(uri)
+    }
+
+    /// The parameters, as names and values, empty for parameters without
+    /// one.
+    pub fn params(&self) -> Params<'a> {
+        Params { rest: self.params.split(';') }
+    }
+
+    /// The value of the first parameter called `name`, compared
+    /// case-insensitively.
+    pub fn param(&self, name: &str) -> Option<&'a str> {
+        self.params().find(|&(param, _)| param.eq_ignore_ascii_case(name)).map(|(_, value)| value)
+    }
+
+    /// The `phone-context` parameter, a domain name or global number
+    /// giving the scope of a local number.
+    #[inline]
+    pub fn phone_context(&self) -> Option<&'a str> {
+        self.param("phone-context")
+    }
+
+    /// The `ext` parameter, the extension.
+    #[inline]
+    pub fn ext(&self) -> Option<&'a str> {
+        self.param("ext")
+    }
+
+    /// The `isub` parameter, the ISDN subaddress.
+    #[inline]
+    pub fn isub(&self) -> Option<&'a str> {
+        self.param("isub")
+    }
+
+    /// Writes the number without its visual separators into `dst`.
+    ///
+    /// Returns `None` if `dst` is too small.
+    pub fn digits_into<'d>(&self, dst: &'d mut [u8]) -> Option<&'d [u8]> {
+        let mut len = 0;
+        for b in self.number.bytes().filter(|b| !b"-.()".contains(b)) {
+            *dst.get_mut(len)? = b;
+            len += 1;
+        }
+        Some(&dst[..len])
+    }
+}
+
+/// An iterator over the parameters of a `SipUri` or `TelUri`.
+#[derive(Clone, Debug)]
+pub struct Params<'a> {
+    rest: str::Split<'a, char>,
+}
+
+impl<'a> Iterator for Params<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<(&'a str, &'a str)> {
+        loop {
+            let param = self.rest.next()?;
+            if param.is_empty() {
+                continue;
+            }
+            return Some(match param.find('=') {
+                Some(eq) => (&param[..eq], &param[eq + 1..]),
+                None => (param, ""),
+            });
+        }
+    }
+}
+
+/// An iterator over the headers of a `SipUri`.
+#[derive(Clone, Debug)]
+pub struct Headers<'a> {
+    rest: str::Split<'a, char>,
+}
+
+impl<'a> Iterator for Headers<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<(&'a str, &'a str)> {
+        loop {
+            let header = self.rest.next()?;
+            if header.is_empty() {
+                continue;
+            }
+            return Some(match header.find('=') {
+                Some(eq) => (&header[..eq], &header[eq + 1..]),
+                None => (header, ""),
+            });
+        }
+    }
+}
+
+/// > ```notrust
+/// > global-number-digits  =  "+" *phonedigit DIGIT *phonedigit
+/// > ```
+fn is_global_number(number: &str) -> bool {
+    match number.strip_prefix('+') {
+        Some(digits) => digits.bytes().any(|b| b.is_ascii_digit()) && is_phone_digits(digits),
+        None => false,
+    }
+}
+
+/// > ```notrust
+/// > phonedigit        =  DIGIT / [ visual-separator ]
+/// > visual-separator  =  "-" / "." / "(" / ")"
+/// > ```
+fn is_phone_digits(digits: &str) -> bool {
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit() || b"-.()".contains(&b))
+}
+
+/// Parses a hostname or IPv4 address.
+fn host<'a>(host: &'a str) -> Result<Host<'a>, UriError> {
+    if host.is_empty() ||
+       !host.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'.') {
+        return Err(UriError::Host);
+    }
+    let is_ipv4 = host.split('.').count() == 4 &&
+                  host.split('.').all(|part| {
+        !part.is_empty() && part.len() <= 3 && part.bytes().all(|b| b.is_ascii_digit()) &&
+        part.parse::<u16>().is_ok_and(|n| n <= 255)
+    });
+    if is_ipv4 {
+        return Ok(Host::Ipv4(host));
+    }
+    // the top label of a domain name starts with a letter
+    let top = host.trim_end_matches('.').rsplit('.').next().unwrap_or("");
+    if !top.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return Err(UriError::Host);
+    }
+    Ok(Host::Domain(host))
+}
+
+/// Checks that `part` only holds bytes passing `allowed` and well-formed
+/// escaped sequences.
+fn check<F: Fn(u8) -> bool>(part: &str, error: UriError, allowed: F) -> Result<(), UriError> {
+    let bytes = part.as_bytes();
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'%' => {
+                match bytes.get(idx + 1..idx + 3) {
+                    Some([hi, lo]) if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() => idx += 3,
+                    _ => return Err(UriError::Escape),
+                }
+            }
+            b if allowed(b) => idx += 1,
+            _ => return Err(error),
+        }
+    }
+    Ok(())
+}
+
+/// > ```notrust
+/// > unreserved  =  alphanum / mark
+/// > mark        =  "-" / "_" / "." / "!" / "~" / "*" / "'" / "(" / ")"
+/// > ```
+#[inline]
+fn is_unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-_.!~*'()".contains(&b)
+}
+
+/// > ```notrust
+/// > paramchar         =  param-unreserved / unreserved / escaped
+/// > param-unreserved  =  "[" / "]" / "/" / ":" / "&" / "+" / "$"
+/// > ```
+#[inline]
+fn is_param_char(b: u8) -> bool {
+    is_unreserved(b) || b"[]/:&+$".contains(&b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Host, Scheme, SipUri, TelUri, Uri, UriError};
+    use escape::EscapeError;
+
+    #[test]
+    fn test_parse_minimal() {
+        let uri = SipUri::parse("sip:biloxi.com").unwrap();
+        assert_eq!(uri,
+                   SipUri {
+                       scheme: Scheme::Sip,
+                       user: None,
+                       password: None,
+                       host: Host::Domain("biloxi.com"),
+                       port: None,
+                       params: "",
+                       headers: "",
+                   });
+        assert_eq!(uri.port_or_default(), 5060);
+    }
+
+    #[test]
+    fn test_parse_full() {
+        let uri = SipUri::parse("SIP:+1-212-555-1212;npdi:p%40ss@192.0.2.4:5080;\
+                                 maddr=[2001:db8::2];user=phone?to=alice%40atlanta.com&x")
+            .unwrap();
+        assert_eq!(uri.scheme, Scheme::Sip);
+        assert_eq!(uri.user, Some("+1-212-555-1212;npdi"));
+        assert_eq!(uri.password, Some("p%40ss"));
+        assert_eq!(uri.host, Host::Ipv4("192.0.2.4"));
+        assert_eq!(uri.port, Some(5080));
+        assert_eq!(uri.params().collect::<Vec<_>>(),
+                   [("maddr", "[2001:db8::2]"), ("user", "phone")]);
+        assert_eq!(uri.param("USER"), Some("phone"));
+        assert_eq!(uri.headers().collect::<Vec<_>>(), [("to", "alice%40atlanta.com"), ("x", "")]);
+    }
+
+    #[test]
+    fn test_parse_escaped_user() {
+        let uri = SipUri::parse("sip:sip%3Auser@example.com").unwrap();
+        let mut buf = [0u8; 16];
+        assert_eq!(uri.user_into(&mut buf), Some(&b"sip:user"[..]));
+        assert_eq!(uri.user_decoded(&mut buf), Ok(Some("sip:user")));
+        assert_eq!(uri.password_decoded(&mut buf), Ok(None));
+        assert_eq!(uri.user_decoded(&mut buf[..4]), Err(EscapeError::BufferTooSmall));
+        let uri = SipUri::parse("sip:null-%00-null:%FF@example.com").unwrap();
+        assert_eq!(uri.user_decoded(&mut buf), Ok(Some("null-\0-null")));
+        assert_eq!(uri.password_decoded(&mut buf), Err(EscapeError::Utf8));
+        assert_eq!(SipUri::parse("sip:a%4@example.com"), Err(UriError::Escape));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(SipUri::parse("tel:+15555550100"), Err(UriError::Scheme));
+        assert_eq!(SipUri::parse("sip:@example.com"), Err(UriError::UserInfo));
+        assert_eq!(SipUri::parse("sip:a b@example.com"), Err(UriError::UserInfo));
+        assert_eq!(SipUri::parse("sip:bob@"), Err(UriError::Host));
+        assert_eq!(SipUri::parse("sip:bob@[::1"), Err(UriError::Host));
+        assert_eq!(SipUri::parse("sip:bob@example.123"), Err(UriError::Host));
+        assert_eq!(SipUri::parse("sip:bob@example.com:65536"), Err(UriError::Port));
+        assert_eq!(SipUri::parse("sip:bob@example.com:"), Err(UriError::Port));
+        assert_eq!(SipUri::parse("sip:bob@example.com;a<b"), Err(UriError::Params));
+        assert_eq!(SipUri::parse("sip:bob@example.com?a=<b>"), Err(UriError::Headers));
+    }
+
+    #[test]
+    fn test_parse_tel() {
+        let uri = TelUri::parse("TEL:+1(201)555-0123;isub=1411;ext=7;foo").unwrap();
+        assert_eq!(uri,
+                   TelUri {
+                       number: "+1(201)555-0123",
+                       global: true,
+                       params: ";isub=1411;ext=7;foo",
+                   });
+        assert_eq!((uri.isub(), uri.ext(), uri.phone_context()), (Some("1411"), Some("7"), None));
+        assert_eq!(uri.param("FOO"), Some(""));
+
+        let uri = TelUri::parse("tel:*86#;phone-context=+1-201").unwrap();
+        assert_eq!((uri.number, uri.global), ("*86#", false));
+        let mut buf = [0u8; 4];
+        assert_eq!(uri.digits_into(&mut buf), Some(&b"*86#"[..]));
+        assert_eq!(uri.digits_into(&mut buf[..3]), None);
+
+        assert_eq!(TelUri::parse("sip:+15555550100@example.com"), Err(UriError::Scheme));
+        assert_eq!(TelUri::parse("tel:+"), Err(UriError::Number));
+        assert_eq!(TelUri::parse("tel:+1 555"), Err(UriError::Number));
+        assert_eq!(TelUri::parse("tel:--"), Err(UriError::Number));
+        assert_eq!(TelUri::parse("tel:+1555;ext=x"), Err(UriError::Number));
+        assert_eq!(TelUri::parse("tel:+1555;isub="), Err(UriError::Params));
+        assert_eq!(TelUri::parse("tel:7042"), Err(UriError::PhoneContext));
+        assert_eq!(TelUri::parse("tel:7042;phone-context=1"), Err(UriError::PhoneContext));
+    }
+
+    #[test]
+    fn test_parse_any() {
+        assert!(matches!(Uri::parse("sip:bob@biloxi.com"), Ok(Uri::Sip(_))));
+        assert!(matches!(Uri::parse("SIPS:bob@biloxi.com"), Ok(Uri::Sips(_))));
+        assert!(matches!(Uri::parse("tel:+15555550100"), Ok(Uri::Tel(_))));
+        assert_eq!(Uri::parse("urn:service:sos"), Ok(Uri::Other("urn:service:sos")));
+        assert_eq!(Uri::parse("tel:12"), Err(UriError::PhoneContext));
+        assert_eq!(Uri::parse("1tel:12"), Err(UriError::Scheme));
+        assert_eq!(Uri::parse("biloxi.com"), Err(UriError::Scheme));
+    }
+
+    #[test]
+    fn test_parse_request_path() {
+        use sip::{Request, EMPTY_HEADER};
+
+        let mut headers = [EMPTY_HEADER; 1];
+        let mut req = Request::new(&mut headers);
+        req.parse(b"OPTIONS sips:[2001:db8::1]:5061 SIP/2.0\r\n\r\n").unwrap().unwrap();
+        let uri = SipUri::parse(req.path.unwrap()).unwrap();
+        assert_eq!(uri.host, Host::Ipv6("2001:db8::1"));
+        assert_eq!(uri.port_or_default(), 5061);
+    }
+}
@@ -0,0 +1,313 @@
+//! Checks of the requirements RFC 3261 puts on parsed messages, beyond
+//! their syntax.
+//!
+//! The parser accepts any well-formed message, so that proxies can relay
+//! what they do not understand. `Request::validate` and
+//! `Response::validate` check what a message must hold to be processed,
+//! reporting every problem found, for instance to answer a request with a
+//! precise `400 Bad Request`.
+//!
+//! # Example
+//!
+//! ```
+//! use parsip::validate::ValidationError;
+//!
+//! let buf = b"BYE sip:alice@pc33.atlanta.com SIP/2.0\r\n\
+//!             Via: SIP/2.0/UDP 192.0.2.4;branch=z9hG4bKnashds10\r\n\
+//!             To: <sip:alice@atlanta.com>;tag=1928301774\r\n\
+//!             From: <sip:bob@biloxi.com>;tag=a6c85cf\r\n\
+//!             CSeq: 231 INVITE\r\n\
+//!             \r\n";
+//! let mut headers = [parsip::EMPTY_HEADER; 8];
+//! let mut req = parsip::Request::new(&mut headers);
+//! req.parse(buf).unwrap();
+//!
+//! let errors = req.validate().unwrap_err();
+//! assert!(errors.contains(ValidationError::MissingHeader("Max-Forwards")));
+//! assert_eq!(errors.to_string(),
+//!            "missing Call-ID header, missing Max-Forwards header, \
+//!             CSeq method does not match");
+//! ```
+
+use std::fmt;
+use headers::typed::CSeq;
+use headers::{names_eq, Headers};
+use scan;
+use sip::{Header, Request, Response};
+
+/// A requirement a message fails.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    /// A mandatory header is missing; holds its name.
+    MissingHeader(&'static str),
+    /// The CSeq value is malformed.
+    CSeq,
+    /// The method of the CSeq differs from the request method.
+    CSeqMethod,
+    /// The message holds more than one Content-Length header.
+    MultipleContentLength,
+    /// A Content-Length value is malformed, or differs from the length of
+    /// the body.
+    ContentLength,
+}
+
+impl ValidationError {
+    /// A short description of the error.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            ValidationError::MissingHeader(_) => "missing mandatory header",
+            ValidationError::CSeq => "invalid CSeq",
+            ValidationError::CSeqMethod => "CSeq method does not match",
+            ValidationError::MultipleContentLength => "multiple Content-Length headers",
+            ValidationError::ContentLength => "invalid Content-Length",
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::MissingHeader(name) => write!(f, "missing {} header", name),
+            _ => f.write_str(self.description()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for ValidationError {}
+
+/// Every error, in the order of the checks.
+const CHECKS: [ValidationError; 10] = [ValidationError::MissingHeader("To"),
+                                       ValidationError::MissingHeader("From"),
+                                       ValidationError::MissingHeader("Call-ID"),
+                                       ValidationError::MissingHeader("CSeq"),
+                                       ValidationError::MissingHeader("Via"),
+                                       ValidationError::MissingHeader("Max-Forwards"),
+                                       ValidationError::CSeq,
+                                       ValidationError::CSeqMethod,
+                                       ValidationError::MultipleContentLength,
+                                       ValidationError::ContentLength];
+
+/// The errors found in a message, a set iterated in the order of the
+/// checks, without allocating.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ValidationErrors(u16);
+
+impl ValidationErrors {
+    #[inline]
+    fn insert(&mut self, error: ValidationError) {
+        let check = CHECKS.iter().position(|&check| check == error);
+        debug_assert!(check.is_some());
+        self.0 |= check.map_or(0, |check| 1 << check);
+    }
+
+    /// The number of errors.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Returns `true` if no error was found.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if `error` was found.
+    #[inline]
+    pub fn contains(&self, error: ValidationError) -> bool {
+        self.iter().any(|found| found == error)
+    }
+
+    /// Iterates over the errors.
+    #[inline]
+    pub fn iter(&self) -> Iter {
+        Iter { errors: self.0, check: 0 }
+    }
+
+    /// Succeeds if no error was found.
+    #[inline]
+    fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl IntoIterator for ValidationErrors {
+    type Item = ValidationError;
+    type IntoIter = Iter;
+
+    #[inline]
+    fn into_iter(self) -> Iter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for &ValidationErrors {
+    type Item = ValidationError;
+    type IntoIter = Iter;
+
+    #[inline]
+    fn into_iter(self) -> Iter {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, error) in self.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(", ")?;
+            }
+            fmt::Display::fmt(&error, f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for ValidationErrors {}
+
+/// An iterator over `ValidationErrors`.
+#[derive(Clone, Debug)]
+pub struct Iter {
+    errors: u16,
+    check: usize,
+}
+
+impl Iterator for Iter {
+    type Item = ValidationError;
+
+    fn next(&mut self) -> Option<ValidationError> {
+        while self.check < CHECKS.len() {
+            let check = self.check;
+            self.check += 1;
+            if self.errors & 1 << check != 0 {
+                return Some(CHECKS[check]);
+            }
+        }
+        None
+    }
+}
+
+/// The headers every request must hold, from
+/// [RFC 3261](https://tools.ietf.org/html/rfc3261#section-8.1.1).
+const REQUEST_HEADERS: [&str; 6] = ["To", "From", "Call-ID", "CSeq", "Via", "Max-Forwards"];
+
+/// The headers every response must hold, from
+/// [RFC 3261](https://tools.ietf.org/html/rfc3261#section-8.2.6.2).
+const RESPONSE_HEADERS: [&str; 5] = ["To", "From", "Call-ID", "CSeq", "Via"];
+
+impl<'h, 'b> Request<'h, 'b> {
+    /// Checks that the request holds the mandatory headers, that its CSeq
+    /// names its method and that its Content-Length, if any, is single
+    /// and matches the body.
+    ///
+    /// The body is only checked once set by `parse_message`.
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        check_headers(self.headers, &REQUEST_HEADERS, &mut errors);
+        let cseq = Headers::new(self.headers).get("CSeq");
+        match (cseq.map(CSeq::parse), self.method) {
+            (Some(Ok(cseq)), Some(method)) if cseq.method != method => {
+                errors.insert(ValidationError::CSeqMethod);
+            }
+            (Some(Err(_)), _) => errors.insert(ValidationError::CSeq),
+            _ => {}
+        }
+        check_content_length(self.headers, self.body, &mut errors);
+        errors.into_result()
+    }
+}
+
+impl<'h, 'b> Response<'h, 'b> {
+    /// Checks that the response holds the mandatory headers, that its CSeq
+    /// is well-formed and that its Content-Length, if any, is single and
+    /// matches the body.
+    ///
+    /// The body is only checked once set by `parse_message`.
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        check_headers(self.headers, &RESPONSE_HEADERS, &mut errors);
+        if let Some(Err(_)) = Headers::new(self.headers).get("CSeq").map(CSeq::parse) {
+            errors.insert(ValidationError::CSeq);
+        }
+        check_content_length(self.headers, self.body, &mut errors);
+        errors.into_result()
+    }
+}
+
+/// Reports the headers among `names` missing from `headers`.
+fn check_headers(headers: &[Header], names: &[&'static str], errors: &mut ValidationErrors) {
+    let headers = Headers::new(headers);
+    for &name in names {
+        if headers.get(name).is_none() {
+            errors.insert(ValidationError::MissingHeader(name));
+        }
+    }
+}
+
+/// Reports duplicate Content-Length headers, and values that are malformed
+/// or, once the body is known, differ from its length.
+fn check_content_length(headers: &[Header],
+                        body: Option<&[u8]>,
+                        errors: &mut ValidationErrors) {
+    let mut values = headers.iter()
+        .filter(|header| names_eq(header.name, "Content-Length"))
+        .map(|header| header.value.as_bytes());
+    if values.clone().nth(1).is_some() {
+        errors.insert(ValidationError::MultipleContentLength);
+    }
+    match scan::content_length(&mut values) {
+        Ok(Some(len)) if body.is_none_or(|body| body.len() == len) => {}
+        Ok(None) => {}
+        _ => errors.insert(ValidationError::ContentLength),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sip::{Request, Response, EMPTY_HEADER};
+    use super::ValidationError;
+
+    #[test]
+    fn test_valid_request() {
+        let buf = b"MESSAGE sip:bob@biloxi.com SIP/2.0\r\nv: SIP/2.0/UDP a\r\nt: <sip:b>\r\n\
+                    f: <sip:a>;tag=1\r\ni: 1\r\nCSeq: 1 MESSAGE\r\nMax-Forwards: 70\r\n\
+                    l: 2\r\n\r\nhi";
+        let mut headers = [EMPTY_HEADER; 8];
+        let mut req = Request::new(&mut headers);
+        req.parse_message(buf).unwrap();
+        assert_eq!(req.validate(), Ok(()));
+
+        req.body = Some(b"h");
+        let errors: Vec<_> = req.validate().unwrap_err().into_iter().collect();
+        assert_eq!(errors, [ValidationError::ContentLength]);
+    }
+
+    #[test]
+    fn test_invalid_response() {
+        let buf = b"SIP/2.0 200 OK\r\nVia: SIP/2.0/UDP a\r\nTo: <sip:b>\r\nFrom: <sip:a>\r\n\
+                    CSeq: INVITE\r\nContent-Length: 0\r\nContent-Length: 0\r\n\r\n";
+        let mut headers = [EMPTY_HEADER; 8];
+        let mut res = Response::new(&mut headers);
+        res.parse_message(buf).unwrap();
+        let errors = res.validate().unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors.iter().collect::<Vec<_>>(),
+                   [ValidationError::MissingHeader("Call-ID"),
+                    ValidationError::CSeq,
+                    ValidationError::MultipleContentLength]);
+        assert_eq!(errors.to_string(),
+                   "missing Call-ID header, invalid CSeq, multiple Content-Length headers");
+    }
+}
@@ -1,12 +1,12 @@
 extern crate parsip;
 
-use parsip::{IResult, Request, Header, EMPTY_HEADER, SipVersion};
+use parsip::{Request, Header, EMPTY_HEADER, SipVersion, Status};
 
 const NUM_OF_HEADERS: usize = 32;
 
 macro_rules! req {
     ($name:ident, $buf:expr, |$arg:ident| $body:expr) => (
-        req! {$name, $buf, |buf| IResult::Done(&buf[buf.len()..], buf.len()), |$arg| $body }
+        req! {$name, $buf, |buf| Ok(Status::Complete(buf.len())), |$arg| $body }
     );
     ($name:ident, $buf:expr, |$res_arg:ident| $res_body:expr, |$arg:ident| $body:expr) => (
     #[test]
@@ -17,7 +17,7 @@ macro_rules! req {
         assert_eq!(result, res_closure($buf));
         closure(req);
 
-        fn res_closure($res_arg: &[u8]) -> IResult<&[u8], usize> {
+        fn res_closure($res_arg: &[u8]) -> parsip::Result<usize> {
             $res_body
         }
 
@@ -33,17 +33,17 @@ fn print_headers(headers: &[Header]) {
     for header in headers.iter() {
         println!("Header {{ name: {:?}, value: {:?} }}",
                  header.name,
-                 std::str::from_utf8(header.value).unwrap());
+                 std::str::from_utf8(&header.value).unwrap());
     }
 }
 
-/// From [RFC 4475](https://tools.ietf.org/html/rfc4475#section-3.1.1.1):
-/// 3.1.1.  Valid Messages
-/// 3.1.1.1.  A Short Tortuous INVITE
+// From [RFC 4475](https://tools.ietf.org/html/rfc4475#section-3.1.1.1):
+// 3.1.1.  Valid Messages
+// 3.1.1.1.  A Short Tortuous INVITE
 req! {
     test_request_short_tortuous_invite,
     include_bytes!("requests/wsinv.dat"),
-    |buf| IResult::Done(&buf[857..], 857),
+    |_buf| Ok(Status::Complete(857)),
     |req| {
         assert_eq!(req.method.unwrap(), "INVITE");
         assert_eq!(req.path.unwrap(), "sip:vivekg@chair-dnrc.example.com;unknownparam");
@@ -53,13 +53,13 @@ req! {
     }
 }
 
-/// From [RFC 4475](https://tools.ietf.org/html/rfc4475#section-3.1.1.2):
-/// 3.1.1.  Valid Messages
-/// 3.1.1.2.  Wide Range of Valid Characters
+// From [RFC 4475](https://tools.ietf.org/html/rfc4475#section-3.1.1.2):
+// 3.1.1.  Valid Messages
+// 3.1.1.2.  Wide Range of Valid Characters
 req! {
     test_request_wide_range_of_valid_characters,
     include_bytes!("requests/intmeth.dat"),
-    |buf| IResult::Done(&buf[681..], 681),
+    |_buf| Ok(Status::Complete(681)),
     |req| {
         assert_eq!(req.method.unwrap(), "!interesting-Method0123456789_*+`.%indeed\'~");
         assert_eq!(req.path.unwrap(),
@@ -71,13 +71,13 @@ req! {
     }
 }
 
-/// From [RFC 4475](https://tools.ietf.org/html/rfc4475#section-3.1.1.3):
-/// 3.1.1.  Valid Messages
-/// 3.1.1.3.  Valid Use of the % Escaping Mechanism
+// From [RFC 4475](https://tools.ietf.org/html/rfc4475#section-3.1.1.3):
+// 3.1.1.  Valid Messages
+// 3.1.1.3.  Valid Use of the % Escaping Mechanism
 req! {
     test_request_valid_use_of_the_percent_escaping_mechanism,
     include_bytes!("requests/esc01.dat"),
-    |buf| IResult::Done(&buf[409..], 409),
+    |_buf| Ok(Status::Complete(409)),
     |req| {
         assert_eq!(req.method.unwrap(), "INVITE");
         assert_eq!(req.path.unwrap(), "sip:sips%3Auser%40example.com@example.net");
@@ -87,13 +87,13 @@ req! {
     }
 }
 
-/// From [RFC 4475](https://tools.ietf.org/html/rfc4475#section-3.1.1.4):
-/// 3.1.1.  Valid Messages
-/// 3.1.1.4.  Escaped Nulls in URIs
+// From [RFC 4475](https://tools.ietf.org/html/rfc4475#section-3.1.1.4):
+// 3.1.1.  Valid Messages
+// 3.1.1.4.  Escaped Nulls in URIs
 req! {
     test_request_escaped_nulls_in_uris,
     include_bytes!("requests/escnull.dat"),
-    |buf| IResult::Done(&buf[365..], 365),
+    |_buf| Ok(Status::Complete(365)),
     |req| {
         assert_eq!(req.method.unwrap(), "REGISTER");
         assert_eq!(req.path.unwrap(), "sip:example.com");
@@ -103,13 +103,13 @@ req! {
     }
 }
 
-/// From [RFC 4475](https://tools.ietf.org/html/rfc4475#section-3.1.1.5):
-/// 3.1.1.  Valid Messages
-/// 3.1.1.5.  Use of % When It Is Not an Escape
+// From [RFC 4475](https://tools.ietf.org/html/rfc4475#section-3.1.1.5):
+// 3.1.1.  Valid Messages
+// 3.1.1.5.  Use of % When It Is Not an Escape
 req! {
     test_request_use_of_percent_when_it_is_not_an_escape,
     include_bytes!("requests/esc02.dat"),
-    |buf| IResult::Done(&buf[445..], 445),
+    |_buf| Ok(Status::Complete(445)),
     |req| {
         assert_eq!(req.method.unwrap(), "RE%47IST%45R");
         assert_eq!(req.path.unwrap(), "sip:registrar.example.com");